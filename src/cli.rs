@@ -0,0 +1,217 @@
+use crate::config::Config;
+use crate::connection::{download, Connection, OnlineConnection};
+use crate::credentials::{backend, CredentialBackend, CredentialStore, FileStore, KeyringStore};
+use crate::model::EntryType;
+use crate::server::Server;
+use crate::utils::{check_writable, directory_str_to_url, save_as};
+use std::error::Error;
+use structopt::StructOpt;
+use url::Url;
+
+/// Command line arguments. Without any of them, ncopds starts the interactive interface.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "ncopds", about = "A TUI program for navigating OPDS catalogs.")]
+pub struct Opt {
+    /// Downloads the file at this URL into the download directory and exits
+    #[structopt(long)]
+    pub download: Option<Url>,
+    /// Prints the entries of the feed at this URL and exits
+    #[structopt(long)]
+    pub list: Option<Url>,
+    /// Name of the connection in the config file whose credentials should be used
+    #[structopt(short, long)]
+    pub connection: Option<String>,
+}
+
+impl Opt {
+    /// Whether the arguments ask for a non-interactive run.
+    pub fn is_batch(&self) -> bool {
+        self.download.is_some() || self.list.is_some()
+    }
+}
+
+/// Finds the configured server the URL belongs to. Uses the connection named on the command line
+/// if there is one, otherwise the first configured server on the same host as the URL.
+///
+/// # Arguments
+///
+/// * `config` - Config struct
+/// * `name` - Connection name given on the command line
+/// * `url` - URL that is being requested
+///
+/// # Errors
+///
+/// Errors if a connection was named but does not exist in the config.
+///
+fn find_server(
+    config: &Config,
+    name: &Option<String>,
+    url: &Url,
+) -> Result<Option<(String, Server)>, Box<dyn Error>> {
+    let servers = config.servers.clone().unwrap_or_default();
+
+    if let Some(n) = name {
+        return match servers.get(n) {
+            Some(s) => Ok(Some((n.clone(), s.clone()))),
+            None => Err(format!("No connection named {} in the config file.", n).into()),
+        };
+    }
+
+    Ok(servers
+        .into_iter()
+        .find(|(_, s)| s.base_url.host_str() == url.host_str()))
+}
+
+/// Opens the credential store picked in the config. The passphrase for the encrypted file is read
+/// from standard input.
+///
+/// # Arguments
+///
+/// * `config` - Config struct
+///
+fn credential_store(config: &Config) -> Result<Box<dyn CredentialStore>, Box<dyn Error>> {
+    match backend(config.credential_store) {
+        CredentialBackend::Keyring => Ok(Box::new(KeyringStore)),
+        CredentialBackend::File => {
+            let passphrase = prompt("Passphrase for saved passwords: ")?;
+            let store = FileStore::open(&config.credentials_path(), &passphrase)?;
+            Ok(Box::new(store))
+        }
+    }
+}
+
+/// Asks for a line on standard input.
+fn prompt(label: &str) -> std::io::Result<String> {
+    eprint!("{}", label);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end().to_string())
+}
+
+/// Connects to a server, reading its password from the credential store.
+///
+/// # Arguments
+///
+/// * `name` - Name of the connection, used in error messages
+/// * `server` - Server to connect to
+/// * `config` - Config struct
+///
+async fn connect(
+    name: &str,
+    server: &Server,
+    config: &Config,
+) -> Result<OnlineConnection, Box<dyn Error>> {
+    // only servers with a username have a password to look up
+    let password = match &server.username {
+        // nothing is saved, so the password has to be given every time
+        Some(u) if !config.store_passwords => Some(prompt(&format!(
+            "Password for {}@{}: ",
+            u, server.base_url
+        ))?),
+        Some(_) => server
+            .get_password(credential_store(config)?.as_ref())
+            .map_err(|e| match e {
+                keyring::Error::NoEntry => format!(
+                    "No password stored for {}. Run ncopds interactively once to enter it.",
+                    name
+                ),
+                other => format!("Could not retrieve password for {}: {}", name, other),
+            })?,
+        None => None,
+    };
+
+    println!("Connecting to {}...", server.base_url);
+    OnlineConnection::new(server, client()?, password).await
+}
+
+fn client() -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder().user_agent("ncopds").build()
+}
+
+/// Prints one line per entry, followed by indented lines for its links.
+fn print_entries(entries: &[EntryType]) {
+    for entry in entries {
+        match entry {
+            EntryType::OPDSEntry(data) => {
+                match &data.author {
+                    Some(a) => println!("{} - {}", data.title, a),
+                    None => println!("{}", data.title),
+                }
+
+                if let Some(href) = &data.href {
+                    println!("    browse: {}", href);
+                }
+
+                for (href, mt, _) in &data.downloads {
+                    println!("    {}: {}", mt, href);
+                }
+            }
+            EntryType::Header(title) => println!("{}:", title),
+            EntryType::More(_) => {}
+            EntryType::File(title, url, _) | EntryType::Directory(title, url, _) => {
+                println!("{} ({})", title, url);
+            }
+        }
+    }
+}
+
+/// Runs ncopds without the interface, downloading a file or listing a feed depending on the
+/// arguments.
+///
+/// # Arguments
+///
+/// * `opt` - Parsed command line arguments
+/// * `config` - Config struct
+///
+/// # Errors
+///
+/// Any error that stops the download or listing; the caller is expected to report it and exit
+/// with a non-zero code.
+///
+pub async fn run(opt: Opt, config: Config) -> Result<(), Box<dyn Error>> {
+    for warning in config.header_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if let Some(url) = &opt.list {
+        // without a matching connection, the feed itself is treated as the catalog root
+        let (name, server) = find_server(&config, &opt.connection, url)?
+            .unwrap_or((url.to_string(), Server::new(url.clone())));
+
+        let mut conn = connect(&name, &server, &config).await?;
+        let entries = conn.get_page(url).await?;
+        print_entries(&entries);
+    }
+
+    if let Some(url) = &opt.download {
+        let download_directory = directory_str_to_url(&config.download_directory)?;
+        check_writable(&download_directory.to_file_path().unwrap())?;
+
+        let res = match find_server(&config, &opt.connection, url)? {
+            Some((name, server)) => {
+                let conn = connect(&name, &server, &config).await?;
+                println!("Downloading {}...", url);
+                conn.download(url).await?
+            }
+            None => {
+                println!("Downloading {}...", url);
+                // servers on this machine are trusted even without a connection for them
+                let client = Server::new(url.clone()).client(&client()?)?;
+                download(client.get(url.to_string()), url, None).await?
+            }
+        };
+
+        let (fname, data) = res;
+        let (_, warning) = save_as(data, &download_directory, &fname, config.verify_downloads)?;
+        if let Some(warning) = warning {
+            eprintln!("{}", warning);
+        }
+        println!(
+            "Saved {} to {}",
+            fname,
+            download_directory.to_file_path().unwrap().display()
+        );
+    }
+
+    Ok(())
+}
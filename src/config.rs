@@ -1,3 +1,4 @@
+use crate::credentials::CredentialBackend;
 use crate::server::Server;
 use crate::Error;
 use serde_derive::{Deserialize, Serialize};
@@ -5,16 +6,237 @@ use std::collections::HashMap;
 use std::env;
 use std::fs::{create_dir_all, read_to_string, File};
 use std::io::{ErrorKind, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use toml;
+use url::Url;
 
 // this is joined with $HOME when the program first launches
 pub const CONFIG_DIRECTORY: &str = "/.config/ncopds/";
 
+// this is joined with $XDG_DATA_HOME, or $HOME/.local/share without it; deleted files are moved
+// there when use_trash is set, away from the download directory so they are not listed in it
+pub const TRASH_DIRECTORY: &str = "/ncopds/trash/";
+
+/// How the file view and the details panel are arranged on screen.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// file view on the left, details on the right
+    #[default]
+    Horizontal,
+    /// file view on top, details below; suits tall, narrow terminals
+    Vertical,
+}
+
+/// How covers are drawn in the details panel.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderBackend {
+    /// one colored cell per pixel; works in every terminal
+    Ascii,
+    /// sixel graphics, for terminals that support them such as xterm, mlterm and foot
+    Sixel,
+}
+
+/// What happens when a download's contents do not match the extension it is saved under.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyDownloads {
+    /// the download is not saved
+    Strict,
+    /// the download is saved and the user is told about the mismatch
+    #[default]
+    Warn,
+    /// contents are not checked
+    Off,
+}
+
+/// What happens to entry titles that are wider than the file view.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleOverflow {
+    /// the file view scrolls sideways
+    #[default]
+    Scroll,
+    /// titles are cut off with "…"; the full title is shown in the details panel
+    Ellipsize,
+    /// titles go on over as many lines as they need
+    Wrap,
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 pub struct Config {
     pub download_directory: String,
+    /// whether connections should reopen the page they were on when ncopds was last closed
+    #[serde(default)]
+    pub restore_last_page: bool,
+    /// whether deleted files are moved to the trash folder instead of being removed
+    #[serde(default)]
+    pub use_trash: bool,
+    /// where logs are written; defaults to ncopds.log inside the config directory
+    pub log_file: Option<String>,
+    /// most verbose level written to the log, i.e. "debug"; RUST_LOG takes precedence
+    pub log_level: Option<String>,
+    /// size in bytes after which the log file is rotated
+    #[serde(default = "default_max_log_size")]
+    pub max_log_size: u64,
+    /// whether searching the download directory also looks inside subdirectories
+    #[serde(default)]
+    pub recursive_search: bool,
+    /// how many levels of subdirectories a recursive search may visit
+    #[serde(default = "default_search_depth")]
+    pub max_search_depth: usize,
+    /// width of covers in the side panel, in characters; fills the panel if missing
+    pub cover_size: Option<usize>,
+    /// how covers are drawn; picked from what the terminal supports if not set
+    pub render_backend: Option<RenderBackend>,
+    /// built-in theme picked from the Theme menu, i.e. "dark"; theme.toml is applied on top
+    pub theme: Option<String>,
+    /// connection switched to once it has connected at startup, instead of the one open when
+    /// ncopds was last closed; "local" stays on the download directory
+    pub default_connection: Option<String>,
+    /// whether the details panel is next to ("horizontal") or below ("vertical") the file view
+    #[serde(default)]
+    pub layout: Layout,
+    /// share of the screen given to the file view, between 0.2 and 0.8
+    #[serde(default = "default_panel_ratio")]
+    pub panel_ratio: f32,
+    /// how titles wider than the file view are shown, "scroll" or "ellipsize"
+    #[serde(default)]
+    pub title_overflow: TitleOverflow,
+    /// formats picked by the quick download action, most preferred first, i.e. ["epub", "pdf"]
+    #[serde(default)]
+    pub preferred_formats: Vec<String>,
+    /// seconds between health checks of every server; 0 turns them off
+    #[serde(default = "default_ping_interval")]
+    pub ping_interval: u32,
+    /// times per second the screen is redrawn while nothing happens; 0 only redraws it when
+    /// something changes
+    #[serde(default = "default_redraw_rate")]
+    pub redraw_rate: u32,
+    /// whether passwords are saved between sessions at all
+    #[serde(default = "default_store_passwords")]
+    pub store_passwords: bool,
+    /// where passwords are stored, "keyring" or "file"; picked automatically if missing
+    pub credential_store: Option<CredentialBackend>,
+    /// how many downloads run at the same time; the rest wait for their turn
+    #[serde(default = "default_max_downloads")]
+    pub max_downloads: usize,
+    /// what happens when a download is not the kind of file its extension says, "strict", "warn"
+    /// or "off"
+    #[serde(default)]
+    pub verify_downloads: VerifyDownloads,
+    /// whether connections keep what the server sent for the last page, to be shown for debugging
+    #[serde(default)]
+    pub keep_raw_feeds: bool,
+    /// whether download links are checked with a HEAD request before the download menu is shown
+    #[serde(default)]
+    pub verify_formats: bool,
+    /// how many entries of a page are shown before a "Show more" entry; 0 shows them all
+    #[serde(default = "default_max_visible_entries")]
+    pub max_visible_entries: usize,
     pub servers: Option<HashMap<String, Server>>,
+    /// commands files are opened with, keyed by extension, i.e. epub = "foliate {}"; other files
+    /// are opened with the OS default
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub open_commands: HashMap<String, String>,
+    /// last visited page for each connection, keyed by connection name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub last_pages: HashMap<String, Url>,
+}
+
+fn default_search_depth() -> usize {
+    5
+}
+
+fn default_ping_interval() -> u32 {
+    60
+}
+
+fn default_redraw_rate() -> u32 {
+    30
+}
+
+fn default_panel_ratio() -> f32 {
+    0.5
+}
+
+fn default_max_downloads() -> usize {
+    3
+}
+
+fn default_max_visible_entries() -> usize {
+    500
+}
+
+fn default_store_passwords() -> bool {
+    true
+}
+
+fn default_max_log_size() -> u64 {
+    1024 * 1024
+}
+
+impl Config {
+    /// Returns the path of the encrypted credentials file.
+    pub fn credentials_path(&self) -> PathBuf {
+        let home = env::var("HOME").unwrap().to_string();
+        PathBuf::from(format!("{}{}{}", home, CONFIG_DIRECTORY, "credentials"))
+    }
+
+    /// Returns the path of the session file.
+    pub fn session_path(&self) -> PathBuf {
+        let home = env::var("HOME").unwrap().to_string();
+        PathBuf::from(format!("{}{}{}", home, CONFIG_DIRECTORY, "session.json"))
+    }
+
+    /// Returns the folder deleted files are moved to when use_trash is set.
+    pub fn trash_path(&self) -> PathBuf {
+        let data = env::var("XDG_DATA_HOME")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .unwrap_or(format!("{}/.local/share", env::var("HOME").unwrap()));
+        PathBuf::from(format!("{}{}", data, TRASH_DIRECTORY))
+    }
+
+    /// Returns the path logs should be written to.
+    pub fn log_path(&self) -> PathBuf {
+        match &self.log_file {
+            Some(f) => PathBuf::from(f),
+            None => {
+                let home = env::var("HOME").unwrap().to_string();
+                PathBuf::from(format!("{}{}{}", home, CONFIG_DIRECTORY, "ncopds.log"))
+            }
+        }
+    }
+
+    /// Removes a connection's server and the page it was last on. Returns the server, if it was
+    /// configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection.
+    ///
+    pub fn remove_server(&mut self, name: &str) -> Option<Server> {
+        self.last_pages.remove(name);
+        self.servers.as_mut()?.remove(name)
+    }
+
+    /// Lists the extra headers of each connection that cannot be sent. They stay in the config
+    /// file but are skipped when requests are made.
+    pub fn header_warnings(&self) -> Vec<String> {
+        let mut servers: Vec<_> = self.servers.iter().flatten().collect();
+        servers.sort_by(|a, b| a.0.cmp(b.0));
+        servers
+            .into_iter()
+            .flat_map(|(name, server)| {
+                server
+                    .invalid_headers()
+                    .into_iter()
+                    .map(move |problem| format!("Skipping header of {}: {}", name, problem))
+            })
+            .collect()
+    }
 }
 
 /// Creates a default config at the path specified. All it contains is a line for the download
@@ -28,10 +250,7 @@ pub fn create_default_config(file_path: &Path) -> Result<File, std::io::Error> {
     // test
     create_dir_all(file_path.parent().unwrap())?;
 
-    let mut fc = match File::create(file_path) {
-        Ok(f) => f,
-        Err(e) => return Err(e),
-    };
+    let mut fc = File::create(file_path)?;
 
     let home = env::var("HOME").unwrap().to_string();
 
@@ -48,7 +267,7 @@ pub fn create_default_config(file_path: &Path) -> Result<File, std::io::Error> {
 pub fn write_to_config(config: &Config, file_path: &Path) -> Result<(), Box<dyn Error>> {
     // add test, rename?
     let s = toml::ser::to_string(config)?;
-    let mut file = File::options().write(true).open(file_path)?;
+    let mut file = File::options().write(true).truncate(true).open(file_path)?;
     file.write_all(s.as_bytes())?;
     Ok(())
 }
@@ -76,3 +295,118 @@ pub fn read_config(file_path: &Path) -> Result<Config, Box<dyn Error>> {
     let config: Config = toml::from_str(&contents)?;
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsendable_headers_are_warned_about() {
+        let config: Config = toml::from_str(
+            r#"
+download_directory = "/tmp/books/"
+
+[servers.kavita]
+base_url = "http://kavita.lan/api/opds"
+
+[servers.kavita.headers]
+"X Api Key" = "abc"
+X-Token = "line\nbreak"
+X-Fine = "ok"
+
+[servers.calibre]
+base_url = "http://calibre.lan/opds"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.header_warnings(),
+            [
+                "Skipping header of kavita: \"X Api Key\" is not a valid header name",
+                "Skipping header of kavita: the value of X-Token is not a valid header value",
+            ]
+        );
+    }
+
+    #[test]
+    fn servers_with_headers_are_written_back() {
+        let mut config: Config = toml::from_str(
+            r#"
+download_directory = "/tmp/books/"
+
+[servers.kavita]
+base_url = "https://kavita.lan/api/opds"
+accept_invalid_certs = true
+
+[servers.kavita.headers]
+X-Api-Key = "abc"
+"#,
+        )
+        .unwrap();
+        config.last_pages.insert(
+            "kavita".to_string(),
+            Url::parse("https://kavita.lan/api/opds/new").unwrap(),
+        );
+
+        let path = env::temp_dir().join(format!("ncopds-config-{}.toml", std::process::id()));
+        File::create(&path).unwrap();
+        write_to_config(&config, &path).unwrap();
+        let read = read_config(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let kavita = &read.servers.unwrap()["kavita"];
+        assert_eq!(kavita.accept_invalid_certs, Some(true));
+        assert_eq!(kavita.headers["X-Api-Key"], "abc");
+        assert_eq!(read.last_pages, config.last_pages);
+    }
+
+    #[test]
+    fn every_title_overflow_is_read() {
+        for (value, overflow) in [
+            ("wrap", TitleOverflow::Wrap),
+            ("ellipsize", TitleOverflow::Ellipsize),
+            ("scroll", TitleOverflow::Scroll),
+        ] {
+            let config: Config = toml::from_str(&format!(
+                "download_directory = \"/tmp/books/\"\ntitle_overflow = \"{}\"",
+                value
+            ))
+            .unwrap();
+            assert_eq!(config.title_overflow, overflow);
+        }
+
+        let config: Config = toml::from_str("download_directory = \"/tmp/books/\"").unwrap();
+        assert_eq!(config.title_overflow, TitleOverflow::Scroll);
+    }
+
+    #[test]
+    fn removed_servers_leave_nothing_behind() {
+        let mut config: Config = toml::from_str(
+            r#"
+download_directory = "/tmp/books/"
+
+[servers.calibre]
+base_url = "http://calibre.lan/opds"
+
+[servers.kavita]
+base_url = "http://kavita.lan/api/opds"
+
+[last_pages]
+calibre = "http://calibre.lan/opds/new"
+kavita = "http://kavita.lan/api/opds/series"
+"#,
+        )
+        .unwrap();
+
+        let removed = config.remove_server("calibre").unwrap();
+        assert_eq!(removed.base_url.as_str(), "http://calibre.lan/opds");
+
+        let servers = config.servers.as_ref().unwrap();
+        assert!(!servers.contains_key("calibre"));
+        assert!(servers.contains_key("kavita"));
+        assert!(!config.last_pages.contains_key("calibre"));
+        assert!(config.last_pages.contains_key("kavita"));
+        assert!(config.remove_server("calibre").is_none());
+    }
+}
@@ -1,16 +1,24 @@
-use crate::model::{get_title_for_entry, process_opds_entry, EntryType};
+use crate::model::{
+    download_filename, get_title_for_entry, process_opds_entry, EntryType, Facet, FeedLinks,
+    FeedTitle, FileInfo, Paging,
+};
 use crate::server::Server;
-use crate::utils::{parse_href, read_dir};
+use crate::utils::{comic_cover, natural_cmp, parse_href, read_dir};
 
 use async_trait::async_trait;
 use atom_syndication::Feed;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use reqwest::{Method, StatusCode};
 use roxmltree::Document;
+use serde_derive::Deserialize;
 use std::any::Any;
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::vec::Vec;
 use url::Url;
 
@@ -24,17 +32,210 @@ pub trait Connection: Send {
     async fn navigate_to(&mut self, s: &Url) -> Result<Vec<EntryType>, Box<dyn Error>>;
     /// pops a page off of the history stack and returns the contents of the previous page
     async fn back(&mut self) -> Result<Vec<EntryType>, Box<dyn Error>>;
+    /// empties the history stack, which puts the connection back at its root
+    fn clear_history(&mut self);
     /// gets data from the image at the URL
     async fn get_image_bytes(&self, addr: &Url) -> Bytes;
     /// uses the connection's search capabilities to run a search
     async fn search(&mut self, query: &str) -> Result<Vec<EntryType>, Box<dyn Error>>;
+    /// forgets any cached content for the URL so the next get_page fetches it again
+    fn invalidate(&mut self, _addr: &Url) {}
+    /// forgets every cached page
+    fn clear_cache(&mut self) {}
+    /// whether requests are made without credentials
+    fn anonymous(&self) -> bool {
+        false
+    }
+    /// makes requests without credentials (true) or with them again (false)
+    fn set_anonymous(&mut self, _anonymous: bool) -> Result<(), Box<dyn Error>> {
+        Err("Only catalogs can be browsed anonymously.".into())
+    }
+    /// loads the next or previous page of the search results being shown; returns the query
+    /// along with the entries
+    async fn turn_search_page(
+        &mut self,
+        _forward: bool,
+    ) -> Result<(String, Vec<EntryType>), Box<dyn Error>> {
+        Err("Search results cannot be paged here.".into())
+    }
+    /// OpenSearch paging hints of the page at the URL, if it had any
+    fn paging(&self, _addr: &Url) -> Option<Paging> {
+        None
+    }
+    /// title and subtitle of the feed at the URL, if it had a title
+    fn feed_title(&self, _addr: &Url) -> Option<FeedTitle> {
+        None
+    }
+    /// parent and root feeds the page at the URL links to, if it had either
+    fn feed_links(&self, _addr: &Url) -> Option<FeedLinks> {
+        None
+    }
+    /// facets the page at the URL can be narrowed with
+    fn facets(&self, _addr: &Url) -> Vec<Facet> {
+        vec![]
+    }
+    /// goes to the page narrowed by a facet of the current page; search results stay search
+    /// results for the same query
+    async fn apply_facet(&mut self, _facet: &Facet) -> Result<Vec<EntryType>, Box<dyn Error>> {
+        Err("Only catalogs have facets.".into())
+    }
+    /// the query whose results are at the URL and the facet they were narrowed with, if the page
+    /// holds search results
+    fn search_query(&self, _addr: &Url) -> Option<(String, Option<String>)> {
+        None
+    }
+    /// when a page was last fetched from its source rather than the cache, if ever
+    fn last_refresh(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+    /// returns the cached content for the URL without fetching it, if there is any
+    fn cached_page(&self, _addr: &Url) -> Option<Vec<EntryType>> {
+        None
+    }
+    /// URL and body of the last page fetched from the server, if the connection keeps them
+    fn raw_page(&self) -> Option<(Url, String)> {
+        None
+    }
     fn as_any(&self) -> &dyn Any;
 }
 
+/// URL the links of a feed are relative to: its `xml:base` if it sets one, where it was
+/// retrieved from otherwise.
+fn feed_base(doc: &Feed, feed_url: &Url) -> Url {
+    doc.base()
+        .and_then(|b| parse_href(b, feed_url).ok())
+        .unwrap_or(feed_url.clone())
+}
+
+/// Converts every entry of a feed, resolving relative links against the `xml:base` of the entry
+/// or the feed if they set one, and against `feed_url` otherwise.
+///
+/// # Arguments
+///
+/// * `doc` - Parsed feed.
+/// * `xml` - The feed's XML tree, needed for the `xml:base` of entries which atom_syndication
+///   does not keep. None if the document could not be parsed as XML.
+/// * `feed_url` - URL the feed was retrieved from.
+/// * `on_progress` - Called with the number of entries converted so far and the total, every
+///   `PROGRESS_STEP` entries of feeds with at least `PROGRESS_MIN_ENTRIES` entries.
+///
+/// # Errors
+///
+/// Errors if an entry contains a link that cannot be parsed.
+///
+fn process_feed(
+    doc: &Feed,
+    xml: Option<&Document>,
+    feed_url: &Url,
+    on_progress: &dyn Fn(usize, usize),
+) -> Result<Vec<EntryType>, Box<dyn Error>> {
+    let feed_base = feed_base(doc, feed_url);
+    let entry_bases = xml.map(entry_bases).unwrap_or_default();
+
+    let total = doc.entries().len();
+    let mut entries = vec![];
+    for (i, entry) in doc.entries().iter().enumerate() {
+        if total >= PROGRESS_MIN_ENTRIES && i > 0 && i % PROGRESS_STEP == 0 {
+            on_progress(i, total);
+        }
+        let base = entry_bases
+            .get(i)
+            .cloned()
+            .flatten()
+            .and_then(|b| parse_href(&b, &feed_base).ok())
+            .unwrap_or(feed_base.clone());
+        entries.push(process_opds_entry(entry, &base)?);
+    }
+    Ok(entries)
+}
+
+/// Returns the `xml:base` attribute of each entry in the feed, in document order.
+fn entry_bases(xml: &Document) -> Vec<Option<String>> {
+    xml.root_element()
+        .children()
+        .filter(|n| n.has_tag_name("entry"))
+        .map(|n| {
+            n.attribute(("http://www.w3.org/XML/1998/namespace", "base"))
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Namespace of the OpenSearch elements a feed can use to describe paging.
+const OPENSEARCH_NAMESPACE: &str = "http://a9.com/-/spec/opensearch/1.1/";
+
+/// Reads the OpenSearch `totalResults`, `startIndex` and `itemsPerPage` elements of a feed.
+/// Returns none if the feed has none of them.
+///
+/// # Arguments
+///
+/// * `xml` - The feed's XML tree.
+///
+fn parse_paging(xml: &Document) -> Option<Paging> {
+    let value = |name: &str| {
+        xml.root_element()
+            .children()
+            .find(|n| n.has_tag_name((OPENSEARCH_NAMESPACE, name)))
+            .and_then(|n| n.text())
+            .and_then(|t| t.trim().parse::<u64>().ok())
+    };
+
+    let paging = Paging {
+        total_results: value("totalResults"),
+        start_index: value("startIndex"),
+        items_per_page: value("itemsPerPage"),
+    };
+
+    (paging != Paging::default()).then_some(paging)
+}
+
+/// Namespace of the OPDS attributes that describe facets.
+const OPDS_NAMESPACE: &str = "http://opds-spec.org/2010/catalog";
+
+/// Namespace of the attribute that gives the number of entries behind a facet.
+const THREADING_NAMESPACE: &str = "http://purl.org/syndication/thread/1.0";
+
+/// Reads the facet links of a feed, in document order.
+///
+/// # Arguments
+///
+/// * `xml` - The feed's XML tree.
+/// * `base` - URL relative links are resolved against.
+///
+fn parse_facets(xml: &Document, base: &Url) -> Vec<Facet> {
+    xml.root_element()
+        .children()
+        .filter(|n| n.has_tag_name("link"))
+        .filter(|n| n.attribute("rel") == Some("http://opds-spec.org/facet"))
+        .filter_map(|n| {
+            Some(Facet {
+                title: n.attribute("title")?.trim().to_string(),
+                group: n
+                    .attribute((OPDS_NAMESPACE, "facetGroup"))
+                    .map(|g| g.trim().to_string()),
+                href: parse_href(n.attribute("href")?, base).ok()?,
+                active: n.attribute((OPDS_NAMESPACE, "activeFacet")) == Some("true"),
+                count: n
+                    .attribute((THREADING_NAMESPACE, "count"))
+                    .and_then(|c| c.trim().parse().ok()),
+            })
+        })
+        .collect()
+}
+
 /// represents a connection to the local disk
 pub struct LocalConnection {
     history: Vec<Url>,
     pub init_dir: Url,
+    /// whether searches also look inside subdirectories
+    pub recursive_search: bool,
+    /// how many directories deep a recursive search is allowed to go
+    pub max_search_depth: usize,
+    /// whether search results are shown instead of the current directory; going back leaves
+    /// them without leaving the directory
+    searching: bool,
+    /// where progress on reading large folders is reported, if anywhere
+    progress_tx: Option<mpsc::Sender<ParseProgress>>,
 }
 
 impl LocalConnection {
@@ -42,6 +243,153 @@ impl LocalConnection {
         LocalConnection {
             history: vec![],
             init_dir,
+            recursive_search: false,
+            max_search_depth: 0,
+            searching: false,
+            progress_tx: None,
+        }
+    }
+
+    /// Makes the connection report how many entries of large folders it has looked at.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Channel the URL of the folder and the number of read and total entries are
+    ///   sent to.
+    ///
+    pub fn report_progress(&mut self, tx: mpsc::Sender<ParseProgress>) {
+        self.progress_tx = Some(tx);
+    }
+
+    /// Lists the entries inside a directory, folders first and then by name, with numbers in names
+    /// compared by value.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - URL of the directory to list.
+    /// * `on_progress` - Called with the number of entries looked at so far and the total, every
+    ///   `PROGRESS_STEP` entries of directories with at least `PROGRESS_MIN_ENTRIES` entries.
+    ///
+    /// # Errors
+    ///
+    /// Errors related to reading the filesystem
+    ///
+    fn read_entries(
+        addr: &Url,
+        on_progress: &dyn Fn(usize, usize),
+    ) -> Result<Vec<EntryType>, Box<dyn Error>> {
+        let mut fnames = read_dir(addr)?;
+        fnames.sort_by(|a, b| natural_cmp(a, b));
+
+        // looking up the details of every file is what takes long
+        let total = fnames.len();
+        let mut entries: Vec<EntryType> = fnames
+            .iter()
+            .enumerate()
+            .map(|(i, fname)| {
+                if total >= PROGRESS_MIN_ENTRIES && i > 0 && i % PROGRESS_STEP == 0 {
+                    on_progress(i, total);
+                }
+                let full_path = Url::parse(&format!("{0}/{1}", addr, fname)).unwrap();
+
+                // entries we cannot stat are still listed, just without details
+                match fs::metadata(full_path.to_file_path().unwrap()) {
+                    Ok(md) => {
+                        let info = Some(FileInfo {
+                            size: md.len(),
+                            modified: md.modified().ok(),
+                        });
+
+                        if md.is_dir() {
+                            EntryType::Directory(fname.to_string(), full_path, info)
+                        } else {
+                            EntryType::File(fname.to_string(), full_path, info)
+                        }
+                    }
+                    Err(_) => EntryType::File(fname.to_string(), full_path, None),
+                }
+            })
+            .collect();
+        // stable, so the names stay in order within folders and files
+        entries.sort_by_key(|e| !matches!(e, EntryType::Directory(..)));
+        Ok(entries)
+    }
+
+    /// Parses an OPDS feed saved on disk into entries. Relative links are resolved against the
+    /// feed's absolute "self" link when it has one, otherwise against the location of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - URL of the feed file.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the file cannot be read or is not a valid feed.
+    ///
+    fn read_feed(addr: &Url) -> Result<Vec<EntryType>, Box<dyn Error>> {
+        let data = fs::read(addr.to_file_path().unwrap())?;
+        let doc = Feed::read_from(data.as_slice())
+            .map_err(|e| format!("Could not parse feed {}: {}", addr, e))?;
+
+        let base = doc
+            .links()
+            .iter()
+            .find(|l| l.rel() == "self")
+            .and_then(|l| Url::parse(l.href()).ok())
+            .unwrap_or(addr.clone());
+
+        let text = String::from_utf8_lossy(&data);
+        let xml = Document::parse(&text).ok();
+        process_feed(&doc, xml.as_ref(), &base, &|_, _| {})
+    }
+
+    /// Walks the directory and its subdirectories, collecting every entry whose name contains
+    /// the query. Matches are titled with their path relative to where the search started.
+    /// Directories that cannot be read are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Directory to search in.
+    /// * `prefix` - Path of `addr` relative to where the search started.
+    /// * `query` - String to look for.
+    /// * `depth` - How many more levels of subdirectories may be visited.
+    /// * `results` - Where matching entries are collected.
+    ///
+    fn search_recursive(
+        addr: &Url,
+        prefix: &str,
+        query: &str,
+        depth: usize,
+        results: &mut Vec<EntryType>,
+    ) {
+        let Ok(entries) = LocalConnection::read_entries(addr, &|_, _| {}) else {
+            return;
+        };
+
+        for entry in entries {
+            let matches = get_title_for_entry(&entry).contains(query);
+
+            match entry {
+                EntryType::Directory(name, url, info) => {
+                    let path = format!("{}{}", prefix, name);
+                    if depth > 0 {
+                        LocalConnection::search_recursive(
+                            &url,
+                            &format!("{}/", path),
+                            query,
+                            depth - 1,
+                            results,
+                        );
+                    }
+                    if matches {
+                        results.push(EntryType::Directory(path, url, info));
+                    }
+                }
+                EntryType::File(name, url, info) if matches => {
+                    results.push(EntryType::File(format!("{}{}", prefix, name), url, info));
+                }
+                _ => {}
+            }
         }
     }
 }
@@ -55,30 +403,47 @@ impl Connection for LocalConnection {
 
     async fn get_page(&mut self, addr: &Url) -> Result<Vec<EntryType>, Box<dyn Error>> {
         // add test
-        let fnames = read_dir(addr)?;
+        if addr.scheme() != "file" {
+            return Err(format!(
+                "{} is not on this computer. Add its server as a connection to browse it.",
+                addr
+            )
+            .into());
+        }
 
-        Ok(fnames
-            .iter()
-            .map(|fname| {
-                let full_path = Url::parse(&format!("{0}/{1}", addr, fname)).unwrap();
-                let md = fs::metadata(full_path.to_file_path().unwrap()).unwrap();
+        if addr.to_file_path().is_ok_and(|p| p.is_file()) {
+            return LocalConnection::read_feed(addr);
+        }
 
-                if md.is_file() {
-                    EntryType::File(fname.to_string(), full_path)
-                } else {
-                    EntryType::Directory(fname.to_string(), full_path)
+        // folders with thousands of files take a while to stat, which must not hold up the runtime
+        let dir = addr.clone();
+        let progress_tx = self.progress_tx.clone();
+        let entries = tokio::task::spawn_blocking(move || {
+            LocalConnection::read_entries(&dir, &|done, total| {
+                if let Some(tx) = &progress_tx {
+                    let _ = tx.send((dir.clone(), done, total));
                 }
             })
-            .collect())
+            .map_err(|e| e.to_string())
+        })
+        .await??;
+        Ok(entries)
     }
 
     async fn navigate_to(&mut self, addr: &Url) -> Result<Vec<EntryType>, Box<dyn Error>> {
         // push history on regardless, user will pop it on failure
         self.history.push(addr.clone());
+        self.searching = false;
         self.get_page(addr).await
     }
 
     async fn back(&mut self) -> Result<Vec<EntryType>, Box<dyn Error>> {
+        // search results are only an overlay over the directory
+        if self.searching {
+            self.searching = false;
+            return self.get_page(&self.current_address()).await;
+        }
+
         // add test
         if !self.history.is_empty() {
             self.history.pop();
@@ -87,21 +452,51 @@ impl Connection for LocalConnection {
         Err("At directory root; cannot go back.".into())
     }
 
-    async fn get_image_bytes(&self, _addr: &Url) -> Bytes {
-        // TODO: implement image rendering for local files
-        // should be reading byte info from file
-        Bytes::new()
+    fn clear_history(&mut self) {
+        self.history.clear();
+        self.searching = false;
+    }
+
+    async fn get_image_bytes(&self, addr: &Url) -> Bytes {
+        // TODO: implement rendering the first page of a pdf / epub
+        let Ok(path) = addr.to_file_path() else {
+            return Bytes::new();
+        };
+
+        match comic_cover(&path) {
+            Ok(Some(page)) => Bytes::from(page),
+            Ok(None) => Bytes::new(),
+            Err(err) => {
+                log::warn!("could not read the cover of {}: {}", path.display(), err);
+                Bytes::new()
+            }
+        }
     }
 
     async fn search(&mut self, query: &str) -> Result<Vec<EntryType>, Box<dyn Error>> {
-        // basically just filter on the results of navigate to
-        // we are deliberately adding onto the history so it's easy to use back()
-        let current_directory = self.navigate_to(&self.current_address()).await;
-        Ok(current_directory
-            .unwrap()
-            .into_iter()
-            .filter(|x| get_title_for_entry(x).contains(query))
-            .collect())
+        // the results are filtered from the current directory, leaving the history as it is;
+        // back() shows the whole directory again
+        let results = if self.recursive_search {
+            // walking a whole library reads every folder in it, which must not hold up the runtime
+            let dir = self.current_address();
+            let query = query.to_string();
+            let depth = self.max_search_depth;
+            tokio::task::spawn_blocking(move || {
+                let mut results = vec![];
+                LocalConnection::search_recursive(&dir, "", &query, depth, &mut results);
+                results
+            })
+            .await?
+        } else {
+            self.get_page(&self.current_address())
+                .await?
+                .into_iter()
+                .filter(|x| get_title_for_entry(x).contains(query))
+                .collect()
+        };
+
+        self.searching = true;
+        Ok(results)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -113,6 +508,8 @@ impl Connection for LocalConnection {
 pub struct OnlineConnection {
     /// server contains base_url and username
     pub server_info: Server,
+    /// where the catalog root was actually served from, after following redirects
+    base_url: Url,
     history: Vec<Url>,
     client: reqwest::Client,
     cache: HashMap<Url, Vec<EntryType>>,
@@ -120,32 +517,484 @@ pub struct OnlineConnection {
     password: Option<String>,
     /// URL used to build search queries
     search_url: Option<String>,
+    /// title of the catalog's root feed
+    title: String,
+    /// when a page was last fetched from the server
+    last_refresh: Option<DateTime<Utc>>,
+    /// OpenSearch paging hints for the pages that had them
+    paging: HashMap<Url, Paging>,
+    /// titles of the pages that had them
+    titles: HashMap<Url, FeedTitle>,
+    /// parent and root feeds of the pages that linked them
+    links: HashMap<Url, FeedLinks>,
+    /// searches by the URL of their results, so the other pages of results can be loaded and
+    /// going back to results keeps them search results
+    searches: HashMap<Url, SearchState>,
+    /// facets of the pages that offered them
+    facets: HashMap<Url, Vec<Facet>>,
+    /// whether requests leave out the credentials, to see what the catalog shows to anyone
+    anonymous: bool,
+    /// told when the server asks requests to wait before trying again
+    throttle_tx: Option<mpsc::Sender<Throttled>>,
+    /// told how far along converting the entries of large pages is
+    progress_tx: Option<mpsc::Sender<ParseProgress>>,
+    /// whether the body of the last page fetched is kept, for debugging feeds
+    keep_raw: bool,
+    /// URL and body of the last page fetched, if keep_raw is set
+    raw_page: Option<(Url, String)>,
+    /// ETag and Last-Modified the server sent with each page, to ask whether it changed since
+    validators: HashMap<Url, Validators>,
+    /// invalidated pages that are reused if the server answers that they did not change
+    stale: HashMap<Url, Vec<EntryType>>,
+}
+
+/// What a server sent to tell later whether a page changed, for conditional requests.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Reads the validators from the headers of a response, if it has any.
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|h: &reqwest::header::HeaderValue| h.to_str().ok())
+                .map(String::from)
+        };
+        let v = Validators {
+            etag: get(reqwest::header::ETAG),
+            last_modified: get(reqwest::header::LAST_MODIFIED),
+        };
+        (v != Validators::default()).then_some(v)
+    }
+
+    /// Asks the server to answer 304 Not Modified if the page did not change.
+    fn apply(&self, mut req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(etag) = &self.etag {
+            req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            req = req.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        req
+    }
+}
+
+/// A request the server asked to wait with: its URL and how long it waits before trying again.
+pub type Throttled = (Url, Duration);
+
+/// A page whose entries are being converted, or a folder whose files are being looked at: its
+/// URL, how many entries are done and how many there are.
+pub type ParseProgress = (Url, usize, usize);
+
+/// Feeds and folders with fewer entries than this are read without reporting progress.
+const PROGRESS_MIN_ENTRIES: usize = 1000;
+
+/// How many entries are read between progress reports.
+const PROGRESS_STEP: usize = 200;
+
+/// Longest a server can make a request wait before it is tried again.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+
+/// How many times a request is tried again when the server asks it to wait.
+const MAX_RETRIES: usize = 3;
+
+/// A search whose results are being shown.
+#[derive(Clone, Debug)]
+struct SearchState {
+    query: String,
+    /// index of the first result on the page, counting from 1
+    start: u64,
+    /// number of results on a page
+    page_len: u64,
+    /// URL of the page of results
+    url: Url,
+    /// title of the facet the results were narrowed with; the template cannot page those
+    facet: Option<String>,
 }
 
-/// Helper function to build a request with authentication
+/// Mime-type of an [OPDS authentication
+/// document](https://drafts.opds.io/authentication-for-opds-1.0).
+const AUTH_DOCUMENT_TYPE: &str = "application/opds-authentication+json";
+
+/// The only authentication method from the authentication document that ncopds supports.
+const BASIC_AUTH_TYPE: &str = "http://opds-spec.org/auth/basic";
+
+#[derive(Deserialize, Debug)]
+struct AuthDocument {
+    title: Option<String>,
+    authentication: Vec<AuthMethod>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AuthMethod {
+    #[serde(rename = "type")]
+    auth_type: String,
+    labels: Option<AuthLabels>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AuthLabels {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+/// Returned when a server rejects a request, either describing how to authenticate through an
+/// OPDS authentication document or through a `WWW-Authenticate` header. Holds the labels the
+/// server would like shown to the user.
+#[derive(Debug, Clone)]
+pub struct AuthenticationRequired {
+    pub title: String,
+    pub login_label: String,
+    pub password_label: String,
+    /// realm named in the `WWW-Authenticate` header, if the server sent one
+    pub realm: Option<String>,
+}
+
+impl fmt::Display for AuthenticationRequired {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.realm {
+            Some(realm) => write!(f, "Authentication failed for realm '{}'.", realm),
+            None => write!(f, "{} requires authentication.", self.title),
+        }
+    }
+}
+
+/// Reads the realm out of a `WWW-Authenticate` header, i.e. `Calibre` out of
+/// `Basic realm="Calibre"`. The realm may be quoted or a bare token.
+///
+/// # Arguments
+///
+/// * `header` - Value of the header.
+///
+fn parse_realm(header: &str) -> Option<String> {
+    let start = header.to_ascii_lowercase().find("realm=")? + "realm=".len();
+    let rest = &header[start..];
+
+    let realm = match rest.strip_prefix('"') {
+        Some(quoted) => &quoted[..quoted.find('"')?],
+        None => rest.split([',', ' ']).next()?,
+    };
+    Some(realm.to_string())
+}
+
+impl Error for AuthenticationRequired {}
+
+/// Returned when a catalog cannot be reached at its configured URL but answers at another one
+/// close to it. The configured URL is left alone; it is up to the user to switch.
+#[derive(Debug, Clone)]
+pub struct UrlSuggestion {
+    /// where the catalog answered
+    pub url: Url,
+    /// why the configured URL failed
+    pub reason: String,
+}
+
+impl fmt::Display for UrlSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}; the server responded at {}", self.reason, self.url)
+    }
+}
+
+impl Error for UrlSuggestion {}
+
+/// How long each guess at where a catalog lives may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lists the URLs a catalog that failed at `base_url` is commonly found at instead: the https
+/// variant of an http URL, and the `opds` path below either.
+///
+/// # Arguments
+///
+/// * `base_url` - URL the catalog was configured with.
+///
+fn url_candidates(base_url: &Url) -> Vec<Url> {
+    let mut bases = vec![base_url.clone()];
+    let mut https = base_url.clone();
+    if base_url.scheme() == "http" && https.set_scheme("https").is_ok() {
+        bases.push(https.clone());
+    }
+
+    let mut candidates: Vec<Url> = bases[1..].to_vec();
+    for base in &bases {
+        if base.path().trim_end_matches('/').ends_with("opds") {
+            continue;
+        }
+        let mut dir = base.clone();
+        if !dir.path().ends_with('/') {
+            dir.set_path(&format!("{}/", dir.path()));
+        }
+        if let Ok(url) = dir.join("opds") {
+            candidates.push(url);
+        }
+    }
+    candidates
+}
+
+/// Tries the URLs from [`url_candidates`] in turn and returns the first that serves a feed.
+///
+/// # Arguments
+///
+/// * `client` - HTTP client to make the requests with.
+/// * `s` - Server whose URL failed.
+/// * `password` - Password to authenticate with, if any.
+///
+async fn find_catalog(
+    client: &reqwest::Client,
+    s: &Server,
+    password: &Option<String>,
+) -> Option<Url> {
+    for url in url_candidates(&s.base_url) {
+        let req = build_req(client, Method::GET, &url, s, password).timeout(PROBE_TIMEOUT);
+        let Ok(response) = req.send().await else {
+            continue;
+        };
+        log::info!("GET {} {}", url, response.status());
+        if !response.status().is_success() {
+            continue;
+        }
+
+        let Ok(bytes) = response.bytes().await else {
+            continue;
+        };
+        if Feed::read_from(bytes.as_ref()).is_ok() {
+            return Some(url);
+        }
+    }
+    None
+}
+
+/// Parses an OPDS authentication document. Returns the labels for basic authentication if the
+/// server supports it.
+///
+/// # Arguments
+///
+/// * `data` - JSON document returned alongside the 401
+///
+/// # Errors
+///
+/// Errors if the document cannot be parsed or if it only advertises authentication methods that
+/// ncopds does not support.
+///
+fn parse_auth_document(data: &[u8]) -> Result<AuthenticationRequired, Box<dyn Error>> {
+    let doc: AuthDocument = serde_json::from_slice(data)?;
+    let title = doc.title.unwrap_or(String::from("Server"));
+
+    let basic = doc
+        .authentication
+        .iter()
+        .find(|m| m.auth_type == BASIC_AUTH_TYPE);
+
+    match basic {
+        Some(method) => {
+            let labels = method.labels.as_ref();
+            Ok(AuthenticationRequired {
+                title,
+                realm: None,
+                login_label: labels
+                    .and_then(|l| l.login.clone())
+                    .unwrap_or(String::from("Username")),
+                password_label: labels
+                    .and_then(|l| l.password.clone())
+                    .unwrap_or(String::from("Password")),
+            })
+        }
+        None => {
+            let methods = doc
+                .authentication
+                .iter()
+                .map(|m| m.auth_type.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            Err(format!(
+                "{} requires an authentication method ncopds does not support ({}). Only basic authentication is supported.",
+                title, methods
+            )
+            .into())
+        }
+    }
+}
+
+/// Helper function to build a request with authentication. If the server has its own user-agent
+/// set, it replaces the client's default one.
 ///
 /// # Arguments
 ///
 /// * `client` - reqwest client
+/// * `method` - HTTP method of the request
 /// * `url` - url to request
-/// * `username` - username for authentication
+/// * `s` - server the request is made to
 /// * `password` - password for authentication
 ///
 fn build_req(
     client: &reqwest::Client,
+    method: Method,
     url: &Url,
-    username: &Option<String>,
+    s: &Server,
     password: &Option<String>,
 ) -> reqwest::RequestBuilder {
-    let req = client.get(url.to_string());
+    let mut req = client.request(method, url.to_string());
 
-    if let Some(u) = username {
+    // request headers take precedence over the client's default headers
+    if let Some(ua) = &s.user_agent {
+        req = req.header(reqwest::header::USER_AGENT, ua);
+    }
+    if !s.headers.is_empty() {
+        req = req.headers(s.header_map());
+    }
+
+    if let Some(u) = &s.username {
         return req.basic_auth(u, password.clone());
     };
 
     req
 }
 
+/// Fills in an OpenSearch URL template. Optional parameters that ncopds has no value for are
+/// left empty; required ones are left as they are.
+///
+/// # Arguments
+///
+/// * `template` - Search URL template.
+/// * `query` - What to search for.
+/// * `start` - Index of the first result to return, counting from 1.
+/// * `page_len` - Number of results on a page, if known.
+///
+fn fill_search_template(template: &str, query: &str, start: u64, page_len: Option<u64>) -> String {
+    let page = page_len.map_or(1, |l| (start - 1) / l.max(1) + 1);
+
+    let mut target = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|c| open + c) else {
+            break;
+        };
+        target.push_str(&rest[..open]);
+
+        let token = &rest[open + 1..close];
+        let value = match token.trim_end_matches('?') {
+            "searchTerms" => Some(query.to_string()),
+            "startIndex" => Some(start.to_string()),
+            "startPage" => Some(page.to_string()),
+            "count" => Some(page_len.map(|l| l.to_string()).unwrap_or_default()),
+            _ => None,
+        };
+        match value {
+            Some(value) => target.push_str(&value),
+            None if token.ends_with('?') => {}
+            None => target.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    target.push_str(rest);
+
+    target
+}
+
+/// Whether a server answered the last health check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    Reachable,
+    Unreachable,
+    /// the server answered but did not accept the credentials
+    AuthError,
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Health::Reachable => write!(f, "reachable"),
+            Health::Unreachable => write!(f, "unreachable"),
+            Health::AuthError => write!(f, "refusing the credentials"),
+        }
+    }
+}
+
+/// Checks whether a server is up with a HEAD request to its catalog root. Servers that do not
+/// support HEAD still count as reachable since they answered.
+///
+/// # Arguments
+///
+/// * `client` - reqwest client
+/// * `s` - server to check
+/// * `password` - password for authentication
+///
+pub async fn ping(client: &reqwest::Client, s: &Server, password: &Option<String>) -> Health {
+    match build_req(client, Method::HEAD, &s.base_url, s, password)
+        .send()
+        .await
+    {
+        Ok(res) => match res.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Health::AuthError,
+            status if status.is_server_error() => Health::Unreachable,
+            _ => Health::Reachable,
+        },
+        Err(_) => Health::Unreachable,
+    }
+}
+
+/// Whether a download link works, as far as a HEAD request can tell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatStatus {
+    Available,
+    /// the server answered with an error, i.e. 404
+    Unavailable(StatusCode),
+    /// the server sends something other than the advertised format, i.e. an HTML error page
+    WrongType(String),
+    Unreachable,
+}
+
+impl fmt::Display for FormatStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatStatus::Available => write!(f, "available"),
+            FormatStatus::Unavailable(status) => write!(f, "unavailable: {}", status),
+            FormatStatus::WrongType(mt) => write!(f, "server sends {}", mt),
+            FormatStatus::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+/// Checks whether a download link works before downloading it, using a HEAD request. Servers
+/// that do not support HEAD, or that do not say what they send, get the benefit of the doubt.
+///
+/// # Arguments
+///
+/// * `req` - HEAD request for the link.
+/// * `mime_type` - Format the feed advertised for the link.
+///
+pub async fn check_format(req: reqwest::RequestBuilder, mime_type: &str) -> FormatStatus {
+    let res = match req.send().await {
+        Ok(res) => res,
+        Err(_) => return FormatStatus::Unreachable,
+    };
+
+    let status = res.status();
+    if status == StatusCode::METHOD_NOT_ALLOWED || status == StatusCode::NOT_IMPLEMENTED {
+        return FormatStatus::Available;
+    }
+    if !status.is_success() {
+        return FormatStatus::Unavailable(status);
+    }
+
+    let essence = |mt: &str| mt.split(';').next().unwrap_or("").trim().to_lowercase();
+    let sent = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map(essence);
+
+    match sent {
+        Some(sent) if sent != "application/octet-stream" && sent != essence(mime_type) => {
+            FormatStatus::WrongType(sent)
+        }
+        _ => FormatStatus::Available,
+    }
+}
+
 /// Parses an opensearchdescription document to get the search url hidden within it. Returns none
 /// if the document did not have a <Url> tag pointing to an Atom feed.
 ///
@@ -175,12 +1024,14 @@ fn parse_osd(osd: &Document) -> Option<String> {
 ///
 /// * `client` - reqwest client
 /// * `doc` - atom feed struct
+/// * `feed_url` - URL the feed was served from
 /// * `s` - server information  
 /// * `password` - password
 ///
 async fn find_search_url(
     client: &reqwest::Client,
     doc: Feed,
+    feed_url: &Url,
     s: &Server,
     password: &Option<String>,
 ) -> Option<String> {
@@ -188,66 +1039,232 @@ async fn find_search_url(
     for l in doc.links {
         if let Some(mt) = l.mime_type() {
             if l.rel == "search" && mt.contains("opensearchdescription") {
-                let u = parse_href(l.href(), &s.get_domain()).expect("");
+                let u = parse_href(l.href(), feed_url).ok()?;
 
-                let osd_res = build_req(client, &u, &s.username, password)
+                let osd_res = build_req(client, Method::GET, &u, s, password)
                     .send()
                     .await
                     .ok()?;
 
-                let b = &osd_res.bytes().await.ok()?;
+                let b = &osd_res.bytes().await.ok()?;
+
+                let bs = std::str::from_utf8(b).ok()?;
+                let osd = Document::parse(bs).ok()?;
+                let search_str = parse_osd(&osd)?;
+                search_url = Some(parse_href(&search_str, &u).ok()?.to_string());
+            }
+        }
+    }
+    search_url
+}
+
+impl OnlineConnection {
+    /// Connects to the catalog of a server. If that fails for any reason other than the
+    /// credentials, a few URLs close to the configured one are tried, and if one of them serves
+    /// a catalog a [`UrlSuggestion`] is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Server to connect to.
+    /// * `client` - HTTP client to make requests with, unless the server needs one of its own.
+    /// * `password` - Password to authenticate with, if any.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the catalog cannot be reached or read, or asks for credentials.
+    ///
+    pub async fn new(
+        s: &Server,
+        client: reqwest::Client,
+        password: Option<String>,
+    ) -> Result<OnlineConnection, Box<dyn Error>> {
+        // servers with a proxy or timeout of their own keep a client of their own
+        let client = s.client(&client)?;
+
+        let reason = match OnlineConnection::connect(s, client.clone(), password.clone()).await {
+            Ok(oc) => return Ok(oc),
+            // a catalog that asks for a password was found
+            Err(err) if err.is::<AuthenticationRequired>() => return Err(err),
+            Err(err) => err.to_string(),
+        };
 
-                let bs = std::str::from_utf8(b).ok()?;
-                let osd = Document::parse(bs).ok()?;
-                let search_str = parse_osd(&osd)?;
-                search_url = Some(parse_href(&search_str, &s.get_domain()).ok()?.to_string());
+        match find_catalog(&client, s, &password).await {
+            Some(url) => {
+                log::info!("{} failed, but a catalog was found at {}", s.base_url, url);
+                Err(Box::new(UrlSuggestion { url, reason }))
             }
+            None => Err(reason.into()),
         }
     }
-    search_url
-}
 
-impl OnlineConnection {
-    pub async fn new(
+    /// Connects to the catalog at the server's configured URL.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the catalog cannot be reached or read, or asks for credentials.
+    ///
+    async fn connect(
         s: &Server,
         client: reqwest::Client,
         password: Option<String>,
     ) -> Result<OnlineConnection, Box<dyn Error>> {
         // test connection
-        let req = build_req(&client, &s.base_url, &s.username, &password);
-        let response = req.send().await?;
+        let req = build_req(&client, Method::GET, &s.base_url, s, &password);
+        let response = send_with_retry(req, &s.base_url, None).await?;
+        log::info!("GET {} {}", s.base_url, response.status());
+
+        // the catalog may live somewhere else than configured, e.g. behind a trailing slash;
+        // relative links have to be resolved against the real location
+        let base_url = response.url().clone();
+        if base_url != s.base_url {
+            log::info!("{} redirected to {}", s.base_url, base_url);
+        }
+
+        // servers can describe how to log in instead of just rejecting the request
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let is_auth_document = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|ct| ct.to_str().ok())
+                .is_some_and(|ct| ct.contains(AUTH_DOCUMENT_TYPE));
+
+            if is_auth_document {
+                let auth = parse_auth_document(&response.bytes().await?)?;
+                return Err(Box::new(auth));
+            }
+
+            // naming the realm tells a mistyped password apart from a misbehaving server
+            let realm = response
+                .headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(parse_realm);
+            log::warn!(
+                "{} rejected the credentials (realm {:?})",
+                s.base_url,
+                realm
+            );
+
+            return Err(Box::new(AuthenticationRequired {
+                title: realm
+                    .clone()
+                    .unwrap_or(s.base_url.host_str().unwrap_or("Server").to_string()),
+                login_label: String::from("Username"),
+                password_label: String::from("Password"),
+                realm,
+            }));
+        }
         response.error_for_status_ref()?;
 
         let response_bytes = &response.bytes().await?;
         let doc = Feed::read_from(response_bytes.as_ref())?;
-        let search_url = find_search_url(&client, doc, s, &password).await;
+        let title = doc.title().to_string();
+        let search_url = find_search_url(&client, doc, &base_url, s, &password).await;
 
         let oc = OnlineConnection {
             history: vec![],
             server_info: s.clone(),
+            base_url,
             client,
             cache: HashMap::new(),
             password,
             search_url,
+            title,
+            last_refresh: None,
+            paging: HashMap::new(),
+            titles: HashMap::new(),
+            links: HashMap::new(),
+            searches: HashMap::new(),
+            facets: HashMap::new(),
+            anonymous: false,
+            throttle_tx: None,
+            progress_tx: None,
+            keep_raw: false,
+            raw_page: None,
+            validators: HashMap::new(),
+            stale: HashMap::new(),
         };
 
         Ok(oc)
     }
 
+    /// Makes the connection report requests that wait because the server asked them to.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Channel the URL of the request and the wait are sent to.
+    ///
+    pub fn report_throttling(&mut self, tx: mpsc::Sender<Throttled>) {
+        self.throttle_tx = Some(tx);
+    }
+
+    /// Makes the connection report how far along it is with converting the entries of large
+    /// pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Channel the URL of the page and the number of converted and total entries are
+    ///   sent to.
+    ///
+    pub fn report_progress(&mut self, tx: mpsc::Sender<ParseProgress>) {
+        self.progress_tx = Some(tx);
+    }
+
+    /// Makes the connection keep what the server sent for the last page it fetched, so it can be
+    /// looked at when a feed is not shown as expected. Off by default since feeds can be large.
+    pub fn keep_raw_pages(&mut self) {
+        self.keep_raw = true;
+    }
+
+    /// Returns the title of the catalog's root feed.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Checks whether the catalog offers a search.
+    pub fn supports_search(&self) -> bool {
+        self.search_url.is_some()
+    }
+
+    /// Returns what the server is checked with so it can be pinged without holding the
+    /// connection: the connection's client, server info and password.
+    pub fn ping_target(&self) -> (reqwest::Client, Server, Option<String>) {
+        (
+            self.client.clone(),
+            self.server_info.clone(),
+            self.password.clone(),
+        )
+    }
+
     /// Shorthand for build_req; builds a request for the URL using the credentials for the
-    /// connection.
+    /// connection, unless it is browsing anonymously.
     ///
     /// # Arguments
     ///
     /// * `url` - URL to build request for
     ///
     pub fn get_request(&self, url: &Url) -> reqwest::RequestBuilder {
-        build_req(
-            &self.client,
-            url,
-            &self.server_info.username,
-            &self.password,
-        )
+        self.request(Method::GET, url)
+    }
+
+    /// Builds a request for the URL using the credentials for the connection, unless it is
+    /// browsing anonymously.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - HTTP method of the request
+    /// * `url` - URL to build request for
+    ///
+    pub fn request(&self, method: Method, url: &Url) -> reqwest::RequestBuilder {
+        if self.anonymous {
+            let anonymous = Server {
+                username: None,
+                ..self.server_info.clone()
+            };
+            return build_req(&self.client, method, url, &anonymous, &None);
+        }
+
+        build_req(&self.client, method, url, &self.server_info, &self.password)
     }
 
     /// Returns the filename and byte data from the URL specified.
@@ -261,32 +1278,125 @@ impl OnlineConnection {
     /// Errors related to making GET requests can arise.
     ///
     pub async fn download(&self, url: &Url) -> Result<(String, Bytes), Box<dyn Error>> {
-        // add test
-        let response = self.get_request(url).send().await?;
-        let headers = &response.headers().to_owned();
-        let response_bytes = response.bytes().await?;
+        download(self.get_request(url), url, self.throttle_tx.as_ref()).await
+    }
+}
 
-        // basically all we do here is try and build up a filename
-        let cd = headers.get("content-disposition");
-        let t = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-            .to_string();
+/// Sends the request. Servers that are too busy (429 / 503) can say when to come back with a
+/// Retry-After header; the request then waits that long, at most `MAX_RETRY_WAIT`, and is sent
+/// again, up to `MAX_RETRIES` times. Without the header, the response is returned as is.
+///
+/// # Arguments
+///
+/// * `req` - Request to send.
+/// * `url` - URL the request is for.
+/// * `throttle_tx` - Told about every wait.
+///
+/// # Errors
+///
+/// Errors if the request could not be sent.
+///
+async fn send_with_retry(
+    mut req: reqwest::RequestBuilder,
+    url: &Url,
+    throttle_tx: Option<&mpsc::Sender<Throttled>>,
+) -> Result<reqwest::Response, reqwest::Error> {
+    for _ in 0..MAX_RETRIES {
+        // requests with a streamed body cannot be sent twice
+        let Some(retry) = req.try_clone() else {
+            break;
+        };
 
-        let filename = url.path_segments().unwrap().last().unwrap_or(&t);
+        let response = req.send().await?;
+        let status = response.status();
+        if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response);
+        }
+        let Some(wait) = retry_after(response.headers(), Utc::now()) else {
+            return Ok(response);
+        };
 
-        if let Some(content_dispo) = cd {
-            let cd_filename =
-                crate::utils::extract_filename_from_content_disposition(content_dispo);
+        log::warn!("{} answered {}; trying again in {:?}", url, status, wait);
+        if let Some(tx) = throttle_tx {
+            let _ = tx.send((url.clone(), wait));
+        }
+        tokio::time::sleep(wait).await;
+        req = retry;
+    }
+    req.send().await
+}
 
-            if let Some(fname) = cd_filename {
-                return Ok((fname.to_string(), response_bytes));
-            }
+/// Reads how long a Retry-After header asks to wait, given either in seconds or as an HTTP
+/// date. Waits are capped at `MAX_RETRY_WAIT`; dates in the past mean no wait. Returns none if
+/// the header is missing or cannot be read.
+///
+/// # Arguments
+///
+/// * `headers` - Headers of the response.
+/// * `now` - Current time, which HTTP dates are counted from.
+///
+pub fn retry_after(headers: &reqwest::header::HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    let wait = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - now)
+                .to_std()
+                .unwrap_or(Duration::ZERO)
         }
+    };
+    Some(wait.min(MAX_RETRY_WAIT))
+}
+
+/// Sends the request and returns the filename and byte data of the response. The filename is
+/// taken from the content-disposition headers if possible, otherwise from the URL.
+///
+/// # Arguments
+///
+/// * `req` - Request to send.
+/// * `url` - URL the request is for.
+/// * `throttle_tx` - Told if the server asks the download to wait.
+///
+/// # Errors
+///
+/// Errors related to making GET requests can arise.
+///
+pub async fn download(
+    req: reqwest::RequestBuilder,
+    url: &Url,
+    throttle_tx: Option<&mpsc::Sender<Throttled>>,
+) -> Result<(String, Bytes), Box<dyn Error>> {
+    // add test
+    let response = send_with_retry(req, url, throttle_tx).await?;
+    log::info!("GET {} {}", url, response.status());
+    let headers = &response.headers().to_owned();
+    let response_bytes = response.bytes().await?;
+
+    // basically all we do here is try and build up a filename
+    let cd = headers.get("content-disposition");
+    let t = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+        .to_string();
 
-        Ok((filename.to_string(), response_bytes))
+    let filename = download_filename(url).unwrap_or(t);
+
+    if let Some(content_dispo) = cd {
+        let cd_filename = crate::utils::extract_filename_from_content_disposition(content_dispo);
+
+        if let Some(fname) = cd_filename {
+            return Ok((fname.to_string(), response_bytes));
+        }
     }
+
+    Ok((filename.to_string(), response_bytes))
 }
 
 #[async_trait]
@@ -296,23 +1406,74 @@ impl Connection for OnlineConnection {
             return Ok(d.to_vec());
         };
 
-        let response = self.get_request(addr).send().await?;
+        let mut req = self.get_request(addr);
+        // only ask whether the page changed when there is something to fall back on
+        if self.stale.contains_key(addr) {
+            if let Some(v) = self.validators.get(addr) {
+                req = v.apply(req);
+            }
+        }
+
+        let response = send_with_retry(req, addr, self.throttle_tx.as_ref()).await?;
+        log::info!("GET {} {}", addr, response.status());
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entries) = self.stale.remove(addr) {
+                self.last_refresh = Some(Utc::now());
+                self.cache.insert(addr.clone(), entries.clone());
+                return Ok(entries);
+            }
+        }
         response.error_for_status_ref()?;
+        let validators = Validators::from_headers(response.headers());
 
+        // relative links are relative to the feed, which may have been reached via redirect
+        let feed_url = response.url().clone();
         let response_bytes = response.bytes().await?;
+        let text = String::from_utf8_lossy(&response_bytes);
+        // kept before parsing, since feeds that fail to parse are the interesting ones
+        if self.keep_raw {
+            self.raw_page = Some((addr.clone(), text.clone().into_owned()));
+        }
         let doc = Feed::read_from(response_bytes.as_ref())?;
+        // the parts of the feed atom_syndication does not keep are read from the XML tree
+        let xml = Document::parse(&text).ok();
 
         // try and fix errors on feed if possible
         // https://github.com/rust-syndication/atom/blob/master/src/feed.rs
         // should be able to call Feed::from_xml on feeds that fail invalid start tags
 
-        let mut entries = vec![];
+        let progress_tx = self.progress_tx.clone();
+        let entries = process_feed(&doc, xml.as_ref(), &feed_url, &|done, total| {
+            if let Some(tx) = &progress_tx {
+                let _ = tx.send((addr.clone(), done, total));
+            }
+        })?;
 
-        for entry in doc.entries().iter() {
-            let processed_entry = process_opds_entry(entry, &self.server_info.get_domain())?;
-            entries.push(processed_entry);
+        if let Some(p) = xml.as_ref().and_then(parse_paging) {
+            self.paging.insert(addr.clone(), p);
         }
+        if let Some(t) = FeedTitle::from_feed(&doc) {
+            self.titles.insert(addr.clone(), t);
+        }
+        if let Some(l) = FeedLinks::from_feed(&doc, &feed_base(&doc, &feed_url)) {
+            self.links.insert(addr.clone(), l);
+        }
+        let facets = xml
+            .as_ref()
+            .map(|x| parse_facets(x, &feed_base(&doc, &feed_url)))
+            .unwrap_or_default();
+        if !facets.is_empty() {
+            self.facets.insert(addr.clone(), facets);
+        }
+
+        match validators {
+            Some(v) => self.validators.insert(addr.clone(), v),
+            None => self.validators.remove(addr),
+        };
+        self.stale.remove(addr);
 
+        self.last_refresh = Some(Utc::now());
         self.cache.insert(addr.clone(), entries.clone());
         Ok(entries)
     }
@@ -331,10 +1492,107 @@ impl Connection for OnlineConnection {
         Err("At ODPS root; cannot go back.".into())
     }
 
+    fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    fn invalidate(&mut self, addr: &Url) {
+        // kept aside in case the server answers that the page did not change
+        if let Some(entries) = self.cache.remove(addr) {
+            if self.validators.contains_key(addr) {
+                self.stale.insert(addr.clone(), entries);
+            }
+        }
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.clear();
+        self.paging.clear();
+        self.titles.clear();
+        self.links.clear();
+        self.facets.clear();
+        self.validators.clear();
+        self.stale.clear();
+    }
+
+    fn anonymous(&self) -> bool {
+        self.anonymous
+    }
+
+    fn set_anonymous(&mut self, anonymous: bool) -> Result<(), Box<dyn Error>> {
+        self.anonymous = anonymous;
+        // anonymous visitors usually see less, so nothing cached before applies
+        self.clear_cache();
+        Ok(())
+    }
+
+    fn paging(&self, addr: &Url) -> Option<Paging> {
+        self.paging.get(addr).copied()
+    }
+
+    fn feed_title(&self, addr: &Url) -> Option<FeedTitle> {
+        self.titles.get(addr).cloned()
+    }
+
+    fn feed_links(&self, addr: &Url) -> Option<FeedLinks> {
+        self.links.get(addr).cloned()
+    }
+
+    fn facets(&self, addr: &Url) -> Vec<Facet> {
+        self.facets.get(addr).cloned().unwrap_or_default()
+    }
+
+    async fn apply_facet(&mut self, facet: &Facet) -> Result<Vec<EntryType>, Box<dyn Error>> {
+        let search = self.searches.get(&self.current_address()).cloned();
+        let entries = match self.navigate_to(&facet.href).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                // the page the facet was picked on stays
+                self.history.pop();
+                return Err(e);
+            }
+        };
+
+        // the narrowed results are still results of the same search
+        if let Some(search) = search {
+            let url = self.current_address();
+            let start = self.paging.get(&url).and_then(|p| p.start_index);
+            self.searches.insert(
+                url.clone(),
+                SearchState {
+                    query: search.query,
+                    start: start.unwrap_or(1),
+                    page_len: entries.len() as u64,
+                    url,
+                    facet: Some(facet.title.clone()),
+                },
+            );
+        }
+        Ok(entries)
+    }
+
+    fn search_query(&self, addr: &Url) -> Option<(String, Option<String>)> {
+        self.searches
+            .get(addr)
+            .map(|s| (s.query.clone(), s.facet.clone()))
+    }
+
+    fn last_refresh(&self) -> Option<DateTime<Utc>> {
+        self.last_refresh
+    }
+
+    fn cached_page(&self, addr: &Url) -> Option<Vec<EntryType>> {
+        self.cache.get(addr).cloned()
+    }
+
+    fn raw_page(&self) -> Option<(Url, String)> {
+        self.raw_page.clone()
+    }
+
     fn current_address(&self) -> Url {
         match self.history.last() {
             Some(h) => h.clone(),
-            None => self.server_info.base_url.clone(),
+            None => self.base_url.clone(),
         }
     }
 
@@ -352,15 +1610,639 @@ impl Connection for OnlineConnection {
         // https://specs.opds.io/opds-1.2#3-search
         // need to add support for advanced search fields
         if let Some(su) = &self.search_url {
-            let target = su.replace("{searchTerms}", query);
+            let target = fill_search_template(su, query, 1, None);
             let tu = Url::parse(&target)?;
-            self.navigate_to(&tu).await
+            let entries = self.navigate_to(&tu).await?;
+
+            let url = self.current_address();
+            self.searches.insert(
+                url.clone(),
+                SearchState {
+                    query: query.to_string(),
+                    start: 1,
+                    page_len: entries.len() as u64,
+                    url,
+                    facet: None,
+                },
+            );
+            Ok(entries)
         } else {
             Err("Server does not have searching enabled.".into())
         }
     }
 
+    async fn turn_search_page(
+        &mut self,
+        forward: bool,
+    ) -> Result<(String, Vec<EntryType>), Box<dyn Error>> {
+        let state = match self.searches.get(&self.current_address()) {
+            Some(s) => s.clone(),
+            None => return Err("Not showing search results.".into()),
+        };
+        if state.facet.is_some() {
+            return Err("Search results narrowed by a facet cannot be paged.".into());
+        }
+        let Some(su) = self.search_url.clone() else {
+            return Err("Server does not have searching enabled.".into());
+        };
+        if !su.contains("{startIndex") && !su.contains("{startPage") {
+            return Err("This catalog does not support paging through search results.".into());
+        }
+
+        // the feed knows better than we do where the page starts and how long it is
+        let paging = self.paging.get(&state.url).copied().unwrap_or_default();
+        let start = paging.start_index.unwrap_or(state.start);
+        let page_len = paging.items_per_page.unwrap_or(state.page_len).max(1);
+
+        let new_start = if forward {
+            if paging.total_results.is_some_and(|t| start + page_len > t) || state.page_len == 0 {
+                return Err("This is the last page of results.".into());
+            }
+            start + page_len
+        } else {
+            if start <= 1 {
+                return Err("This is the first page of results.".into());
+            }
+            start.saturating_sub(page_len).max(1)
+        };
+
+        let target = fill_search_template(&su, &state.query, new_start, Some(page_len));
+        let entries = self.navigate_to(&Url::parse(&target)?).await?;
+
+        let url = self.current_address();
+        self.searches.insert(
+            url.clone(),
+            SearchState {
+                query: state.query.clone(),
+                start: new_start,
+                page_len,
+                url,
+                facet: None,
+            },
+        );
+        Ok((state.query, entries))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockServer, Response};
+    use crate::model::EntryData;
+    use crate::test_utils::{entry_data, scratch_dir};
+
+    /// Wraps entries into a feed document, with extra attributes on the feed element.
+    fn feed_xml(attributes: &str, body: &str) -> String {
+        format!(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" {}><id>f</id><title>Feed</title><updated>2024-01-01T00:00:00Z</updated>{}</feed>"#,
+            attributes, body
+        )
+    }
+
+    /// Converts the entries of a feed document retrieved from the URL.
+    fn entries(xml: &str, feed_url: &str) -> Vec<EntryData> {
+        let doc = Feed::read_from(xml.as_bytes()).unwrap();
+        let feed_url = Url::parse(feed_url).unwrap();
+        let tree = Document::parse(xml).unwrap();
+        process_feed(&doc, Some(&tree), &feed_url, &|_, _| {})
+            .unwrap()
+            .into_iter()
+            .map(entry_data)
+            .collect()
+    }
+
+    #[test]
+    fn relative_links_resolve_against_the_feed() {
+        let xml = feed_xml(
+            "",
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="cover.jpg" type="image/jpeg"/>
+            <link rel="http://opds-spec.org/acquisition" href="../files/book.epub" type="application/epub+zip"/>
+            </entry>"#,
+        );
+        let data = &entries(&xml, "http://example.com/opds/authors/feed.xml")[0];
+
+        assert_eq!(
+            data.image.as_ref().unwrap().as_str(),
+            "http://example.com/opds/authors/cover.jpg"
+        );
+        assert_eq!(
+            data.downloads[0].0.as_str(),
+            "http://example.com/opds/files/book.epub"
+        );
+    }
+
+    #[test]
+    fn relative_links_keep_ipv6_hosts() {
+        let xml = feed_xml(
+            "",
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="/covers/1.jpg" type="image/jpeg"/>
+            <link rel="http://opds-spec.org/acquisition" href="get/1.epub" type="application/epub+zip"/>
+            </entry>"#,
+        );
+        let data = &entries(&xml, "http://[::1]:8080/opds/")[0];
+
+        assert_eq!(
+            data.image.as_ref().unwrap().as_str(),
+            "http://[::1]:8080/covers/1.jpg"
+        );
+        assert_eq!(
+            data.downloads[0].0.as_str(),
+            "http://[::1]:8080/opds/get/1.epub"
+        );
+    }
+
+    #[test]
+    fn xml_base_changes_where_links_resolve() {
+        let xml = feed_xml(
+            r#"xml:base="http://cdn.example.com/library/""#,
+            r#"<entry><id>1</id><title>Feed base</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="covers/1.jpg" type="image/jpeg"/>
+            </entry>
+            <entry xml:base="/other/"><id>2</id><title>Entry base</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="2.jpg" type="image/jpeg"/>
+            </entry>"#,
+        );
+        let data = entries(&xml, "http://example.com/opds/feed.xml");
+
+        assert_eq!(
+            data[0].image.as_ref().unwrap().as_str(),
+            "http://cdn.example.com/library/covers/1.jpg"
+        );
+        // the entry's base is itself relative to the feed's
+        assert_eq!(
+            data[1].image.as_ref().unwrap().as_str(),
+            "http://cdn.example.com/other/2.jpg"
+        );
+    }
+
+    #[test]
+    fn only_unknown_optional_parameters_are_dropped() {
+        let template = "http://example.com/search?q={searchTerms}&lang={language}&a={atom:author?}&start={startIndex?}";
+        assert_eq!(
+            fill_search_template(template, "dune", 21, Some(20)),
+            "http://example.com/search?q=dune&lang={language}&a=&start=21"
+        );
+        assert_eq!(
+            fill_search_template(
+                "http://example.com/s/{searchTerms}/{startPage}?n={count}",
+                "dune",
+                21,
+                Some(20)
+            ),
+            "http://example.com/s/dune/2?n=20"
+        );
+        // braces in the query are not mistaken for parameters
+        assert_eq!(
+            fill_search_template(
+                "http://example.com/?q={searchTerms}&x={y?}",
+                "{a?}",
+                1,
+                None
+            ),
+            "http://example.com/?q={a?}&x="
+        );
+    }
+
+    #[test]
+    fn opensearch_paging_is_read() {
+        let xml = feed_xml(
+            r#"xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/""#,
+            "<opensearch:totalResults>437</opensearch:totalResults>
+            <opensearch:startIndex>21</opensearch:startIndex>
+            <opensearch:itemsPerPage>20</opensearch:itemsPerPage>",
+        );
+        let paging = parse_paging(&Document::parse(&xml).unwrap()).unwrap();
+
+        assert_eq!(
+            paging,
+            Paging {
+                total_results: Some(437),
+                start_index: Some(21),
+                items_per_page: Some(20),
+            }
+        );
+        assert_eq!(paging.describe(20).unwrap(), "showing 21–40 of 437");
+    }
+
+    #[test]
+    fn feeds_without_paging_have_none() {
+        let xml = feed_xml("", "");
+        assert_eq!(parse_paging(&Document::parse(&xml).unwrap()), None);
+    }
+
+    #[test]
+    fn facets_and_paging_come_from_one_tree() {
+        let xml = feed_xml(
+            r#"xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/" xmlns:opds="http://opds-spec.org/2010/catalog" xmlns:thr="http://purl.org/syndication/thread/1.0""#,
+            r#"<opensearch:totalResults>12</opensearch:totalResults>
+            <link rel="http://opds-spec.org/facet" href="?lang=en" title="English"
+                opds:facetGroup="Language" opds:activeFacet="true" thr:count="12"/>
+            <link rel="http://opds-spec.org/facet" href="?lang=fr" title="French"
+                opds:facetGroup="Language"/>"#,
+        );
+        let tree = Document::parse(&xml).unwrap();
+        let base = Url::parse("http://example.com/opds/new").unwrap();
+
+        assert_eq!(parse_paging(&tree).unwrap().total_results, Some(12));
+        let facets = parse_facets(&tree, &base);
+        assert_eq!(
+            facets,
+            [
+                Facet {
+                    title: "English".to_string(),
+                    group: Some("Language".to_string()),
+                    href: Url::parse("http://example.com/opds/new?lang=en").unwrap(),
+                    active: true,
+                    count: Some(12),
+                },
+                Facet {
+                    title: "French".to_string(),
+                    group: Some("Language".to_string()),
+                    href: Url::parse("http://example.com/opds/new?lang=fr").unwrap(),
+                    active: false,
+                    count: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn redirected_roots_become_the_base() {
+        let root = feed_xml(
+            "",
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="cover.jpg" type="image/jpeg"/>
+            </entry>"#,
+        );
+        let srv = MockServer::start(move |req| match req.path.as_str() {
+            "/opds" => Response::new(301, "").with_header("Location", "/opds/"),
+            "/opds/" => Response::new(200, &root),
+            _ => Response::new(404, ""),
+        })
+        .await;
+
+        let mut conn =
+            OnlineConnection::new(&Server::new(srv.url("/opds")), reqwest::Client::new(), None)
+                .await
+                .unwrap();
+        assert_eq!(conn.current_address(), srv.url("/opds/"));
+        let requests = srv.requests();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, ["/opds", "/opds/"]);
+        assert_eq!(requests[1].headers["user-agent"], "ncopds");
+
+        let addr = conn.current_address();
+        let data: Vec<_> = conn
+            .get_page(&addr)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(entry_data)
+            .collect();
+        assert_eq!(data[0].image.as_ref().unwrap(), &srv.url("/opds/cover.jpg"));
+    }
+
+    #[test]
+    fn realm_is_read_from_the_header() {
+        assert_eq!(
+            parse_realm(r#"Basic realm="Calibre", charset="UTF-8""#).as_deref(),
+            Some("Calibre")
+        );
+        assert_eq!(
+            parse_realm("Digest REALM=library, nonce=\"1\"").as_deref(),
+            Some("library")
+        );
+        assert_eq!(parse_realm("Bearer"), None);
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_name_the_realm() {
+        let srv = MockServer::start(|_| {
+            Response::new(401, "").with_header("WWW-Authenticate", r#"Basic realm="Calibre""#)
+        })
+        .await;
+        let mut s = Server::new(srv.url("/opds"));
+        s.username = Some("reader".to_string());
+
+        let err = OnlineConnection::new(&s, reqwest::Client::new(), Some("wrong".to_string()))
+            .await
+            .err()
+            .unwrap();
+        let auth = err.downcast_ref::<AuthenticationRequired>().unwrap();
+        assert_eq!(auth.realm.as_deref(), Some("Calibre"));
+        assert_eq!(
+            err.to_string(),
+            "Authentication failed for realm 'Calibre'."
+        );
+        assert!(srv.requests()[0].headers["authorization"].starts_with("Basic "));
+    }
+
+    #[tokio::test]
+    async fn pages_remember_the_feed_title() {
+        let titled = r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>f</id><title> Project Gutenberg </title>
+            <subtitle>Free ebooks</subtitle><updated>2024-01-01T00:00:00Z</updated></feed>"#;
+        let untitled = r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>f</id><title></title>
+            <updated>2024-01-01T00:00:00Z</updated></feed>"#;
+        let srv = MockServer::start(move |req| match req.path.as_str() {
+            "/untitled" => Response::new(200, untitled),
+            _ => Response::new(200, titled),
+        })
+        .await;
+
+        let mut conn =
+            OnlineConnection::new(&Server::new(srv.url("/opds")), reqwest::Client::new(), None)
+                .await
+                .unwrap();
+        let root = srv.url("/opds");
+        conn.get_page(&root).await.unwrap();
+        let title = conn.feed_title(&root).unwrap();
+        assert_eq!(title.describe(), "Project Gutenberg — Free ebooks");
+
+        let other = srv.url("/untitled");
+        conn.get_page(&other).await.unwrap();
+        assert!(conn.feed_title(&other).is_none());
+    }
+
+    #[tokio::test]
+    async fn loopback_servers_are_browsed_like_any_other() {
+        let root = feed_xml(
+            "",
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/image" href="covers/1.jpg" type="image/jpeg"/>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.epub" type="application/epub+zip"/>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.pdf" type="application/pdf"/>
+            </entry>"#,
+        );
+        let srv = MockServer::start(move |req| match req.path.as_str() {
+            "/opds/" => Response::new(200, &root),
+            "/opds/covers/1.jpg" => Response::new(200, "cover"),
+            "/get/1.epub" => Response::new(200, "epub"),
+            "/get/1.pdf" => Response::new(200, "pdf")
+                .with_header("Content-Disposition", "attachment; filename=Some Book.pdf"),
+            _ => Response::new(404, ""),
+        })
+        .await;
+        let s = Server::new(srv.url("/opds/"));
+        assert_eq!(s.base_url.host_str(), Some("127.0.0.1"));
+        assert!(s.is_loopback());
+        assert_eq!(s.get_domain(), srv.url("/"));
+
+        // loopback servers get a client of their own that accepts self-signed certificates
+        let mut conn = OnlineConnection::new(&s, reqwest::Client::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(conn.current_address(), srv.url("/opds/"));
+
+        let addr = conn.current_address();
+        let data: Vec<_> = conn
+            .get_page(&addr)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(entry_data)
+            .collect();
+        let image = data[0].image.clone().unwrap();
+        assert_eq!(image, srv.url("/opds/covers/1.jpg"));
+        assert_eq!(conn.get_image_bytes(&image).await.as_ref(), b"cover");
+
+        let (epub, _, _) = &data[0].downloads[0];
+        assert_eq!(epub, &srv.url("/get/1.epub"));
+        let (name, bytes) = conn.download(epub).await.unwrap();
+        assert_eq!(
+            (name.as_str(), bytes.as_ref()),
+            ("1.epub", b"epub".as_slice())
+        );
+
+        let (pdf, _, _) = &data[0].downloads[1];
+        let (name, bytes) = conn.download(pdf).await.unwrap();
+        assert_eq!(
+            (name.as_str(), bytes.as_ref()),
+            ("Some Book.pdf", b"pdf".as_slice())
+        );
+    }
+
+    fn retry_header(value: &str) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        let now = Utc::now();
+        assert_eq!(
+            retry_after(&retry_header("12"), now),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(
+            retry_after(&retry_header("3600"), now),
+            Some(MAX_RETRY_WAIT)
+        );
+        assert_eq!(retry_after(&retry_header("soon"), now), None);
+        assert_eq!(retry_after(&reqwest::header::HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn retry_after_reads_http_dates() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            retry_after(&retry_header("Wed, 21 Oct 2015 07:28:20 GMT"), now),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            retry_after(&retry_header("Thu, 22 Oct 2015 07:28:00 GMT"), now),
+            Some(MAX_RETRY_WAIT)
+        );
+        // dates that already passed mean trying again right away
+        assert_eq!(
+            retry_after(&retry_header("Wed, 21 Oct 2015 07:00:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[tokio::test]
+    async fn busy_servers_are_retried() {
+        let srv = MockServer::start({
+            let calls = std::sync::atomic::AtomicUsize::new(0);
+            move |_| match calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => Response::new(429, "").with_header("Retry-After", "0"),
+                _ => Response::new(200, "ok"),
+            }
+        })
+        .await;
+        let url = srv.url("/opds");
+        let (tx, rx) = mpsc::channel();
+
+        let req = reqwest::Client::new().get(url.clone());
+        let response = send_with_retry(req, &url, Some(&tx)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(rx.try_recv().unwrap(), (url, Duration::ZERO));
+        assert_eq!(srv.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn local_search_leaves_the_history_alone() {
+        let dir = Url::from_directory_path(scratch_dir(
+            "search",
+            &["alpha.epub", "beta.epub", "gamma.pdf"],
+        ))
+        .unwrap();
+        let mut conn = LocalConnection::new(dir.clone());
+        conn.navigate_to(&dir).await.unwrap();
+        let before = conn.history.len();
+
+        let results = conn.search("beta").await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(get_title_for_entry(&results[0]), "beta.epub");
+        assert_eq!(conn.history.len(), before);
+        assert_eq!(conn.current_address(), dir);
+
+        // going back leaves the results, not the directory
+        assert_eq!(conn.back().await.unwrap().len(), 3);
+        assert_eq!(conn.history.len(), before);
+        std::fs::remove_dir_all(dir.to_file_path().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recursive_search_stops_at_the_depth_limit() {
+        let dir = Url::from_directory_path(scratch_dir("depth", &["book.epub"])).unwrap();
+        let root = dir.to_file_path().unwrap();
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::File::create(root.join("a/book.epub")).unwrap();
+        std::fs::File::create(root.join("a/b/book.epub")).unwrap();
+
+        let mut conn = LocalConnection::new(dir.clone());
+        conn.recursive_search = true;
+        conn.max_search_depth = 1;
+        let results = conn.search("book").await.unwrap();
+        let mut titles: Vec<_> = results.iter().map(get_title_for_entry).collect();
+        titles.sort();
+        assert_eq!(titles, ["a/book.epub", "book.epub"]);
+
+        conn.max_search_depth = 2;
+        assert_eq!(conn.search("book").await.unwrap().len(), 3);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn recursive_search_skips_unreadable_folders() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // root may read anywhere
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = Url::from_directory_path(scratch_dir("unreadable", &["book.epub"])).unwrap();
+        let locked = dir.to_file_path().unwrap().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::File::create(locked.join("book.epub")).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        let mut conn = LocalConnection::new(dir.clone());
+        conn.recursive_search = true;
+        conn.max_search_depth = 5;
+        let results = conn.search("book").await;
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let titles: Vec<_> = results.unwrap().iter().map(get_title_for_entry).collect();
+        assert_eq!(titles, ["book.epub"]);
+        std::fs::remove_dir_all(dir.to_file_path().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unchanged_pages_reuse_their_entries() {
+        let books = feed_xml(
+            "",
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.epub" type="application/epub+zip"/>
+            </entry>"#,
+        );
+        let root = feed_xml("", "");
+        let srv = MockServer::start(move |req| match req.path.as_str() {
+            "/books"
+                if req
+                    .headers
+                    .get("if-none-match")
+                    .is_some_and(|e| e == "\"v1\"") =>
+            {
+                Response::new(304, "")
+            }
+            "/books" => Response::new(200, &books)
+                .with_header("ETag", "\"v1\"")
+                .with_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            _ => Response::new(200, &root),
+        })
+        .await;
+
+        let mut conn =
+            OnlineConnection::new(&Server::new(srv.url("/opds")), reqwest::Client::new(), None)
+                .await
+                .unwrap();
+        let url = srv.url("/books");
+        let fetched: Vec<_> = conn
+            .get_page(&url)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(entry_data)
+            .collect();
+
+        // refreshing asks whether the page changed
+        conn.invalidate(&url);
+        let reused: Vec<_> = conn
+            .get_page(&url)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(entry_data)
+            .collect();
+        assert_eq!(reused.len(), 1);
+        assert_eq!(reused[0].title, fetched[0].title);
+        assert_eq!(reused[0].downloads[0].0, srv.url("/get/1.epub"));
+
+        let revalidated = srv.requests().pop().unwrap();
+        assert_eq!(revalidated.headers["if-none-match"], "\"v1\"");
+        assert_eq!(
+            revalidated.headers["if-modified-since"],
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+        // the reused page is cached again
+        conn.get_page(&url).await.unwrap();
+        assert_eq!(srv.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn large_folders_report_progress() {
+        let names: Vec<String> = (1..=1200).map(|i| format!("book{}.epub", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let dir = Url::from_directory_path(scratch_dir("large", &names)).unwrap();
+        std::fs::create_dir(dir.to_file_path().unwrap().join("series")).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let mut conn = LocalConnection::new(dir.clone());
+        conn.report_progress(tx);
+        let entries = conn.get_page(&dir).await.unwrap();
+
+        let reports: Vec<_> = rx.try_iter().collect();
+        assert_eq!(reports.len(), 1201 / PROGRESS_STEP);
+        assert!(reports
+            .iter()
+            .all(|(url, _, total)| *url == dir && *total == 1201));
+        assert_eq!(reports[0].1, PROGRESS_STEP);
+
+        // still sorted once everything was read: folders first, numbers by value
+        let titles: Vec<_> = entries.iter().take(4).map(get_title_for_entry).collect();
+        assert_eq!(titles, ["series", "book1.epub", "book2.epub", "book3.epub"]);
+        assert_eq!(get_title_for_entry(&entries[1200]), "book1200.epub");
+        std::fs::remove_dir_all(dir.to_file_path().unwrap()).unwrap();
+    }
+}
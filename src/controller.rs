@@ -1,21 +1,39 @@
 use crate::config::{write_to_config, Config};
-use crate::connection::{Connection, LocalConnection, OnlineConnection};
-use crate::model::EntryType;
-use crate::server::{store_password, Server};
-use crate::ui::uiroot::{UIMessage, UIRoot};
-use crate::utils::{directory_str_to_url, rename_full_dir_fname};
-use chrono::prelude::*;
+use crate::connection::{
+    check_format, download, ping, AuthenticationRequired, Connection, FormatStatus, Health,
+    LocalConnection, OnlineConnection, ParseProgress, Throttled, UrlSuggestion,
+};
+use crate::credentials::{
+    backend, CredentialBackend, CredentialStore, FileStore, KeyringStore, MemoryStore,
+};
+use crate::logging;
+use crate::model::{
+    get_title_for_entry, preferred_download, EntryData, EntryType, Facet, FinishedDownload,
+    PageState,
+};
+use crate::server::{delete_password, store_password, Server};
+use crate::session::Session;
+use crate::ui::uiroot::{ConnectionStatus, UIMessage, UIRoot};
+use crate::utils::{
+    check_writable, copy_path, copy_to_clipboard, directory_str_to_url, human_readable_size,
+    is_feed_file, move_path, move_to_trash, open_with, remove_partial_downloads,
+    rename_full_dir_fname, Periodic,
+};
 use image::load_from_memory;
 use keyring;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use opener::open;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::fs::{remove_dir, remove_file};
-use std::path::PathBuf;
+use std::fs::{create_dir, read_dir, remove_dir, remove_file};
+use std::future::Future;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use termsize;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
 use url::Url;
 
 #[derive(Clone, Debug)]
@@ -24,16 +42,53 @@ pub enum ControllerMessage {
     EntrySelected(EntryType),
     /// adds a connection  
     AddConnection(String, Server, Option<String>),
+    /// browses the catalog at the URL for this session only, without adding it to the config
+    QuickOpen(String),
+    /// tries to connect to a server without adding it and reports how it went
+    TestConnection(Server, Option<String>),
+    /// adds a connection from the connection dialog, or edits the one named first; names used
+    /// by other connections are refused
+    SaveConnection(Option<String>, String, Server, Option<String>),
     /// changes the currently active connection
     ChangeConnection(String),
     /// moves up a directory in the current connection and updates the UI
     GoBack(),
+    /// moves the current connection back to its root, forgetting the pages visited on the way
+    GoHome(),
+    /// follows the current page's link to its parent feed, or goes back if it has none
+    GoUp(),
+    /// follows the current page's link to the catalog's start feed, or goes home if it has none
+    GoStart(),
     /// opens a file URL using the OS mimetype handler (e.g. xdg-open)
     Open(Url),
+    /// opens the folder containing a file in the OS file manager
+    Reveal(Url),
+    /// puts the filesystem path of a local file on the clipboard
+    CopyPath(Url),
     /// moves the currently active connection to the specified URL
     Navigate(Url),
     /// downloads the file at the specified URL to the download directory
     Download(Url),
+    /// downloads the file at the specified URL, then opens it once it is saved
+    DownloadAndOpen(Url),
+    /// adds a download that finished to the recent downloads
+    DownloadFinished(FinishedDownload),
+    /// opens the recent downloads, or closes them if they are open
+    ShowRecentDownloads(),
+    /// checks which of the entry's download links work, then shows its download menu
+    VerifyFormats(Box<EntryData>),
+    /// shows what the server sent for the current page
+    ShowRawPage(),
+    /// downloads the entry in the most preferred format without asking
+    QuickDownload(EntryType),
+    /// shows more books by the entry's author
+    BrowseAuthor(EntryType),
+    /// puts the entry's links and how they were read on the clipboard, for bug reports
+    CopyDebugInfo(Box<EntryData>),
+    /// asks whether every entry on the current page should be downloaded
+    ConfirmDownloadAll(),
+    /// downloads every URL
+    DownloadAll(Vec<Url>),
     /// downloads the image for the entry and stores it in the UI
     RequestImage(EntryType),
     /// renames a file
@@ -42,6 +97,288 @@ pub enum ControllerMessage {
     Delete(Url),
     /// uses the connection's available search function to search for a given string
     Search(String),
+    /// opens the search dialog, offering the active connection's recent searches
+    ShowSearch(),
+    /// asks for the address of a page to go to, starting from the active connection's current one
+    ShowAddressBar(),
+    /// shows the next (true) or previous (false) page of the current search results
+    SearchPage(bool),
+    /// lists the facets the current page can be narrowed with
+    ShowFacets(),
+    /// goes to the page narrowed by the facet, keeping search results search results
+    ApplyFacet(Facet),
+    /// reloads the current page of the active connection, skipping the cache
+    Refresh(),
+    /// forgets every page cached by the connection with the name
+    ClearCache(String),
+    /// forgets every page cached by any connection
+    ClearAllCaches(),
+    /// switches the active connection between browsing with and without credentials
+    ToggleAnonymous(),
+    /// draws the interface with the named preset theme, or with none, and remembers the choice
+    SetTheme(Option<String>),
+    /// opens the theme file in the user's editor
+    EditTheme(),
+    /// creates a folder with the given name inside the current local directory
+    CreateDirectory(PathBuf),
+    /// puts files on the clipboard so they can be moved or copied somewhere else
+    Mark(Vec<PathBuf>, Transfer),
+    /// moves or copies the files on the clipboard into the current local directory
+    Paste(),
+    /// moves a file to a new path
+    MoveFile(PathBuf, PathBuf),
+    /// copies a file to a new path
+    CopyFile(PathBuf, PathBuf),
+    /// reverses the most recent delete, rename or move
+    Undo(),
+    /// removes every notification from the screen
+    DismissNotifications(),
+    /// hides the details panel, or shows it again
+    ToggleDetails(),
+    /// sorts catalog pages by date added, or back to the order of the feed
+    ToggleSortByDate(),
+    /// makes logging more verbose, going from trace back to error
+    CycleLogLevel(),
+    /// redraws the file view after files were selected or unselected
+    SelectionChanged(),
+    /// handles every message in order, i.e. after a single confirmation
+    Batch(Vec<ControllerMessage>),
+    /// the user closed the password prompt of the named connection without logging in
+    LoginCancelled(String),
+    /// unlocks the encrypted credentials file with the passphrase and connects to the servers
+    Unlock(String),
+    /// quits ncopds; asks first if downloads are still running unless true
+    Quit(bool),
+}
+
+/// Everything needed to reverse the last destructive file operation.
+#[derive(Clone, Debug)]
+enum UndoRecord {
+    /// a file was moved from `original` into the trash at `trashed`
+    Delete { original: PathBuf, trashed: PathBuf },
+    /// a file was renamed from `from` to `to`
+    Rename { from: PathBuf, to: PathBuf },
+    /// a file was moved from `from` to `to`
+    Move { from: PathBuf, to: PathBuf },
+}
+
+impl UndoRecord {
+    /// Returns where the file is now and where it has to go back to.
+    fn paths(&self) -> (&PathBuf, &PathBuf) {
+        match self {
+            UndoRecord::Delete { original, trashed } => (trashed, original),
+            UndoRecord::Rename { from, to } | UndoRecord::Move { from, to } => (to, from),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            UndoRecord::Delete { .. } => "Restored from trash",
+            UndoRecord::Rename { .. } => "Undid rename",
+            UndoRecord::Move { .. } => "Undid move",
+        }
+    }
+}
+
+/// Outcome of connecting to a server in the background: the connection's name, its server info,
+/// its password and either the connection or the reason it failed.
+type ConnectResult = (
+    String,
+    Server,
+    Option<String>,
+    Result<OnlineConnection, ConnectFailure>,
+);
+
+/// Why connecting to a server failed.
+struct ConnectFailure {
+    reason: String,
+    /// what the server asked for, if it wants credentials
+    auth: Option<AuthenticationRequired>,
+}
+
+/// How often a server may reject a connection's password before the user is asked for another
+/// one. Fewer rejections are reported as a failed connection.
+const MAX_AUTH_FAILURES: u32 = 2;
+
+/// Counts a rejected login and tells whether the user should be asked for credentials. A
+/// connection without a password has not been rejected yet, so it asks right away.
+///
+/// # Arguments
+///
+/// * `failures` - Rejected logins per connection; updated in place.
+/// * `name` - Name of the connection.
+/// * `had_password` - Whether the connection tried to log in with a password.
+///
+fn count_auth_failure(failures: &mut HashMap<String, u32>, name: &str, had_password: bool) -> bool {
+    if !had_password {
+        return true;
+    }
+    let n = failures.entry(name.to_string()).or_default();
+    *n += 1;
+    *n >= MAX_AUTH_FAILURES
+}
+
+/// Requests from the UI that load a page of the current connection in the background.
+#[derive(Debug)]
+enum PageRequest {
+    Search(String),
+    SearchPage(bool),
+    ApplyFacet(Facet),
+    Back,
+}
+
+impl PageRequest {
+    /// Title of the file view while the page loads.
+    fn describe(&self) -> String {
+        match self {
+            PageRequest::Search(query) => format!("Searching for {}", query),
+            PageRequest::SearchPage(_) => "Loading search results".to_string(),
+            PageRequest::ApplyFacet(facet) => format!("Loading {}", facet.title),
+            PageRequest::Back => "Going back".to_string(),
+        }
+    }
+}
+
+/// A page requested from the UI that finished loading in the background.
+struct PageLoaded {
+    /// name of the connection the page was loaded on
+    connection: String,
+    /// what was searched for, if the page holds the results of a new search
+    query: Option<String>,
+    /// title and entries of the page, or why it could not be loaded
+    result: Result<(String, Vec<EntryType>), String>,
+}
+
+/// The download links of an entry, checked in the background with verify_formats.
+struct FormatsChecked {
+    /// entry whose links were checked
+    data: Box<EntryData>,
+    /// what was found for each link that was checked
+    statuses: Vec<(Url, FormatStatus)>,
+}
+
+/// How long a download link may take to answer its HEAD request before it is taken to be
+/// unreachable.
+const FORMAT_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks download links with HEAD requests, all at once. Links that do not answer within the
+/// time limit are unreachable.
+///
+/// # Arguments
+///
+/// * `links` - Each link with the format the feed advertised for it and its HEAD request.
+/// * `limit` - How long each check may take.
+///
+async fn check_formats(
+    links: Vec<(Url, String, reqwest::RequestBuilder)>,
+    limit: Duration,
+) -> Vec<(Url, FormatStatus)> {
+    let mut checks = JoinSet::new();
+    for (href, mt, req) in links {
+        checks.spawn(async move {
+            let status = tokio::time::timeout(limit, check_format(req, &mt))
+                .await
+                .unwrap_or(FormatStatus::Unreachable);
+            (href, status)
+        });
+    }
+
+    let mut statuses = vec![];
+    while let Some(res) = checks.join_next().await {
+        if let Ok(checked) = res {
+            statuses.push(checked);
+        }
+    }
+    statuses
+}
+
+/// Moves a freshly created connection to the page it was on when ncopds was last closed. If
+/// that page cannot be loaded anymore, the connection stays at its root.
+///
+/// # Arguments
+///
+/// * `conn` - The new connection.
+/// * `addr` - Page it was on.
+///
+/// # Errors
+///
+/// Errors if the page cannot be loaded.
+///
+async fn restore_page(conn: &mut dyn Connection, addr: &Url) -> Result<(), Box<dyn Error>> {
+    if &conn.current_address() == addr {
+        return Ok(());
+    }
+
+    // load the page first so a failure does not leave a dead entry in the history
+    conn.get_page(addr).await?;
+    conn.navigate_to(addr).await?;
+    Ok(())
+}
+
+/// How long a server may take to answer a ping before it is taken to be unreachable.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks whether a server is up, giving up once the time limit has passed.
+///
+/// # Arguments
+///
+/// * `client` - Client to send the request with.
+/// * `s` - Server to check.
+/// * `password` - Password for authentication.
+/// * `limit` - How long the server may take to answer.
+///
+async fn ping_with_timeout(
+    client: &reqwest::Client,
+    s: &Server,
+    password: &Option<String>,
+    limit: Duration,
+) -> Health {
+    tokio::time::timeout(limit, ping(client, s, password))
+        .await
+        .unwrap_or(Health::Unreachable)
+}
+
+/// Carries out a request for a page and titles the page.
+///
+/// # Arguments
+///
+/// * `conn` - Connection to load the page on.
+/// * `request` - What to load.
+///
+/// # Errors
+///
+/// Errors if the page cannot be loaded.
+///
+async fn load_page(
+    conn: &mut dyn Connection,
+    request: PageRequest,
+) -> Result<(String, Vec<EntryType>), Box<dyn Error>> {
+    match request {
+        PageRequest::Search(query) => {
+            let res = conn.search(&query).await?;
+            Ok((search_title(conn, &query, None, res.len()), res))
+        }
+        PageRequest::SearchPage(forward) => {
+            let (query, res) = conn.turn_search_page(forward).await?;
+            Ok((search_title(conn, &query, None, res.len()), res))
+        }
+        PageRequest::ApplyFacet(facet) => {
+            let res = conn.apply_facet(&facet).await?;
+            Ok((current_title(conn, res.len()), res))
+        }
+        // results of a search are titled as such when they are gone back to
+        PageRequest::Back => {
+            let res = conn.back().await?;
+            Ok((current_title(conn, res.len()), res))
+        }
+    }
+}
+
+/// What happens to a marked file once it is pasted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    Move,
+    Copy,
 }
 
 pub struct Controller {
@@ -53,8 +390,121 @@ pub struct Controller {
     client: reqwest::Client,
     config: Config,
     config_path: Box<std::path::PathBuf>,
-    refresh_timer: u32,
+    /// how often the pages of online connections are reloaded
+    refresh_interval: Duration,
     download_directory: Url,
+    /// files marked to be moved or copied by the next paste
+    clipboard: Option<(Vec<PathBuf>, Transfer)>,
+    /// the last destructive file operation, if it can be undone
+    last_undo: Option<UndoRecord>,
+    /// connections being established in the background report back on this channel
+    connect_tx: mpsc::Sender<ConnectResult>,
+    connect_rx: mpsc::Receiver<ConnectResult>,
+    /// requests that wait because the server asked them to are reported on this channel
+    throttle_tx: mpsc::Sender<Throttled>,
+    throttle_rx: mpsc::Receiver<Throttled>,
+    /// pages the UI asked for are reported on this channel once they are loaded
+    page_tx: mpsc::Sender<PageLoaded>,
+    page_rx: mpsc::Receiver<PageLoaded>,
+    /// download links checked with verify_formats are reported on this channel
+    formats_tx: mpsc::Sender<FormatsChecked>,
+    formats_rx: mpsc::Receiver<FormatsChecked>,
+    /// connections converting the entries of large pages report how far along they are here
+    progress_tx: mpsc::Sender<ParseProgress>,
+    progress_rx: mpsc::Receiver<ParseProgress>,
+    /// why connecting failed, for connections that could not be established
+    connection_errors: HashMap<String, String>,
+    /// how often each connection's password was rejected since it last connected
+    auth_failures: HashMap<String, u32>,
+    /// where passwords are stored; missing until the credentials file is unlocked
+    credentials: Option<Box<dyn CredentialStore>>,
+    /// state remembered between runs
+    session: Session,
+    /// limits how many downloads run at the same time
+    download_slots: Arc<Semaphore>,
+    /// downloads that were started, so they can be stopped when quitting
+    downloads: Vec<JoinHandle<()>>,
+    /// connections opened from a URL for this session only; they are never written to the
+    /// config and their passwords are not stored
+    temporary: HashSet<String>,
+    /// names connections were renamed from; connection attempts still running under them are
+    /// dropped when they finish
+    renamed: HashSet<String>,
+    /// servers whose last ping has not finished yet; they are not pinged again until it has
+    pinging: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// results of checking download links with verify_formats, kept until ncopds is closed
+    format_status: HashMap<Url, FormatStatus>,
+    /// downloads that finished since ncopds was started, most recent first
+    recent_downloads: VecDeque<FinishedDownload>,
+}
+
+/// How many finished downloads are listed among the recent downloads.
+const RECENT_DOWNLOADS: usize = 20;
+
+/// Builds the title for a page of search results, including the facet they were narrowed with
+/// and which results are shown if the feed says how many there are.
+///
+/// # Arguments
+///
+/// * `conn` - Connection showing the results.
+/// * `query` - What was searched for.
+/// * `facet` - Title of the facet the results were narrowed with, if any.
+/// * `shown` - Number of results on the page.
+///
+fn search_title(conn: &dyn Connection, query: &str, facet: Option<&str>, shown: usize) -> String {
+    let mut title = format!("Search results for {}", query);
+    if let Some(facet) = facet {
+        title.push_str(&format!(" · {}", facet));
+    }
+    if let Some(range) = conn
+        .paging(&conn.current_address())
+        .and_then(|pg| pg.describe(shown))
+    {
+        title.push_str(&format!(" ({})", range));
+    }
+    title
+}
+
+/// Builds the title for the page a connection is on: the feed's own title if it has one, its
+/// address otherwise.
+///
+/// # Arguments
+///
+/// * `conn` - Connection showing the page.
+///
+fn page_title(conn: &dyn Connection) -> String {
+    let addr = conn.current_address();
+    conn.feed_title(&addr)
+        .map(|t| t.describe())
+        .unwrap_or(addr.to_string())
+}
+
+/// Describes which results the page a connection is on shows, i.e. "showing 1–20 of 437", if
+/// the feed gave paging hints; empty otherwise.
+///
+/// # Arguments
+///
+/// * `conn` - Connection showing the page.
+/// * `shown` - Number of entries on the page.
+///
+fn paging_note(conn: &dyn Connection, shown: usize) -> String {
+    conn.paging(&conn.current_address())
+        .and_then(|pg| pg.describe(shown))
+        .unwrap_or_default()
+}
+
+/// Builds the title for the page a connection is on, titling search results as such.
+///
+/// # Arguments
+///
+/// * `conn` - Connection showing the page.
+/// * `shown` - Number of entries on the page.
+///
+fn current_title(conn: &dyn Connection, shown: usize) -> String {
+    match conn.search_query(&conn.current_address()) {
+        Some((query, facet)) => search_title(conn, &query, facet.as_deref(), shown),
+        None => page_title(conn),
+    }
 }
 
 impl Controller {
@@ -76,15 +526,27 @@ impl Controller {
         t_size: termsize::Size,
     ) -> Result<Controller, Box<dyn Error>> {
         let (tx, rx) = mpsc::channel::<ControllerMessage>();
+        let (connect_tx, connect_rx) = mpsc::channel::<ConnectResult>();
+        let (throttle_tx, throttle_rx) = mpsc::channel::<Throttled>();
+        let (progress_tx, progress_rx) = mpsc::channel::<ParseProgress>();
+        let (page_tx, page_rx) = mpsc::channel::<PageLoaded>();
+        let (formats_tx, formats_rx) = mpsc::channel::<FormatsChecked>();
         let download_directory = directory_str_to_url(&config.download_directory)?;
 
-        let lc = LocalConnection::new(download_directory.clone());
+        let mut lc = LocalConnection::new(download_directory.clone());
+        lc.recursive_search = config.recursive_search;
+        lc.max_search_depth = config.max_search_depth;
+        lc.report_progress(progress_tx.clone());
         let client = reqwest::Client::builder()
             .user_agent("ncopds")
             .build()
             .unwrap();
 
-        let ui = UIRoot::new(tx.clone(), theme_path, t_size);
+        let ui = UIRoot::new(tx.clone(), theme_path, t_size, &config);
+        // logging only works once the UI is set up
+        for warning in config.header_warnings() {
+            log::warn!("{}", warning);
+        }
         let mut connections = HashMap::<String, Arc<Mutex<dyn Connection>>>::new();
 
         connections.insert("local".to_string(), Arc::new(Mutex::new(lc)));
@@ -96,10 +558,33 @@ impl Controller {
             current_tab: "local".to_string(),
             connections,
             client,
-            config,
             config_path: Box::new(config_path.to_owned()),
             download_directory,
-            refresh_timer: 30 * 5 * 60, // fps * time in seconds
+            refresh_interval: Duration::from_secs(5 * 60),
+            clipboard: None,
+            last_undo: None,
+            connect_tx,
+            connect_rx,
+            throttle_tx,
+            throttle_rx,
+            page_tx,
+            page_rx,
+            formats_tx,
+            formats_rx,
+            progress_tx,
+            progress_rx,
+            connection_errors: HashMap::new(),
+            auth_failures: HashMap::new(),
+            credentials: None,
+            session: Session::read(&config.session_path()),
+            download_slots: Arc::new(Semaphore::new(config.max_downloads.max(1))),
+            downloads: vec![],
+            temporary: HashSet::new(),
+            renamed: HashSet::new(),
+            pinging: Arc::default(),
+            format_status: HashMap::new(),
+            recent_downloads: VecDeque::new(),
+            config,
         })
     }
 
@@ -107,20 +592,34 @@ impl Controller {
     /// over each server in memory and retrieves its password from the OS keyring (if applicable).
     /// If the password is present (or unneeded), it establishes a connection and makes it
     /// available in the UI. Connections that are missing passwords ask the user to input the
-    /// password, which is again stored in the OS keyring.
-    ///
-    /// # Panics
+    /// password, which is again stored in the OS keyring. Servers whose credentials cannot be
+    /// read are skipped and shown as failed connections.
     ///
-    /// Panics can occur if there is something wrong with the OS keyring.
+    /// Every server is connected to at once, so startup takes as long as the slowest server
+    /// rather than all of them together. Connections become usable as they finish (see
+    /// `connection_finished`), but all of them are added to the View menu in order of their
+    /// names before that, so their numbers do not depend on which server answers first.
     ///
     pub async fn connect_to_servers(&mut self) {
         // test
         let mut missing_passwords = vec![];
         let servers = self.config.servers.clone().unwrap_or_default();
 
-        for (name, server) in servers.iter() {
+        let mut results: Vec<_> = match self.credentials.as_deref() {
+            Some(store) => servers
+                .iter()
+                .map(|(name, server)| (name, server, server.get_password(store)))
+                .collect(),
+            None => return,
+        };
+        // connections are numbered in the order they are added, which should not change
+        // between runs
+        results.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut connecting = JoinSet::new();
+        for (name, server, result) in results {
             let mut missing_password = false;
-            let password = match server.get_password() {
+            let password = match result {
                 Ok(pwd) => pwd,
                 Err(err) => match err {
                     keyring::Error::NoEntry => {
@@ -128,27 +627,51 @@ impl Controller {
                         None
                     }
                     err => {
-                        panic!(
-                            "Could not retrieve password for connection {:?}:{}",
-                            server, err
-                        );
+                        log::error!("could not retrieve password for {}: {}", name, err);
+                        self.credentials_failed(name, server, err.to_string())
+                            .expect("failed to send UI message");
+                        continue;
                     }
                 },
             };
 
+            // listed right away, so the order does not change as connections finish
+            self.ui
+                .ui_tx
+                .send(UIMessage::AddConnection(
+                    name.clone(),
+                    server.clone(),
+                    password.clone(),
+                ))
+                .expect("failed to send UI message");
+            self.ui
+                .ui_tx
+                .send(UIMessage::ConnectionStatus(
+                    name.clone(),
+                    ConnectionStatus::Connecting,
+                ))
+                .expect("failed to send UI message");
+
             if !missing_password {
-                self.tx
-                    .send(ControllerMessage::AddConnection(
-                        name.to_string(),
-                        server.clone(),
-                        password,
-                    ))
-                    .expect("could not send controller message");
+                connecting.spawn(self.connect(name.clone(), server.clone(), password));
             } else {
                 missing_passwords.push(name);
             }
         }
 
+        // results are picked up in run() in the order they arrive
+        let connect_tx = self.connect_tx.clone();
+        tokio::spawn(async move {
+            while let Some(joined) = connecting.join_next().await {
+                match joined {
+                    Ok(result) => connect_tx
+                        .send(result)
+                        .expect("failed to send connection result"),
+                    Err(err) => log::error!("connecting to a server failed: {}", err),
+                }
+            }
+        });
+
         // not sure if maybe this should be moved out into a separate function
         for server_name in missing_passwords {
             let server = servers.get(server_name).unwrap();
@@ -157,11 +680,302 @@ impl Controller {
                 .send(UIMessage::PasswordPrompt(
                     server_name.clone(),
                     server.clone(),
+                    None,
                 ))
                 .expect("failed to send UI message");
         }
     }
 
+    /// Opens the credential store picked in the config and connects to the servers. The
+    /// encrypted file needs a passphrase first, so the user is asked for it and the servers are
+    /// connected to once it is unlocked. If passwords are not stored, every server with a username
+    /// asks for its password.
+    ///
+    async fn open_credentials(&mut self) -> Result<(), Box<dyn Error>> {
+        // nothing to unlock when passwords only live for this session
+        if !self.config.store_passwords {
+            self.credentials = Some(Box::<MemoryStore>::default());
+            self.connect_to_servers().await;
+            return Ok(());
+        }
+
+        match backend(self.config.credential_store) {
+            CredentialBackend::Keyring => {
+                self.credentials = Some(Box::new(KeyringStore));
+                self.connect_to_servers().await;
+            }
+            CredentialBackend::File => {
+                let exists = self.config.credentials_path().exists();
+                self.ui.ui_tx.send(UIMessage::PassphrasePrompt(exists))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes a connection that finished connecting in the background available, or records why
+    /// it failed. A failed connection does not replace one that is already working.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - Name, server info, password and outcome of the connection attempt.
+    ///
+    async fn connection_finished(&mut self, result: ConnectResult) -> Result<(), Box<dyn Error>> {
+        let (name, s, pwd, res) = result;
+        if self.renamed.contains(&name) {
+            return Ok(());
+        }
+
+        match res {
+            Ok(oc) => {
+                self.ui.ui_tx.send(UIMessage::ConnectionSearchable(
+                    name.clone(),
+                    oc.supports_search(),
+                ))?;
+                self.connections
+                    .insert(name.clone(), Arc::new(Mutex::new(oc)));
+                self.connection_errors.remove(&name);
+                self.auth_failures.remove(&name);
+                self.ui.ui_tx.send(UIMessage::ConnectionStatus(
+                    name.clone(),
+                    ConnectionStatus::Ready,
+                ))?;
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ConnectionHealth(name.clone(), Health::Reachable))?;
+
+                if self.temporary.contains(&name) {
+                    // opened on request, so it is shown right away
+                    return self.change_connection(name).await;
+                }
+
+                self.update_config(&name, &s)?;
+
+                // only switch back if the user has not moved on since launching
+                if self.current_tab == "local"
+                    && self.session.active_connection.as_ref() == Some(&name)
+                {
+                    self.session.active_connection = None;
+                    self.change_connection(name).await?;
+                }
+            }
+            Err(ConnectFailure { reason: err, auth }) => {
+                log::error!("could not connect to {}: {}", name, err);
+
+                // the connection only counts as failed if the user gives up on logging in
+                if let Some(auth) = auth {
+                    if count_auth_failure(&mut self.auth_failures, &name, pwd.is_some()) {
+                        self.connection_errors.insert(name.clone(), err);
+                        self.ui
+                            .ui_tx
+                            .send(UIMessage::PasswordPrompt(name, s, Some(auth)))?;
+                        return Ok(());
+                    }
+                }
+
+                let status = if self.connections.contains_key(&name) {
+                    ConnectionStatus::Ready
+                } else {
+                    ConnectionStatus::Failed
+                };
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ConnectionStatus(name.clone(), status))?;
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Connection failed".to_string(),
+                    format!("Could not connect to {}: {}", name, err),
+                ))?;
+                self.connection_errors.insert(name, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Connects to a server and moves it to the page it was on when ncopds was last closed, if
+    /// `restore_last_page` is enabled. The returned future can run on its own;
+    /// connection_finished handles its result.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection.
+    /// * `s` - Server to connect to.
+    /// * `pwd` - Password to log in with, if any.
+    ///
+    fn connect(
+        &self,
+        name: String,
+        s: Server,
+        pwd: Option<String>,
+    ) -> impl Future<Output = ConnectResult> + Send + 'static {
+        let client = self.client.clone();
+        let tx_clone = self.ui.ui_tx.clone();
+        let throttle_tx = self.throttle_tx.clone();
+        let progress_tx = self.progress_tx.clone();
+        let keep_raw = self.config.keep_raw_feeds;
+        let last_page = match self.config.restore_last_page && !self.temporary.contains(&name) {
+            true => self.config.last_pages.get(&name).cloned(),
+            false => None,
+        };
+
+        async move {
+            let mut res = OnlineConnection::new(&s, client, pwd.clone())
+                .await
+                .map(|mut oc| {
+                    oc.report_throttling(throttle_tx);
+                    oc.report_progress(progress_tx);
+                    if keep_raw {
+                        oc.keep_raw_pages();
+                    }
+                    oc
+                })
+                .map_err(|err| {
+                    // the configured URL only changes if the user agrees
+                    if let Some(suggestion) = err.downcast_ref::<UrlSuggestion>() {
+                        let mut server = s.clone();
+                        server.base_url = suggestion.url.clone();
+                        tx_clone
+                            .send(UIMessage::Confirm(
+                                "Connection failed".to_string(),
+                                format!(
+                                    "Could not connect to {}: {}.\n\nThis server responded at {} — use that instead?",
+                                    name, suggestion.reason, suggestion.url
+                                ),
+                                ControllerMessage::AddConnection(
+                                    name.clone(),
+                                    server,
+                                    pwd.clone(),
+                                ),
+                            ))
+                            .expect("failed to send UI message");
+                    }
+                    // whether to ask for credentials is decided once the result is in
+                    ConnectFailure {
+                        reason: err.to_string(),
+                        auth: err.downcast_ref::<AuthenticationRequired>().cloned(),
+                    }
+                });
+
+            if let (Ok(oc), Some(addr)) = (&mut res, last_page) {
+                let restored = restore_page(oc, &addr).await.map_err(|e| e.to_string());
+                if let Err(err) = restored {
+                    tx_clone
+                        .send(UIMessage::ShowNotification(
+                            format!("Could not restore {}", name),
+                            format!("{} ({}); returning to root.", addr, err),
+                        ))
+                        .expect("failed to send UI message");
+                }
+            }
+
+            (name, s, pwd, res)
+        }
+    }
+
+    /// Shows a connection whose credentials could not be loaded as failed so the rest of the
+    /// servers can still load.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection
+    /// * `server` - Server info of the connection
+    /// * `err` - Why the credentials could not be loaded
+    ///
+    fn credentials_failed(
+        &mut self,
+        name: &str,
+        server: &Server,
+        err: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.ui.ui_tx.send(UIMessage::AddConnection(
+            name.to_string(),
+            server.clone(),
+            None,
+        ))?;
+        self.ui.ui_tx.send(UIMessage::ConnectionStatus(
+            name.to_string(),
+            ConnectionStatus::Failed,
+        ))?;
+        self.ui.ui_tx.send(UIMessage::ShowNotification(
+            "Attention".to_string(),
+            format!("Couldn't load credentials for {}: {}", name, err),
+        ))?;
+        self.connection_errors.insert(
+            name.to_string(),
+            format!("couldn't load credentials ({})", err),
+        );
+        Ok(())
+    }
+
+    /// Checks whether a name is taken by a configured connection or one that is being
+    /// established.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name to look up.
+    ///
+    fn connection_exists(&self, name: &str) -> bool {
+        name == "local"
+            || self.connections.contains_key(name)
+            || self.connection_errors.contains_key(name)
+            || self
+                .config
+                .servers
+                .as_ref()
+                .is_some_and(|s| s.contains_key(name))
+    }
+
+    /// Removes everything kept under a connection's old name once it is renamed: its server and
+    /// saved page in the config, the connection itself, its search history, its stored password
+    /// and its entries in the menus. The connection is then added under its new name; if it was
+    /// open, it is switched to once it has connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - Name the connection had.
+    /// * `new` - Name it is renamed to.
+    /// * `s` - Server the connection is saved with under its new name.
+    ///
+    async fn rename_connection(
+        &mut self,
+        old: &str,
+        new: &str,
+        s: &Server,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.current_tab == old {
+            self.change_connection("local".to_string()).await?;
+            self.session.active_connection = Some(new.to_string());
+        } else if self.session.active_connection.as_deref() == Some(old) {
+            self.session.active_connection = Some(new.to_string());
+        }
+
+        if let Some(server) = self.config.remove_server(old) {
+            // the renamed connection still logs in with the password stored for the same user
+            let same_login = server.username == s.username && server.base_url == s.base_url;
+            if let (false, Some(store)) = (same_login, self.credentials.as_deref_mut()) {
+                if let Err(err) = delete_password(store, &server) {
+                    log::error!("could not remove the password of {}: {}", old, err);
+                }
+            }
+        }
+        write_to_config(&self.config, &self.config_path.to_owned())?;
+
+        self.connections.remove(old);
+        self.connection_errors.remove(old);
+        self.auth_failures.remove(old);
+        self.renamed.insert(old.to_string());
+
+        self.session.search_history.remove(old);
+        if let Err(err) = self.session.write(&self.config.session_path()) {
+            log::error!("could not save the session: {}", err);
+        }
+
+        self.ui.ui_tx.send(UIMessage::RenameConnection(
+            old.to_string(),
+            new.to_string(),
+        ))?;
+        Ok(())
+    }
+
     /// Sets the currently active connection, updating the UI.
     ///
     /// # Arguments
@@ -169,6 +983,15 @@ impl Controller {
     /// * `id` - id of the connection
     ///
     pub async fn change_connection(&mut self, id: String) -> Result<(), Box<dyn Error>> {
+        if !self.connections.contains_key(&id) {
+            let msg = match self.connection_errors.get(&id) {
+                Some(err) => format!("Could not connect to {}: {}", id, err),
+                None => format!("Still connecting to {}.", id),
+            };
+            return Err(msg.into());
+        }
+
+        self.remember_page(&self.current_tab.clone()).await?;
         self.current_tab = id.clone();
         let connection = &self.connections[&id];
         self.navigate_to_async(connection, &connection.lock().await.current_address())
@@ -176,6 +999,147 @@ impl Controller {
         Ok(())
     }
 
+    /// Stores the current address of a connection in the config so it can be reopened the next
+    /// time ncopds is launched. Does nothing unless `restore_last_page` is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection.
+    ///
+    async fn remember_page(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if !self.config.restore_last_page || self.temporary.contains(name) {
+            return Ok(());
+        }
+
+        if let Some(conn) = self.connections.get(name) {
+            let addr = conn.lock().await.current_address();
+            self.config.last_pages.insert(name.to_string(), addr);
+            write_to_config(&self.config, &self.config_path.to_owned())?;
+        }
+        Ok(())
+    }
+
+    /// Starts downloading a file into the download directory in the background. A notification
+    /// says when it is done; if `open` is set, the file is opened as well once it has been saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - Connection the file is downloaded through.
+    /// * `url` - URL of the file.
+    /// * `open` - Whether to open the file once it is saved.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the download directory cannot be written to.
+    ///
+    fn start_download(
+        &mut self,
+        conn: Arc<Mutex<dyn Connection>>,
+        url: Url,
+        open: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // failing now beats failing once the whole file has been fetched
+        check_writable(&self.download_directory.to_file_path().unwrap())?;
+
+        let download_directory = self.download_directory.clone();
+        let client = self.client.clone();
+        let throttle_tx = self.throttle_tx.clone();
+        let slots = Arc::clone(&self.download_slots);
+        let tx_clone = self.ui.ui_tx.clone();
+        let controller_tx = self.tx.clone();
+        let verify = self.config.verify_downloads;
+        let url_name = url.to_string();
+
+        let handle = tokio::spawn(async move {
+            // feeds opened from disk have no server credentials to download with
+            let req = match conn
+                .lock()
+                .await
+                .as_any()
+                .downcast_ref::<OnlineConnection>()
+            {
+                Some(oc) => oc.get_request(&url),
+                None => client.get(url.to_string()),
+            };
+
+            // the connection stays usable while waiting for a free slot
+            let _permit = slots.acquire_owned().await;
+            let res = download(req, &url, Some(&throttle_tx)).await;
+
+            let finished = match res {
+                Ok((fname, data)) => {
+                    let size = data.len() as u64;
+                    let res = crate::utils::save_as(data, &download_directory, &fname, verify);
+
+                    let (msg, finished) = match res {
+                        Ok((path, warning)) => {
+                            // a file that could not be saved is never opened
+                            if open {
+                                controller_tx
+                                    .send(ControllerMessage::Open(
+                                        Url::from_file_path(&path).unwrap(),
+                                    ))
+                                    .expect("failed to send controller message");
+                            }
+                            (
+                                warning
+                                    .clone()
+                                    .unwrap_or(format!("File {0} finished downloading", &fname)),
+                                FinishedDownload {
+                                    name: fname,
+                                    path: Some(path),
+                                    size: Some(size),
+                                    problem: warning,
+                                },
+                            )
+                        }
+                        Err(err) => (
+                            err.to_string(),
+                            FinishedDownload {
+                                name: fname,
+                                path: None,
+                                size: Some(size),
+                                problem: Some(err.to_string()),
+                            },
+                        ),
+                    };
+
+                    tx_clone
+                        .send(UIMessage::ShowNotification("Attention".to_string(), msg))
+                        .expect("failed to send UI message");
+                    finished
+                }
+                Err(err) => {
+                    log::error!("download from {} failed: {}", url, err);
+                    tx_clone
+                        .send(UIMessage::ShowInfo(
+                            "Error".to_string(),
+                            format!("Download from {} failed: {}", url, err),
+                        ))
+                        .expect("failed to send UI message");
+                    FinishedDownload {
+                        name: url.to_string(),
+                        path: None,
+                        size: None,
+                        problem: Some(err.to_string()),
+                    }
+                }
+            };
+            controller_tx
+                .send(ControllerMessage::DownloadFinished(finished))
+                .expect("failed to send controller message");
+        });
+        self.downloads.retain(|h| !h.is_finished());
+        self.downloads.push(handle);
+
+        self.ui.ui_tx.send(UIMessage::ShowNotification(
+            "Starting download".to_string(),
+            url_name,
+        ))?;
+
+        Ok(())
+    }
+
     /// Asynchronously moves the connection to the specified URL.
     ///
     /// # Arguments
@@ -192,14 +1156,21 @@ impl Controller {
         let c_clone = Arc::clone(conn);
         let p = url.clone();
 
+        log::info!("navigating to {}", p);
+
         tokio::spawn(async move {
             let mut cloned = c_clone.lock().await;
             let e = cloned.navigate_to(&p).await;
             let addr = cloned.current_address().to_string();
 
             if let Ok(en) = e {
+                let msg = paging_note(cloned.deref(), en.len());
                 tx_clone
-                    .send(UIMessage::UpdateDirectoryView(addr, en, String::from("")))
+                    .send(UIMessage::UpdateDirectoryView(
+                        page_title(cloned.deref()),
+                        en,
+                        PageState::Loaded(msg),
+                    ))
                     .expect("failed to send UI message");
             } else {
                 // perhaps should be more consistent as a msgbox
@@ -207,7 +1178,7 @@ impl Controller {
                     .send(UIMessage::UpdateDirectoryView(
                         addr,
                         vec![],
-                        format!("Load failed: {}", e.err().unwrap()).to_string(),
+                        PageState::Error(e.err().unwrap().to_string()),
                     ))
                     .expect("failed to send UI message");
             }
@@ -216,7 +1187,7 @@ impl Controller {
         self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
             url.to_string(),
             vec![],
-            "Loading...".to_string(),
+            PageState::Loading,
         ))?;
 
         Ok(())
@@ -231,15 +1202,50 @@ impl Controller {
     ///
     fn entry_selected(&self, item: EntryType) -> Result<(), Box<dyn Error>> {
         match item {
-            EntryType::File(title, url) => {
+            EntryType::Header(_) => Ok(()),
+            EntryType::More(_) => {
+                self.ui.ui_tx.send(UIMessage::ShowMoreEntries())?;
+                Ok(())
+            }
+            EntryType::File(title, url, _) => {
+                // feeds saved to disk are browsed like any other catalog page
+                if url.to_file_path().is_ok_and(|p| is_feed_file(&p)) {
+                    self.tx.send(ControllerMessage::Navigate(url))?;
+                    return Ok(());
+                }
+
                 let mut ctx_entries = vec![];
                 ctx_entries.push(("Open".to_string(), ControllerMessage::Open(url.clone())));
-                ctx_entries.push(("Delete".to_string(), ControllerMessage::Delete(url.clone())));
+                ctx_entries.push((
+                    "Show in file manager".to_string(),
+                    ControllerMessage::Reveal(url.clone()),
+                ));
+                ctx_entries.push((
+                    "Copy path".to_string(),
+                    ControllerMessage::CopyPath(url.clone()),
+                ));
+                let delete_label = if self.config.use_trash {
+                    "Move to Trash"
+                } else {
+                    "Delete"
+                };
+                ctx_entries.push((
+                    delete_label.to_string(),
+                    ControllerMessage::Delete(url.clone()),
+                ));
 
                 let fp = url.to_file_path().expect("Somehow file path was wrong");
                 ctx_entries.push((
                     String::from("Rename"),
-                    ControllerMessage::Rename(fp.clone(), fp),
+                    ControllerMessage::Rename(fp.clone(), fp.clone()),
+                ));
+                ctx_entries.push((
+                    String::from("Cut"),
+                    ControllerMessage::Mark(vec![fp.clone()], Transfer::Move),
+                ));
+                ctx_entries.push((
+                    String::from("Copy"),
+                    ControllerMessage::Mark(vec![fp], Transfer::Copy),
                 ));
 
                 self.ui
@@ -247,7 +1253,7 @@ impl Controller {
                     .send(UIMessage::ShowContextMenu(title, ctx_entries))?;
                 Ok(())
             }
-            EntryType::Directory(_title, url) => {
+            EntryType::Directory(_title, url, _) => {
                 self.tx.send(ControllerMessage::Navigate(url))?;
                 Ok(())
             }
@@ -258,33 +1264,146 @@ impl Controller {
                 }
 
                 // implies that this entry is a directory
-                if let Some(href) = data.href {
-                    self.tx.send(ControllerMessage::Navigate(href))?;
-                    return Ok(());
+                if let Some(href) = &data.href {
+                    if data.downloads.is_empty() {
+                        self.tx.send(ControllerMessage::Navigate(href.clone()))?;
+                        return Ok(());
+                    }
                 }
 
-                if data.downloads.is_empty() {
-                    return Err("Cannot perform any action on this entry.".into());
+                if !data.is_actionable() {
+                    self.ui.ui_tx.send(UIMessage::ShowNotification(
+                        "Attention".to_string(),
+                        "This entry has no links; its details are all there is.".to_string(),
+                    ))?;
+                    return Ok(());
                 }
 
-                // build list of download entries
-                let mut download_entries = vec![];
-                for (href, mt) in data.downloads {
-                    download_entries.push((
-                        format!("Download as {}", mt).clone(),
-                        ControllerMessage::Download(href),
-                    ));
+                let unchecked = data
+                    .downloads
+                    .iter()
+                    .any(|(href, _, _)| !self.format_status.contains_key(href));
+                if self.config.verify_formats && unchecked {
+                    self.tx.send(ControllerMessage::VerifyFormats(data))?;
+                    return Ok(());
                 }
 
-                self.ui
-                    .ui_tx
-                    .send(UIMessage::ShowContextMenu(data.title, download_entries))?;
-
-                Ok(())
+                self.show_download_menu(*data)
             }
         }
     }
 
+    /// Shows the menu of everything that can be done with a catalog entry: browsing it if it is
+    /// also a catalog, more by the author and downloading it in each format. Formats that were
+    /// checked and found not to work are marked.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Entry to show the menu for.
+    ///
+    fn show_download_menu(&self, data: EntryData) -> Result<(), Box<dyn Error>> {
+        // build list of download entries; entries that are also a sub-catalog can be
+        // browsed from the same menu
+        let mut download_entries = vec![];
+        if let Some(href) = &data.href {
+            download_entries.push((
+                String::from("Browse"),
+                ControllerMessage::Navigate(href.clone()),
+            ));
+        }
+
+        if data.author_link.is_some() || data.author.is_some() {
+            download_entries.push((
+                String::from("More by this author"),
+                ControllerMessage::BrowseAuthor(EntryType::OPDSEntry(Box::new(data.clone()))),
+            ));
+        }
+
+        if let Some(href) = preferred_download(&data, &self.config.preferred_formats) {
+            download_entries.push((
+                String::from("Download and open"),
+                ControllerMessage::DownloadAndOpen(href),
+            ));
+        }
+
+        for (href, mt, size) in data.downloads {
+            let mut label = match size {
+                Some(size) => format!("Download as {} ({})", mt, human_readable_size(size)),
+                None => format!("Download as {}", mt),
+            };
+            match self.format_status.get(&href) {
+                Some(FormatStatus::Available) | None => {}
+                Some(status) => label = format!("{} ✗ {}", label, status),
+            }
+            download_entries.push((label, ControllerMessage::Download(href)));
+        }
+
+        self.ui
+            .ui_tx
+            .send(UIMessage::ShowContextMenu(data.title, download_entries))?;
+        Ok(())
+    }
+
+    /// Checks the entry's download links that were not checked yet in the background, so that
+    /// slow servers do not hold up the interface. The download menu is shown once run() picks
+    /// up the results.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - Connection the entry comes from.
+    /// * `data` - Entry whose links are checked.
+    ///
+    fn verify_formats(&self, conn: Arc<Mutex<dyn Connection>>, data: Box<EntryData>) {
+        let unchecked: Vec<_> = data
+            .downloads
+            .iter()
+            .filter(|(href, _, _)| !self.format_status.contains_key(href))
+            .map(|(href, mt, _)| (href.clone(), mt.clone()))
+            .collect();
+        let client = self.client.clone();
+        let formats_tx = self.formats_tx.clone();
+
+        tokio::spawn(async move {
+            let links = {
+                let lock = conn.lock().await;
+                let oc = lock.as_any().downcast_ref::<OnlineConnection>();
+                unchecked
+                    .into_iter()
+                    .map(|(href, mt)| {
+                        // feeds opened from disk have no server credentials
+                        let req = match oc {
+                            Some(oc) => oc.request(reqwest::Method::HEAD, &href),
+                            None => client.head(href.to_string()),
+                        };
+                        (href, mt, req)
+                    })
+                    .collect()
+            };
+
+            let statuses = check_formats(links, FORMAT_CHECK_TIMEOUT).await;
+            formats_tx
+                .send(FormatsChecked { data, statuses })
+                .expect("failed to send format checks");
+        });
+    }
+
+    /// Keeps the results of checking an entry's download links until ncopds is closed and shows
+    /// the entry's download menu.
+    ///
+    /// # Arguments
+    ///
+    /// * `checked` - The checked links.
+    ///
+    fn formats_checked(&mut self, checked: FormatsChecked) -> Result<(), Box<dyn Error>> {
+        for (href, status) in checked.statuses {
+            if status != FormatStatus::Available {
+                log::warn!("{} is {}", href, status);
+            }
+            self.format_status.insert(href, status);
+        }
+        self.show_download_menu(*checked.data)
+    }
+
     /// Updates the configuration file with the data for the specified connection.
     ///
     /// # Arguments
@@ -303,6 +1422,117 @@ impl Controller {
         Ok(())
     }
 
+    /// Creates a folder inside the directory currently shown by the local connection and
+    /// refreshes the view.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the new folder. Must not contain path separators.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the name is invalid, the download directory is not being viewed or the folder
+    /// could not be created.
+    ///
+    async fn create_directory(
+        &mut self,
+        name: &std::path::Path,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let name_str = name.to_string_lossy();
+        if name_str.trim().is_empty() {
+            return Err("folder name cannot be empty.".into());
+        }
+
+        if name.components().count() != 1 || name_str.contains(std::path::MAIN_SEPARATOR) {
+            return Err(format!("{} is not a valid folder name.", name_str).into());
+        }
+
+        if self.current_tab != "local" {
+            return Err("folders can only be created in the download directory.".into());
+        }
+
+        let current = self.connections["local"]
+            .lock()
+            .await
+            .current_address()
+            .to_file_path()
+            .map_err(|_| "current directory is not on disk.")?;
+
+        if !current.is_dir() {
+            return Err(format!("{} is not a directory.", current.display()).into());
+        }
+
+        let path = current.join(name);
+        create_dir(&path)?;
+        self.refresh().await?;
+        Ok(path)
+    }
+
+    /// Moves or copies the files on the clipboard into the directory currently shown by the local
+    /// connection. If files with the same names already exist there, the user is asked once to
+    /// confirm overwriting them first.
+    ///
+    /// # Errors
+    ///
+    /// Errors if nothing is on the clipboard or the download directory is not being viewed.
+    ///
+    async fn paste(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some((marked, transfer)) = self.clipboard.clone() else {
+            return Err("Nothing to paste. Cut or copy a file first.".into());
+        };
+
+        if self.current_tab != "local" {
+            return Err("Files can only be pasted into the download directory.".into());
+        }
+
+        let current = self.connections["local"]
+            .lock()
+            .await
+            .current_address()
+            .to_file_path()
+            .map_err(|_| "Current directory is not on disk.")?;
+
+        if !current.is_dir() {
+            return Err(format!("{} is not a directory.", current.display()).into());
+        }
+
+        let mut msgs = vec![];
+        let mut existing = vec![];
+        for from in marked {
+            let to = current.join(from.file_name().ok_or("Marked path has no filename.")?);
+            if to == from {
+                continue;
+            }
+            if to.exists() {
+                existing.push(to.clone());
+            }
+
+            msgs.push(match transfer {
+                Transfer::Move => ControllerMessage::MoveFile(from, to),
+                Transfer::Copy => ControllerMessage::CopyFile(from, to),
+            });
+        }
+
+        if msgs.is_empty() {
+            return Err("Marked files are already in this directory.".into());
+        }
+
+        match existing.as_slice() {
+            [] => self.tx.send(ControllerMessage::Batch(msgs))?,
+            [to] => self.ui.ui_tx.send(UIMessage::Confirm(
+                "File exists".to_string(),
+                format!("{} already exists. Overwrite it?", to.display()),
+                ControllerMessage::Batch(msgs),
+            ))?,
+            _ => self.ui.ui_tx.send(UIMessage::Confirm(
+                "Files exist".to_string(),
+                format!("{} files already exist. Overwrite them?", existing.len()),
+                ControllerMessage::Batch(msgs),
+            ))?,
+        }
+        Ok(())
+    }
+
     /// Function that reacts to messages from the UI.  
     ///
     /// # Arguments
@@ -320,83 +1550,401 @@ impl Controller {
                 Ok(())
             }
             ControllerMessage::Open(p) => {
-                open(p.to_file_path().unwrap())?;
+                let path = p.to_file_path().unwrap();
+                let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+                let command = self
+                    .config
+                    .open_commands
+                    .iter()
+                    .find(|(e, _)| ext.as_ref().is_some_and(|ext| e.to_lowercase() == *ext));
+
+                match command {
+                    Some((_, template)) => {
+                        if let Err(err) = open_with(template, &path) {
+                            log::error!("could not open {}: {}", path.display(), err);
+                            self.ui.ui_tx.send(UIMessage::ShowNotification(
+                                "Could not open file".to_string(),
+                                err.to_string(),
+                            ))?;
+                        }
+                    }
+                    None => open(path)?,
+                }
+                Ok(())
+            }
+            ControllerMessage::Reveal(p) => {
+                let root = self.download_directory.to_file_path().unwrap();
+                let folder = p
+                    .to_file_path()
+                    .ok()
+                    .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+                    .filter(|d| d.is_dir())
+                    .unwrap_or(root);
+                open(folder)?;
+                Ok(())
+            }
+            ControllerMessage::CopyPath(url) => {
+                let copied = url
+                    .to_file_path()
+                    .map_err(|_| format!("{} is not a local file.", url).into())
+                    .and_then(|p| {
+                        let path = p.to_string_lossy().to_string();
+                        copy_to_clipboard(&path).map(|_| path)
+                    });
+
+                let (title, msg) = match copied {
+                    Ok(path) => ("Path copied", path),
+                    Err(err) => ("Could not copy path", err.to_string()),
+                };
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ShowNotification(title.to_string(), msg))?;
+                Ok(())
+            }
+            ControllerMessage::CopyDebugInfo(data) => {
+                let info = data.debug_info();
+                let (title, msg) = match copy_to_clipboard(&info) {
+                    Ok(_) => ("Debug info copied", data.title),
+                    Err(err) => {
+                        // the debug console still shows it
+                        log::info!("debug info of {}:\n{}", data.title, info);
+                        (
+                            "Could not copy debug info",
+                            format!("{} It was written to the log instead.", err),
+                        )
+                    }
+                };
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ShowNotification(title.to_string(), msg))?;
                 Ok(())
             }
             ControllerMessage::Delete(p) => {
                 let path = p.to_file_path().unwrap();
 
+                if self.config.use_trash {
+                    match move_to_trash(&path, &self.config.trash_path()) {
+                        Ok(trashed) => {
+                            self.ui.ui_tx.send(UIMessage::ShowNotification(
+                                "Moved to trash".to_string(),
+                                format!("{}", path.display()),
+                            ))?;
+                            self.last_undo = Some(UndoRecord::Delete {
+                                original: path,
+                                trashed,
+                            });
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            self.ui.ui_tx.send(UIMessage::ShowNotification(
+                                "Warning".to_string(),
+                                format!(
+                                    "Could not move {} to trash ({}); deleted it instead.",
+                                    path.display(),
+                                    err
+                                ),
+                            ))?;
+                        }
+                    }
+                }
+
                 if path.is_dir() {
                     remove_dir(path)?;
                 } else {
                     remove_file(path)?;
                 }
 
+                // permanently deleted files cannot come back
+                self.last_undo = None;
+
                 Ok(())
             }
-            ControllerMessage::AddConnection(name, s, pwd) => {
-                store_password(&s, &pwd);
+            ControllerMessage::TestConnection(s, pwd) => {
+                self.ui.ui_tx.send(UIMessage::ConnectionTest(None))?;
 
-                let oc = OnlineConnection::new(&s, self.client.clone(), pwd.clone()).await?;
-                self.connections
-                    .insert(name.clone(), Arc::new(Mutex::new(oc)));
+                let client = self.client.clone();
+                tokio::spawn(async move {
+                    let res = match OnlineConnection::new(&s, client, pwd).await {
+                        Ok(oc) => Ok(format!(
+                            "Connected to {}. {}",
+                            oc.title(),
+                            if oc.supports_search() {
+                                "The catalog can be searched."
+                            } else {
+                                "The catalog cannot be searched."
+                            }
+                        )),
+                        Err(err) => Err(err.to_string()),
+                    };
+                    tx_clone
+                        .send(UIMessage::ConnectionTest(Some(res)))
+                        .expect("failed to send UI message");
+                });
+                Ok(())
+            }
+            ControllerMessage::SaveConnection(editing, name, mut s, pwd) => {
+                // an edit may keep its own name
+                if editing.as_ref() != Some(&name) && self.connection_exists(&name) {
+                    self.ui.ui_tx.send(UIMessage::ShowInfo(
+                        "Duplicate connection".to_string(),
+                        format!(
+                            "A connection named {} already exists. Pick another name, or edit \
+                             the existing connection from the Edit menu.",
+                            name
+                        ),
+                    ))?;
+                    return Ok(());
+                }
 
-                self.update_config(&name, &s)?;
+                // the dialog has no fields for extra headers or certificate checks
+                if let Some(old) = editing
+                    .as_ref()
+                    .and_then(|e| self.config.servers.as_ref()?.get(e))
+                {
+                    s.headers = old.headers.clone();
+                    s.accept_invalid_certs = old.accept_invalid_certs;
+                }
 
-                self.ui
-                    .ui_tx
-                    .send(UIMessage::AddConnection(name, s.clone(), pwd))?;
+                if let Some(old) = editing.filter(|e| *e != name) {
+                    self.rename_connection(&old, &name, &s).await?;
+                }
 
+                self.tx
+                    .send(ControllerMessage::AddConnection(name, s, pwd))?;
                 Ok(())
             }
-            ControllerMessage::ChangeConnection(url) => self.change_connection(url).await,
-            ControllerMessage::GoBack() => {
-                let mut mut_conn = conn.lock().await;
-                let e = mut_conn.back().await?;
-                self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
-                    mut_conn.current_address().to_string(),
-                    e,
-                    String::from(""),
+            ControllerMessage::QuickOpen(address) => {
+                let base_url = match Url::parse(address.trim()) {
+                    Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+                    _ => return Err(format!("{} is not an http(s) URL.", address.trim()).into()),
+                };
+
+                // the name says the connection goes away when ncopds is closed
+                let host = base_url.host_str().unwrap_or("catalog").to_string();
+                let mut name = format!("{} (temporary)", host);
+                let mut n = 2;
+                while self.connection_exists(&name) {
+                    name = format!("{} (temporary {})", host, n);
+                    n += 1;
+                }
+
+                self.temporary.insert(name.clone());
+                self.tx.send(ControllerMessage::AddConnection(
+                    name,
+                    Server::new(base_url),
+                    None,
                 ))?;
                 Ok(())
             }
-            ControllerMessage::Download(url) => {
-                let download_directory = self.download_directory.clone();
-                let url_name = url.to_string();
+            ControllerMessage::AddConnection(name, s, pwd) => {
+                self.renamed.remove(&name);
+                // the password still works for this session even if it cannot be remembered
+                let stored = match self.credentials.as_deref_mut() {
+                    _ if self.temporary.contains(&name) => Ok(()),
+                    Some(store) => store_password(store, &s, &pwd),
+                    None => Err(keyring::Error::NoStorageAccess(
+                        "the credentials file is locked".into(),
+                    )),
+                };
+                if let Err(err) = stored {
+                    log::error!("could not store password for {}: {}", name, err);
+                    self.ui.ui_tx.send(UIMessage::ShowNotification(
+                        "Warning".to_string(),
+                        format!("Couldn't save the password for {}: {}", name, err),
+                    ))?;
+                }
 
+                self.ui.ui_tx.send(UIMessage::AddConnection(
+                    name.clone(),
+                    s.clone(),
+                    pwd.clone(),
+                ))?;
+                self.ui.ui_tx.send(UIMessage::ConnectionStatus(
+                    name.clone(),
+                    ConnectionStatus::Connecting,
+                ))?;
+
+                // slow servers should not freeze the UI; the result is picked up in run()
+                let connecting = self.connect(name, s, pwd);
+                let connect_tx = self.connect_tx.clone();
                 tokio::spawn(async move {
-                    let lock = c_clone.lock().await;
-                    let oc: &OnlineConnection =
-                        lock.as_any().downcast_ref::<OnlineConnection>().unwrap();
-                    let res = oc.download(&url).await;
-
-                    if res.is_ok() {
-                        let (fname, data) = res.unwrap();
-                        let res = crate::utils::save_as(data, &download_directory, &fname);
-
-                        let msg = match res {
-                            Ok(_) => format!("File {0} finished downloading", &fname),
-                            Err(err) => err.to_string(),
-                        };
+                    connect_tx
+                        .send(connecting.await)
+                        .expect("failed to send connection result");
+                });
 
-                        tx_clone
-                            .send(UIMessage::ShowNotification("Attention".to_string(), msg))
-                            .expect("failed to send UI message");
-                    } else {
-                        tx_clone
-                            .send(UIMessage::ShowInfo(
-                                "Error".to_string(),
-                                format!("Download from {} failed: {}", url, res.err().unwrap()),
-                            ))
-                            .expect("failed to send UI message");
+                Ok(())
+            }
+            ControllerMessage::ChangeConnection(url) => self.change_connection(url).await,
+            ControllerMessage::GoBack() => self.load_in_background(PageRequest::Back),
+            ControllerMessage::GoHome() => {
+                // going back from the root has nowhere to go, so nothing is left on the stack
+                conn.lock().await.clear_history();
+                self.refresh().await
+            }
+            ControllerMessage::GoUp() => {
+                let up = {
+                    let conn = conn.lock().await;
+                    conn.feed_links(&conn.current_address()).and_then(|l| l.up)
+                };
+                match up {
+                    Some(url) => self.navigate_to_async(conn, &url).await,
+                    None => Ok(self.tx.send(ControllerMessage::GoBack())?),
+                }
+            }
+            ControllerMessage::GoStart() => {
+                let start = {
+                    let conn = conn.lock().await;
+                    conn.feed_links(&conn.current_address())
+                        .and_then(|l| l.start)
+                };
+                match start {
+                    Some(url) => self.navigate_to_async(conn, &url).await,
+                    None => Ok(self.tx.send(ControllerMessage::GoHome())?),
+                }
+            }
+            ControllerMessage::Download(url) => self.start_download(c_clone, url, false),
+            ControllerMessage::DownloadAndOpen(url) => self.start_download(c_clone, url, true),
+            ControllerMessage::DownloadFinished(finished) => {
+                self.recent_downloads.push_front(finished);
+                self.recent_downloads.truncate(RECENT_DOWNLOADS);
+                self.ui.ui_tx.send(UIMessage::RecentDownloads(
+                    self.recent_downloads.iter().cloned().collect(),
+                    false,
+                ))?;
+                Ok(())
+            }
+            ControllerMessage::ShowRecentDownloads() => {
+                self.ui.ui_tx.send(UIMessage::RecentDownloads(
+                    self.recent_downloads.iter().cloned().collect(),
+                    true,
+                ))?;
+                Ok(())
+            }
+            ControllerMessage::ShowRawPage() => {
+                if !self.config.keep_raw_feeds {
+                    return Err(
+                        "Set keep_raw_feeds = true in the config to see what servers send.".into(),
+                    );
+                }
+
+                let conn = conn.lock().await;
+                match conn.raw_page() {
+                    Some((url, body)) if url == conn.current_address() => {
+                        self.ui.ui_tx.send(UIMessage::ShowRawPage(url, body))?;
+                        Ok(())
                     }
-                });
+                    _ => Err(
+                        "What the server sent for this page was not kept; reload it with F5 to see it."
+                            .into(),
+                    ),
+                }
+            }
+            ControllerMessage::VerifyFormats(data) => {
+                self.verify_formats(c_clone, data);
+                Ok(())
+            }
+            ControllerMessage::QuickDownload(entry) => {
+                let href = match &entry {
+                    EntryType::OPDSEntry(data) => {
+                        preferred_download(data, &self.config.preferred_formats)
+                    }
+                    _ => None,
+                };
 
-                self.ui.ui_tx.send(UIMessage::ShowNotification(
-                    "Starting download".to_string(),
-                    url_name,
+                match href {
+                    Some(h) => self.tx.send(ControllerMessage::Download(h))?,
+                    None => self.ui.ui_tx.send(UIMessage::ShowNotification(
+                        "Attention".to_string(),
+                        "Nothing to download for this entry.".to_string(),
+                    ))?,
+                }
+                Ok(())
+            }
+            ControllerMessage::BrowseAuthor(entry) => {
+                let EntryType::OPDSEntry(data) = entry else {
+                    return Err("Only books from a catalog have authors.".into());
+                };
+
+                // catalogs without author feeds can still be searched for the name
+                match (data.author_link, data.author) {
+                    (Some(link), _) => self.tx.send(ControllerMessage::Navigate(link))?,
+                    (None, Some(author)) => self.tx.send(ControllerMessage::Search(author))?,
+                    (None, None) => return Err("This book has no author.".into()),
+                }
+                Ok(())
+            }
+            ControllerMessage::ConfirmDownloadAll() => {
+                let conn = conn.lock().await;
+                let entries = conn
+                    .cached_page(&conn.current_address())
+                    .unwrap_or_default();
+
+                let mut urls = vec![];
+                let mut skipped = 0;
+                let mut size = 0;
+                let mut sizes_known = true;
+                for entry in entries {
+                    let EntryType::OPDSEntry(data) = entry else {
+                        continue;
+                    };
+                    let href = match data.unsupported {
+                        Some(_) => None,
+                        None => preferred_download(&data, &self.config.preferred_formats),
+                    };
+                    let Some(href) = href else {
+                        skipped += 1;
+                        continue;
+                    };
+
+                    match data
+                        .downloads
+                        .iter()
+                        .find(|(u, _, _)| *u == href)
+                        .and_then(|(_, _, s)| *s)
+                    {
+                        Some(s) => size += s,
+                        None => sizes_known = false,
+                    }
+                    urls.push(href);
+                }
+
+                if urls.is_empty() {
+                    return Err("Nothing on this page can be downloaded.".into());
+                }
+
+                let estimate = if sizes_known {
+                    format!(" ({})", human_readable_size(size))
+                } else if size > 0 {
+                    format!(" (at least {})", human_readable_size(size))
+                } else {
+                    String::new()
+                };
+                self.ui.ui_tx.send(UIMessage::Confirm(
+                    "Download all".to_string(),
+                    format!(
+                        "Download {} books{}?{}",
+                        urls.len(),
+                        estimate,
+                        match skipped {
+                            0 => String::new(),
+                            n => format!(" {} entries have nothing to download.", n),
+                        }
+                    ),
+                    ControllerMessage::DownloadAll(urls),
                 ))?;
+                Ok(())
+            }
+            ControllerMessage::DownloadAll(urls) => {
+                let count = urls.len();
+                for url in urls {
+                    self.tx.send(ControllerMessage::Download(url))?;
+                }
 
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Download all".to_string(),
+                    format!("Queued {} downloads.", count),
+                ))?;
                 Ok(())
             }
             ControllerMessage::Navigate(p) => {
@@ -405,13 +1953,23 @@ impl Controller {
             }
             ControllerMessage::RequestImage(entry) => {
                 match entry {
-                    EntryType::File(_title, _url) => {
-                        // TODO: implement rendering the first page of a pdf / epub
-                        // load from disk
+                    EntryType::File(title, url, _) => {
+                        // only comic archives have a cover so far
+                        tokio::spawn(async move {
+                            let lock = c_clone.lock().await;
+                            let byte_data = lock.get_image_bytes(&url).await;
+
+                            if let Ok(id) = load_from_memory(&byte_data) {
+                                tx_clone
+                                    .send(UIMessage::StoreImage(title, id))
+                                    .expect("failed to send UI message");
+                            }
+                        });
                     }
-                    EntryType::Directory(_title, _url) => {
+                    EntryType::Directory(_title, _url, _) => {
                         // return generic image
                     }
+                    EntryType::Header(_) | EntryType::More(_) => {}
                     EntryType::OPDSEntry(data) => {
                         let title = data.title.clone();
 
@@ -419,10 +1977,12 @@ impl Controller {
                             tokio::spawn(async move {
                                 let lock = c_clone.lock().await;
                                 let byte_data = lock.get_image_bytes(&image_url).await;
-                                let id = load_from_memory(&byte_data).unwrap();
-                                tx_clone
-                                    .send(UIMessage::StoreImage(title.clone(), id))
-                                    .expect("failed to send UI message");
+
+                                if let Ok(id) = load_from_memory(&byte_data) {
+                                    tx_clone
+                                        .send(UIMessage::StoreImage(title.clone(), id))
+                                        .expect("failed to send UI message");
+                                }
                             });
                         }
                     }
@@ -430,24 +1990,307 @@ impl Controller {
                 Ok(())
             }
             ControllerMessage::Rename(old_path, new_path) => {
-                rename_full_dir_fname(old_path, new_path)
-            }
-            ControllerMessage::Search(query) => {
-                let mut mut_conn = conn.lock().await;
-                let res = mut_conn.search(&query).await?;
-                self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
-                    format!("Search results for {}", query),
-                    res,
-                    String::from(""),
+                let to = rename_full_dir_fname(old_path.clone(), new_path)?;
+                self.last_undo = Some(UndoRecord::Rename { from: old_path, to });
+                Ok(())
+            }
+            ControllerMessage::ClearCache(name) => {
+                let conn = self
+                    .connections
+                    .get(&name)
+                    .ok_or(format!("{} is not connected.", name))?;
+                conn.lock().await.clear_cache();
+
+                if name == self.current_tab {
+                    self.tx.send(ControllerMessage::Refresh())?;
+                }
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Cache cleared".to_string(),
+                    format!("Forgot every page cached for {}.", name),
+                ))?;
+                Ok(())
+            }
+            ControllerMessage::ToggleAnonymous() => {
+                let anonymous = {
+                    let mut lock = conn.lock().await;
+                    let anonymous = !lock.anonymous();
+                    lock.set_anonymous(anonymous)?;
+                    anonymous
+                };
+
+                self.tx.send(ControllerMessage::Refresh())?;
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Attention".to_string(),
+                    if anonymous {
+                        format!("Browsing {} anonymously.", self.current_tab)
+                    } else {
+                        format!("Browsing {} with your credentials.", self.current_tab)
+                    },
+                ))?;
+                self.report_last_refresh()
+            }
+            ControllerMessage::ClearAllCaches() => {
+                for conn in self.connections.values() {
+                    conn.lock().await.clear_cache();
+                }
+
+                self.tx.send(ControllerMessage::Refresh())?;
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Cache cleared".to_string(),
+                    "Forgot every cached page.".to_string(),
+                ))?;
+                Ok(())
+            }
+            ControllerMessage::Refresh() => {
+                let addr = c_clone.lock().await.current_address();
+                let addr_name = addr.to_string();
+
+                tokio::spawn(async move {
+                    let mut lock = c_clone.lock().await;
+                    lock.invalidate(&addr);
+                    let res = lock.get_page(&addr).await;
+
+                    let msg = match res {
+                        Ok(e) => UIMessage::UpdateDirectoryView(
+                            page_title(lock.deref()),
+                            e,
+                            PageState::Loaded(String::new()),
+                        ),
+                        Err(err) => UIMessage::ShowInfo(
+                            "Error".to_string(),
+                            format!("Refreshing {} failed: {}", addr, err),
+                        ),
+                    };
+                    tx_clone.send(msg).expect("failed to send UI message");
+                });
+
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Refreshing".to_string(),
+                    addr_name,
                 ))?;
+                Ok(())
+            }
+            ControllerMessage::CreateDirectory(name) => {
+                let res = self.create_directory(&name).await;
 
+                let msg = match res {
+                    Ok(p) => format!("Created folder {}", p.display()),
+                    Err(err) => format!("Could not create folder: {}", err),
+                };
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ShowNotification("Attention".to_string(), msg))?;
                 Ok(())
             }
+            ControllerMessage::Mark(paths, transfer) => {
+                let verb = match transfer {
+                    Transfer::Move => "move",
+                    Transfer::Copy => "copy",
+                };
+                let what = match paths.as_slice() {
+                    [] => return Ok(()),
+                    [path] => format!("{}", path.display()),
+                    _ => format!("{} files", paths.len()),
+                };
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    format!("Ready to {}", verb),
+                    what,
+                ))?;
+                self.clipboard = Some((paths, transfer));
+                Ok(())
+            }
+            ControllerMessage::Paste() => self.paste().await,
+            ControllerMessage::MoveFile(from, to) => {
+                move_path(&from, &to)?;
+                self.last_undo = Some(UndoRecord::Move {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+
+                // the marked file is gone from where it was, so it cannot be pasted again
+                if let Some((paths, _)) = self.clipboard.as_mut() {
+                    paths.retain(|p| p != &from);
+                    if paths.is_empty() {
+                        self.clipboard = None;
+                    }
+                }
+
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Moved".to_string(),
+                    format!("{}", to.display()),
+                ))?;
+                self.refresh().await
+            }
+            ControllerMessage::CopyFile(from, to) => {
+                copy_path(&from, &to)?;
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Copied".to_string(),
+                    format!("{}", to.display()),
+                ))?;
+                self.refresh().await
+            }
+            ControllerMessage::LoginCancelled(name) => {
+                // reported now that nobody is going to log in
+                let status = if self.connections.contains_key(&name) {
+                    ConnectionStatus::Ready
+                } else {
+                    self.connection_errors
+                        .entry(name.clone())
+                        .or_insert_with(|| "no password was entered".to_string());
+                    ConnectionStatus::Failed
+                };
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ConnectionStatus(name, status))?;
+                Ok(())
+            }
+            ControllerMessage::Unlock(passphrase) => {
+                match FileStore::open(&self.config.credentials_path(), &passphrase) {
+                    Ok(store) => {
+                        self.credentials = Some(Box::new(store));
+                        self.connect_to_servers().await;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        self.ui.ui_tx.send(UIMessage::PassphrasePrompt(true))?;
+                        Err(format!("Could not unlock credentials: {}", err).into())
+                    }
+                }
+            }
+            ControllerMessage::Undo() => {
+                let Some(record) = self.last_undo.take() else {
+                    self.ui.ui_tx.send(UIMessage::ShowNotification(
+                        "Undo".to_string(),
+                        "Nothing to undo.".to_string(),
+                    ))?;
+                    return Ok(());
+                };
+
+                let (current, original) = record.paths();
+                if original.exists() {
+                    let msg = format!("Cannot undo: {} already exists.", original.display());
+                    self.last_undo = Some(record);
+                    return Err(msg.into());
+                }
+
+                move_path(current, original)?;
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    record.describe().to_string(),
+                    format!("{}", original.display()),
+                ))?;
+
+                if self.current_tab == "local" {
+                    self.refresh().await?;
+                }
+                Ok(())
+            }
+            ControllerMessage::DismissNotifications() => {
+                self.ui.ui_tx.send(UIMessage::DismissNotifications())?;
+                Ok(())
+            }
+            ControllerMessage::Quit(force) => {
+                self.downloads.retain(|h| !h.is_finished());
+                match self.downloads.len() {
+                    n if n > 0 && !force => self.ui.ui_tx.send(UIMessage::Confirm(
+                        "Quit".to_string(),
+                        format!(
+                            "{} download{} in progress, quit anyway?",
+                            n,
+                            if n == 1 { "" } else { "s" }
+                        ),
+                        ControllerMessage::Quit(true),
+                    ))?,
+                    _ => self.ui.ui_tx.send(UIMessage::Quit())?,
+                }
+                Ok(())
+            }
+            ControllerMessage::SelectionChanged() => {
+                self.ui.ui_tx.send(UIMessage::SelectionChanged())?;
+                Ok(())
+            }
+            ControllerMessage::Batch(msgs) => {
+                for msg in msgs {
+                    self.tx.send(msg)?;
+                }
+                Ok(())
+            }
+            ControllerMessage::SetTheme(preset) => {
+                self.config.theme = preset.clone();
+                write_to_config(&self.config, &self.config_path.to_owned())?;
+                self.ui.ui_tx.send(UIMessage::ApplyTheme(preset))?;
+                Ok(())
+            }
+            ControllerMessage::EditTheme() => {
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::EditTheme(self.config.theme.clone()))?;
+                Ok(())
+            }
+            ControllerMessage::ToggleDetails() => {
+                self.session.details_hidden = !self.session.details_hidden;
+                self.ui.ui_tx.send(UIMessage::ToggleDetails())?;
+                Ok(())
+            }
+            ControllerMessage::CycleLogLevel() => {
+                let level = logging::cycle_level();
+                log::info!("log level is now {}", level);
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Log level".to_string(),
+                    format!("Logging {} and above until ncopds is closed.", level),
+                ))?;
+                Ok(())
+            }
+            ControllerMessage::ToggleSortByDate() => {
+                self.session.sort_by_date = !self.session.sort_by_date;
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::SortByDate(self.session.sort_by_date))?;
+                // pages come from the cache, so this only puts them in the new order
+                self.refresh().await
+            }
+            ControllerMessage::ShowSearch() => {
+                let history = self
+                    .session
+                    .search_history
+                    .get(&self.current_tab)
+                    .cloned()
+                    .unwrap_or_default();
+                self.ui.ui_tx.send(UIMessage::SearchPrompt(history))?;
+                Ok(())
+            }
+            ControllerMessage::ShowAddressBar() => {
+                let current = conn.lock().await.current_address();
+                self.ui.ui_tx.send(UIMessage::AddressPrompt(current))?;
+                Ok(())
+            }
+            ControllerMessage::Search(query) => self.load_in_background(PageRequest::Search(query)),
+            ControllerMessage::SearchPage(forward) => {
+                self.load_in_background(PageRequest::SearchPage(forward))
+            }
+            ControllerMessage::ShowFacets() => {
+                let conn = conn.lock().await;
+                let facets = conn.facets(&conn.current_address());
+                if facets.is_empty() {
+                    return Err("This page has no facets.".into());
+                }
+
+                let entries = facets
+                    .into_iter()
+                    .map(|f| (f.label(), ControllerMessage::ApplyFacet(f)))
+                    .collect();
+                self.ui
+                    .ui_tx
+                    .send(UIMessage::ShowContextMenu("Facets".to_string(), entries))?;
+                Ok(())
+            }
+            ControllerMessage::ApplyFacet(facet) => {
+                self.load_in_background(PageRequest::ApplyFacet(facet))
+            }
         }
     }
 
-    /// Refreshes the currently active page. Called by the file watcher as well as by the main
-    /// event loop on a timer.
+    /// Refreshes the currently active page. Called by the file watcher as well as after file
+    /// operations in the local view.
     ///
     /// # Errors
     ///
@@ -458,17 +2301,318 @@ impl Controller {
         let mut mut_conn = conn.lock().await;
         let cr = &mut_conn.current_address();
         let e = mut_conn.get_page(cr).await?;
-
-        let msg = format!("Updated {}", Utc::now());
+        let note = paging_note(mut_conn.deref(), e.len());
 
         self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
-            mut_conn.current_address().to_string(),
+            page_title(mut_conn.deref()),
             e,
-            msg,
+            PageState::Loaded(note),
         ))?;
         Ok(())
     }
 
+    /// Loads a page of the current connection without holding up the interface, since servers
+    /// that ask to be retried later can take a while to answer. The page is shown once run()
+    /// picks it up.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - What to load.
+    ///
+    fn load_in_background(&self, request: PageRequest) -> Result<(), Box<dyn Error>> {
+        let c_clone = Arc::clone(&self.connections[&self.current_tab]);
+        let connection = self.current_tab.clone();
+        let page_tx = self.page_tx.clone();
+        let title = request.describe();
+        let query = match &request {
+            PageRequest::Search(query) => Some(query.clone()),
+            _ => None,
+        };
+
+        tokio::spawn(async move {
+            let mut conn = c_clone.lock().await;
+            let result = load_page(&mut *conn, request)
+                .await
+                .map_err(|err| err.to_string());
+            page_tx
+                .send(PageLoaded {
+                    connection,
+                    query,
+                    result,
+                })
+                .expect("failed to send page");
+        });
+
+        self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
+            title,
+            vec![],
+            PageState::Loading,
+        ))?;
+        Ok(())
+    }
+
+    /// Shows a page that was loaded in the background and remembers what was searched for.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The loaded page.
+    ///
+    /// # Errors
+    ///
+    /// Errors with the reason the page could not be loaded.
+    ///
+    fn page_loaded(&mut self, page: PageLoaded) -> Result<(), Box<dyn Error>> {
+        let PageLoaded {
+            connection,
+            query,
+            result,
+        } = page;
+        // the user may have moved on to another connection in the meantime
+        let shown = connection == self.current_tab;
+
+        match result {
+            Ok((title, entries)) => {
+                if let Some(query) = query {
+                    self.session.remember_search(&connection, &query);
+                    if let Err(err) = self.session.write(&self.config.session_path()) {
+                        log::error!("could not save search history: {}", err);
+                    }
+                }
+                if shown {
+                    self.ui.ui_tx.send(UIMessage::UpdateDirectoryView(
+                        title,
+                        entries,
+                        PageState::Loaded(String::new()),
+                    ))?;
+                }
+                Ok(())
+            }
+            Err(err) => {
+                // the connection is still on the page it was on before
+                if shown {
+                    self.refresh_in_background();
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Refreshes the currently active page without waiting for it, so that folders with many
+    /// files do not hold up the interface. Errors are only logged, since nobody asked for the
+    /// reload.
+    fn refresh_in_background(&self) {
+        let c_clone = Arc::clone(&self.connections[&self.current_tab]);
+        let tx_clone = self.ui.ui_tx.clone();
+
+        tokio::spawn(async move {
+            let mut conn = c_clone.lock().await;
+            let addr = conn.current_address();
+            match conn.get_page(&addr).await {
+                Ok(e) => tx_clone
+                    .send(UIMessage::UpdateDirectoryView(
+                        page_title(conn.deref()),
+                        e,
+                        PageState::Loaded(String::new()),
+                    ))
+                    .expect("failed to send UI message"),
+                // the directory itself may have just been removed
+                Err(err) => log::error!("{}", err),
+            }
+        });
+    }
+
+    /// Sends the names of the files in the download directory to the UI, so it can mark entries
+    /// that were already downloaded. Only read when the directory changes.
+    fn report_downloaded_files(&self) {
+        let files = match read_dir(&self.config.download_directory) {
+            Ok(rd) => rd
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .collect(),
+            Err(err) => {
+                log::error!("could not list the download directory: {}", err);
+                return;
+            }
+        };
+
+        self.ui
+            .ui_tx
+            .send(UIMessage::DownloadedFiles(files))
+            .expect("failed to send UI message");
+    }
+
+    /// Reloads the current page of every online connection in the background. The active
+    /// connection's view is updated; for the others, the user is notified if their page gained
+    /// entries that were not there before.
+    fn refresh_all(&self) {
+        for (name, conn) in self.connections.iter() {
+            if name == "local" {
+                continue;
+            }
+
+            let name = name.clone();
+            let is_current = name == self.current_tab;
+            let c_clone = Arc::clone(conn);
+            let tx_clone = self.ui.ui_tx.clone();
+
+            tokio::spawn(async move {
+                let mut lock = c_clone.lock().await;
+                let addr = lock.current_address();
+                let old = lock.cached_page(&addr);
+
+                lock.invalidate(&addr);
+                let Ok(entries) = lock.get_page(&addr).await else {
+                    return;
+                };
+
+                if is_current {
+                    // redrawing the same page would only move the cursor back to the top
+                    if old.is_some_and(|old| old == entries) {
+                        return;
+                    }
+                    let note = paging_note(lock.deref(), entries.len());
+                    tx_clone
+                        .send(UIMessage::UpdateDirectoryView(
+                            page_title(lock.deref()),
+                            entries,
+                            PageState::Loaded(note),
+                        ))
+                        .expect("failed to send UI message");
+                    return;
+                }
+
+                // nothing to compare against if the page was never loaded
+                let Some(old) = old else {
+                    return;
+                };
+
+                let known: HashSet<String> = old.iter().map(get_title_for_entry).collect();
+                let new_entries = entries
+                    .iter()
+                    .filter(|e| !known.contains(&get_title_for_entry(e)))
+                    .count();
+
+                if new_entries > 0 {
+                    tx_clone
+                        .send(UIMessage::ShowNotification(
+                            name,
+                            format!("{} new entries", new_entries),
+                        ))
+                        .expect("failed to send UI message");
+                }
+            });
+        }
+    }
+
+    /// Checks every configured server in the background and reports the results to the UI.
+    /// Connected servers are checked with their credentials; servers that are still connecting
+    /// or whose last check has not finished are skipped.
+    fn ping_servers(&self) {
+        let servers = self.config.servers.clone().unwrap_or_default();
+
+        for (name, s) in servers {
+            let conn = self.connections.get(&name).map(Arc::clone);
+            if conn.is_none() && !self.connection_errors.contains_key(&name) {
+                continue;
+            }
+            if !self.pinging.lock().unwrap().insert(name.clone()) {
+                continue;
+            }
+
+            let client = s.client(&self.client).ok();
+            let tx_clone = self.ui.ui_tx.clone();
+            let pinging = Arc::clone(&self.pinging);
+
+            tokio::spawn(async move {
+                let target = match conn {
+                    // the connection is only held while copying what it is pinged with
+                    Some(c) => c
+                        .lock()
+                        .await
+                        .as_any()
+                        .downcast_ref::<OnlineConnection>()
+                        .map(OnlineConnection::ping_target),
+                    None => client.map(|client| (client, s, None)),
+                };
+                let health = match &target {
+                    Some((client, s, pwd)) => ping_with_timeout(client, s, pwd, PING_TIMEOUT).await,
+                    // a server whose client cannot be built cannot be reached either
+                    None => Health::Unreachable,
+                };
+                pinging.lock().unwrap().remove(&name);
+
+                tx_clone
+                    .send(UIMessage::ConnectionHealth(name, health))
+                    .expect("failed to send UI message");
+            });
+        }
+    }
+
+    /// Tells the UI when the active connection last fetched a page so it can show how stale the
+    /// view is. Connections that are busy loading are skipped; they report on the next tick.
+    fn report_last_refresh(&self) -> Result<(), Box<dyn Error>> {
+        let conn = &self.connections[&self.current_tab];
+        if let Ok(lock) = conn.try_lock() {
+            self.ui.ui_tx.send(UIMessage::LastRefresh(
+                lock.last_refresh(),
+                lock.anonymous(),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Stops watching the download directory once it is removed or renamed, and watches it again
+    /// once it is back, telling the user either way. Returns whether the directory is watched.
+    ///
+    /// # Arguments
+    ///
+    /// * `watcher` - Watcher reporting changes to the download directory.
+    /// * `watching` - Whether the directory was being watched.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the UI cannot be told.
+    ///
+    async fn check_watch(
+        &mut self,
+        watcher: &mut RecommendedWatcher,
+        watching: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        let dir = Path::new(&self.config.download_directory);
+        if watching == dir.is_dir() {
+            return Ok(watching);
+        }
+
+        if watching {
+            log::warn!("{} is gone; no longer watching it", dir.display());
+            // the watch may already have been dropped along with the directory
+            let _ = watcher.unwatch(dir);
+            self.ui.ui_tx.send(UIMessage::ShowNotification(
+                "Download directory".to_string(),
+                format!(
+                    "{} was removed or renamed; new files will show up once it is back.",
+                    dir.display()
+                ),
+            ))?;
+            return Ok(false);
+        }
+
+        if let Err(err) = watcher.watch(dir, RecursiveMode::Recursive) {
+            log::error!("could not watch {} again: {}", dir.display(), err);
+            return Ok(false);
+        }
+        log::info!("{} is back; watching it again", dir.display());
+        self.ui.ui_tx.send(UIMessage::ShowNotification(
+            "Download directory".to_string(),
+            format!("{} is back.", dir.display()),
+        ))?;
+        self.report_downloaded_files();
+        if &self.current_tab == "local" {
+            self.refresh_in_background();
+        }
+        Ok(true)
+    }
+
     /// Main loop that updates the controller's state as well as the UI's.
     ///
     /// # Errors
@@ -477,42 +2621,318 @@ impl Controller {
     /// propagate up past this function to main will be related to message passing failing.
     ///
     pub async fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        // reading folders from disk is quick, so the local connection is restored right away
+        if let Some(addr) = self.config.last_pages.get("local") {
+            if self.config.restore_last_page {
+                let mut conn = self.connections["local"].lock().await;
+                if let Err(err) = restore_page(&mut *conn, addr).await {
+                    self.ui.ui_tx.send(UIMessage::ShowNotification(
+                        "Could not restore local".to_string(),
+                        format!("{} ({}); returning to root.", addr, err),
+                    ))?;
+                }
+            }
+        }
+        // other connections are switched to once they connect; if they fail, this one stays
         self.change_connection("local".to_string()).await?;
-        self.connect_to_servers().await;
+        if let Some(name) = &self.config.default_connection {
+            if !self.connection_exists(name) {
+                log::warn!("default_connection {} is not a configured connection", name);
+            }
+            self.session.active_connection = Some(name.clone()).filter(|n| n != "local");
+        }
+        if self.session.details_hidden {
+            self.ui.ui_tx.send(UIMessage::ToggleDetails())?;
+        }
+        if self.session.sort_by_date {
+            self.ui.ui_tx.send(UIMessage::SortByDate(true))?;
+        }
 
-        let mut frame = 0;
+        // browsing still works, so this is only a warning
+        if let Err(err) = check_writable(&self.download_directory.to_file_path().unwrap()) {
+            log::error!("{}", err);
+            self.ui
+                .ui_tx
+                .send(UIMessage::ShowInfo("Warning".to_string(), err.to_string()))?;
+        }
+        self.open_credentials().await?;
+
+        self.report_downloaded_files();
+
+        let mut ping = Periodic::new(Duration::from_secs(self.config.ping_interval.into()));
+        let mut every_second = Periodic::new(Duration::from_secs(1));
+        let mut auto_refresh = Periodic::new(self.refresh_interval);
         let (wtx, wrx) = mpsc::channel();
         let mut watcher = RecommendedWatcher::new(wtx, notify::Config::default())?;
 
-        watcher
-            .watch(
-                self.config.download_directory.as_ref(),
-                RecursiveMode::Recursive,
-            )
-            .expect("failed to watch directory");
+        // a directory that is missing now is watched once it shows up
+        let mut watching = match watcher.watch(
+            self.config.download_directory.as_ref(),
+            RecursiveMode::Recursive,
+        ) {
+            Ok(()) => true,
+            Err(err) => {
+                log::error!(
+                    "could not watch {}: {}",
+                    self.config.download_directory,
+                    err
+                );
+                false
+            }
+        };
 
-        while self.ui.step(frame) {
+        while self.ui.step() {
             while let Some(message) = self.rx.try_iter().next() {
                 let res = self.handle_messages(message).await;
-                if res.is_err() {
-                    self.ui.ui_tx.send(UIMessage::ShowInfo(
-                        "Error".to_string(),
-                        res.unwrap_err().to_string(),
-                    ))?;
+                if let Err(err) = res {
+                    log::error!("{}", err);
+                    self.ui
+                        .ui_tx
+                        .send(UIMessage::ShowInfo("Error".to_string(), err.to_string()))?;
+                }
+            }
+
+            while let Some(result) = self.connect_rx.try_iter().next() {
+                if let Err(err) = self.connection_finished(result).await {
+                    log::error!("{}", err);
+                    self.ui
+                        .ui_tx
+                        .send(UIMessage::ShowInfo("Error".to_string(), err.to_string()))?;
                 }
             }
 
+            while let Some(page) = self.page_rx.try_iter().next() {
+                if let Err(err) = self.page_loaded(page) {
+                    log::error!("{}", err);
+                    self.ui
+                        .ui_tx
+                        .send(UIMessage::ShowInfo("Error".to_string(), err.to_string()))?;
+                }
+            }
+
+            while let Some(checked) = self.formats_rx.try_iter().next() {
+                if let Err(err) = self.formats_checked(checked) {
+                    log::error!("{}", err);
+                    self.ui
+                        .ui_tx
+                        .send(UIMessage::ShowInfo("Error".to_string(), err.to_string()))?;
+                }
+            }
+
+            while let Some((url, wait)) = self.throttle_rx.try_iter().next() {
+                self.ui.ui_tx.send(UIMessage::ShowNotification(
+                    "Throttled".to_string(),
+                    format!(
+                        "{} is busy; trying again in {}s.",
+                        url.host_str().unwrap_or("The server"),
+                        wait.as_secs()
+                    ),
+                ))?;
+            }
+
+            // only the latest report of each page is worth showing
+            let mut progress = HashMap::new();
+            while let Some((url, done, total)) = self.progress_rx.try_iter().next() {
+                progress.insert(url, (done, total));
+            }
+            for (url, (done, total)) in progress {
+                self.ui.ui_tx.send(UIMessage::LoadProgress(
+                    url.to_string(),
+                    format!("Loading... {}/{} entries", done, total),
+                ))?;
+            }
+
+            // a download writing its file sends many events; the folder is only read once
+            let mut changed = false;
             while let Some(res) = wrx.try_iter().next() {
-                if res.is_ok() && &self.current_tab == "local" {
-                    self.refresh().await?;
+                match res {
+                    Ok(_) => changed = true,
+                    Err(err) => log::error!("watching the download directory failed: {}", err),
+                }
+            }
+            if changed {
+                self.report_downloaded_files();
+                if &self.current_tab == "local" {
+                    self.refresh_in_background();
                 }
             }
 
-            if frame % (30 * self.refresh_timer) == 0 && &self.current_tab != "local" {
-                self.refresh().await?;
+            if self.config.ping_interval > 0 && ping.due() {
+                self.ping_servers();
             }
-            frame += 1;
+
+            // once a second is enough for "updated 2m ago"
+            if every_second.due() {
+                self.report_last_refresh()?;
+                watching = self.check_watch(&mut watcher, watching).await?;
+            }
+
+            if auto_refresh.due() {
+                self.refresh_all();
+            }
+        }
+
+        self.shutdown().await
+    }
+
+    /// Stops whatever is still running and saves the state that is kept between runs. Downloads
+    /// are cancelled, and files they left half written are removed.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the last visited pages cannot be written to the config.
+    ///
+    async fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
+        for handle in &self.downloads {
+            handle.abort();
+        }
+        // a download that is already writing its file finishes doing so
+        for handle in self.downloads.drain(..) {
+            let _ = handle.await;
+        }
+        if let Ok(dir) = self.download_directory.to_file_path() {
+            remove_partial_downloads(&dir);
+        }
+
+        let names: Vec<String> = self.connections.keys().cloned().collect();
+        for name in names {
+            self.remember_page(&name).await?;
+        }
+
+        // temporary connections are gone by the next run
+        self.session.active_connection =
+            Some(self.current_tab.clone()).filter(|t| !self.temporary.contains(t));
+        if let Err(err) = self.session.write(&self.config.session_path()) {
+            log::error!("could not save session: {}", err);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockServer, Response};
+
+    #[test]
+    fn prompts_once_passwords_keep_being_rejected() {
+        let mut failures = HashMap::new();
+        assert!(!count_auth_failure(&mut failures, "calibre", true));
+        assert!(count_auth_failure(&mut failures, "calibre", true));
+        assert!(count_auth_failure(&mut failures, "calibre", true));
+        // every connection is counted on its own
+        assert!(!count_auth_failure(&mut failures, "kavita", true));
+    }
+
+    #[test]
+    fn prompts_right_away_without_a_password() {
+        let mut failures = HashMap::new();
+        assert!(count_auth_failure(&mut failures, "calibre", false));
+        assert!(failures.is_empty());
+    }
+
+    #[test]
+    fn untitled_pages_are_titled_by_address() {
+        let addr = Url::parse("file:///home/reader/books/").unwrap();
+        let conn = LocalConnection::new(addr.clone());
+        assert_eq!(page_title(&conn), addr.to_string());
+    }
+
+    #[tokio::test]
+    async fn silent_links_are_unreachable_after_the_time_limit() {
+        let srv = MockServer::start(|_| {
+            Response::new(200, "").with_header("Content-Type", "application/epub+zip")
+        })
+        .await;
+        // accepts connections but never answers them
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_url =
+            Url::parse(&format!("http://{}/1.pdf", silent.local_addr().unwrap())).unwrap();
+
+        let client = reqwest::Client::new();
+        let epub = srv.url("/1.epub");
+        let links = vec![
+            (
+                epub.clone(),
+                "application/epub+zip".to_string(),
+                client.head(epub.clone()),
+            ),
+            (
+                silent_url.clone(),
+                "application/pdf".to_string(),
+                client.head(silent_url.clone()),
+            ),
+        ];
+        let statuses: HashMap<_, _> = check_formats(links, Duration::from_millis(200))
+            .await
+            .into_iter()
+            .collect();
+
+        assert_eq!(statuses[&epub], FormatStatus::Available);
+        assert_eq!(statuses[&silent_url], FormatStatus::Unreachable);
+    }
+
+    #[tokio::test]
+    async fn refreshed_pages_keep_their_entries_and_note() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/"><id>f</id><title>Feed</title><updated>2024-01-01T00:00:00Z</updated>
+            <opensearch:totalResults>437</opensearch:totalResults>
+            <entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.epub" type="application/epub+zip"/>
+            </entry></feed>"#;
+        let srv = MockServer::start(move |_| Response::new(200, feed)).await;
+        let s = Server::new(srv.url("/opds"));
+        let mut conn = OnlineConnection::new(&s, reqwest::Client::new(), None)
+            .await
+            .unwrap();
+        let addr = conn.current_address();
+        conn.get_page(&addr).await.unwrap();
+        let shown = conn.cached_page(&addr).unwrap();
+
+        // a page that did not change is recognized as the one already shown
+        conn.invalidate(&addr);
+        let refreshed = conn.get_page(&addr).await.unwrap();
+        assert_eq!(refreshed, shown);
+        assert_eq!(paging_note(&conn, refreshed.len()), "showing 1–1 of 437");
+    }
+
+    #[tokio::test]
+    async fn restoring_a_dead_page_stays_at_the_root() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>f</id><title>Feed</title><updated>2024-01-01T00:00:00Z</updated></feed>"#;
+        let srv = MockServer::start(move |req| match req.path.as_str() {
+            "/opds" | "/opds/new" => Response::new(200, feed),
+            _ => Response::new(404, ""),
+        })
+        .await;
+        let s = Server::new(srv.url("/opds"));
+        let mut conn = OnlineConnection::new(&s, reqwest::Client::new(), None)
+            .await
+            .unwrap();
+
+        assert!(restore_page(&mut conn, &srv.url("/opds/gone"))
+            .await
+            .is_err());
+        assert_eq!(conn.current_address(), srv.url("/opds"));
+
+        restore_page(&mut conn, &srv.url("/opds/new"))
+            .await
+            .unwrap();
+        assert_eq!(conn.current_address(), srv.url("/opds/new"));
+    }
+
+    #[tokio::test]
+    async fn silent_servers_are_unreachable_after_the_time_limit() {
+        // accepts connections but never answers them
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut s = Server::new(
+            Url::parse(&format!("http://{}/opds", silent.local_addr().unwrap())).unwrap(),
+        );
+        let client = reqwest::Client::new();
+        let health = ping_with_timeout(&client, &s, &None, Duration::from_millis(200)).await;
+        assert_eq!(health, Health::Unreachable);
+
+        let srv = MockServer::start(|_| Response::new(200, "")).await;
+        s.base_url = srv.url("/opds");
+        let health = ping_with_timeout(&client, &s, &None, Duration::from_millis(200)).await;
+        assert_eq!(health, Health::Reachable);
+    }
+}
@@ -0,0 +1,228 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use keyring::{Entry, Error};
+use rand::Rng;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+const SERVICE: &str = "ncopds";
+const SALT_LEN: usize = 16;
+const KEY_ITERATIONS: u32 = 100_000;
+
+/// Where passwords are kept between sessions.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialBackend {
+    /// the OS keyring
+    Keyring,
+    /// a file in the config directory, encrypted with a passphrase asked for at startup
+    File,
+}
+
+/// Storage for connection passwords. Errors use the keyring's error type so callers can treat
+/// every backend the same way; a missing password is always `Error::NoEntry`.
+pub trait CredentialStore: Send {
+    /// Returns the password stored under the key.
+    fn get_password(&self, key: &str) -> Result<String, Error>;
+    /// Stores a password under the key, replacing any existing one.
+    fn set_password(&mut self, key: &str, password: &str) -> Result<(), Error>;
+    /// Removes the password stored under the key.
+    fn delete_password(&mut self, key: &str) -> Result<(), Error>;
+}
+
+/// Stores passwords in the OS keyring.
+pub struct KeyringStore;
+
+impl CredentialStore for KeyringStore {
+    fn get_password(&self, key: &str) -> Result<String, Error> {
+        Entry::new(SERVICE, key)?.get_password()
+    }
+
+    fn set_password(&mut self, key: &str, password: &str) -> Result<(), Error> {
+        Entry::new(SERVICE, key)?.set_password(password)
+    }
+
+    fn delete_password(&mut self, key: &str) -> Result<(), Error> {
+        Entry::new(SERVICE, key)?.delete_credential()
+    }
+}
+
+/// Keeps passwords in memory only, so they are asked for again in every session.
+#[derive(Default)]
+pub struct MemoryStore {
+    passwords: HashMap<String, String>,
+}
+
+impl CredentialStore for MemoryStore {
+    fn get_password(&self, key: &str) -> Result<String, Error> {
+        self.passwords.get(key).cloned().ok_or(Error::NoEntry)
+    }
+
+    fn set_password(&mut self, key: &str, password: &str) -> Result<(), Error> {
+        self.passwords.insert(key.to_string(), password.to_string());
+        Ok(())
+    }
+
+    fn delete_password(&mut self, key: &str) -> Result<(), Error> {
+        self.passwords.remove(key).map(|_| ()).ok_or(Error::NoEntry)
+    }
+}
+
+/// Layout of the credentials file on disk. All fields are base64 encoded.
+#[derive(Deserialize, Serialize)]
+struct EncryptedFile {
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+/// Stores passwords in a file encrypted with ChaCha20-Poly1305. The key is derived from a
+/// passphrase, so the file is useless without it.
+pub struct FileStore {
+    path: PathBuf,
+    salt: [u8; SALT_LEN],
+    key: [u8; 32],
+    passwords: HashMap<String, String>,
+}
+
+impl FileStore {
+    /// Unlocks the credentials file at the path. A new, empty store is started if the file does
+    /// not exist yet; it is only written once a password is stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the credentials file.
+    /// * `passphrase` - Passphrase the file is encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the file cannot be read, is damaged or the passphrase is wrong.
+    ///
+    pub fn open(path: &Path, passphrase: &str) -> Result<FileStore, Error> {
+        if !path.exists() {
+            let mut salt = [0u8; SALT_LEN];
+            rand::thread_rng().fill(&mut salt);
+
+            return Ok(FileStore {
+                path: path.to_path_buf(),
+                salt,
+                key: derive_key(passphrase, &salt),
+                passwords: HashMap::new(),
+            });
+        }
+
+        let contents = fs::read_to_string(path).map_err(platform_failure)?;
+        let file: EncryptedFile = serde_json::from_str(&contents).map_err(platform_failure)?;
+
+        let salt: [u8; SALT_LEN] = decode(&file.salt)?;
+        let nonce: [u8; NONCE_LEN] = decode(&file.nonce)?;
+        let mut data = STANDARD.decode(&file.data).map_err(platform_failure)?;
+
+        let key = derive_key(passphrase, &salt);
+        let plain = cipher(&key)
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| platform_failure("wrong passphrase or damaged credentials file"))?;
+        let passwords = serde_json::from_slice(plain).map_err(platform_failure)?;
+
+        Ok(FileStore {
+            path: path.to_path_buf(),
+            salt,
+            key,
+            passwords,
+        })
+    }
+
+    /// Encrypts the passwords with a fresh nonce and writes them to disk. The file is only
+    /// readable by the current user.
+    fn save(&self) -> Result<(), Error> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill(&mut nonce);
+
+        let mut data = serde_json::to_vec(&self.passwords).map_err(platform_failure)?;
+        cipher(&self.key)
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| platform_failure("could not encrypt credentials"))?;
+
+        let file = EncryptedFile {
+            salt: STANDARD.encode(self.salt),
+            nonce: STANDARD.encode(nonce),
+            data: STANDARD.encode(data),
+        };
+        let contents = serde_json::to_string(&file).map_err(platform_failure)?;
+
+        let mut options = fs::File::options();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut f = options.open(&self.path).map_err(platform_failure)?;
+        f.write_all(contents.as_bytes()).map_err(platform_failure)
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn get_password(&self, key: &str) -> Result<String, Error> {
+        self.passwords.get(key).cloned().ok_or(Error::NoEntry)
+    }
+
+    fn set_password(&mut self, key: &str, password: &str) -> Result<(), Error> {
+        self.passwords.insert(key.to_string(), password.to_string());
+        self.save()
+    }
+
+    fn delete_password(&mut self, key: &str) -> Result<(), Error> {
+        self.passwords.remove(key).ok_or(Error::NoEntry)?;
+        self.save()
+    }
+}
+
+/// Picks the backend to use. Without a configured backend, the keyring is used if it can be
+/// reached and the encrypted file otherwise.
+///
+/// # Arguments
+///
+/// * `configured` - Backend set in the config file, if any.
+///
+pub fn backend(configured: Option<CredentialBackend>) -> CredentialBackend {
+    configured.unwrap_or_else(|| {
+        // a reachable keyring reports the probe entry as missing instead of failing
+        match Entry::new(SERVICE, "ncopds-probe").and_then(|e| e.get_password()) {
+            Ok(_) | Err(Error::NoEntry) => CredentialBackend::Keyring,
+            Err(_) => CredentialBackend::File,
+        }
+    })
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(KEY_ITERATIONS).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+fn cipher(key: &[u8; 32]) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).expect("key has the wrong length"))
+}
+
+fn decode<const N: usize>(value: &str) -> Result<[u8; N], Error> {
+    STANDARD
+        .decode(value)
+        .map_err(platform_failure)?
+        .try_into()
+        .map_err(|_| platform_failure("damaged credentials file"))
+}
+
+fn platform_failure<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> Error {
+    Error::PlatformFailure(err.into())
+}
@@ -0,0 +1,153 @@
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Logger that keeps feeding cursive's debug console and additionally appends every record to a
+/// file. Once the file grows past `max_size`, it is moved to `<name>.1` and a new one is started,
+/// so at most two files are ever kept around.
+pub struct FileLogger {
+    path: PathBuf,
+    max_size: u64,
+    /// open log file and the number of bytes in it
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl FileLogger {
+    /// Opens (or creates) the log file. Logging to the file is silently skipped if it cannot be
+    /// opened, the debug console keeps working either way.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the log file.
+    /// * `max_size` - Size in bytes after which the file is rotated.
+    ///
+    pub fn new(path: &Path, max_size: u64) -> FileLogger {
+        FileLogger {
+            path: path.to_path_buf(),
+            max_size,
+            file: Mutex::new(FileLogger::open(path)),
+        }
+    }
+
+    fn open(path: &Path) -> Option<(File, u64)> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).ok()?;
+        }
+
+        let file = File::options().create(true).append(true).open(path).ok()?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Some((file, size))
+    }
+
+    /// Moves the current log file out of the way and starts a fresh one.
+    fn rotate(&self) -> Option<(File, u64)> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        fs::rename(&self.path, rotated).ok()?;
+        FileLogger::open(&self.path)
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // the level can be changed while running, see set_level
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        // the console does its own filtering
+        cursive::logger::get_logger().log(record);
+
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let mut lock = self.file.lock().unwrap();
+        if lock
+            .as_ref()
+            .is_some_and(|(_, size)| *size >= self.max_size)
+        {
+            *lock = self.rotate();
+        }
+
+        if let Some((file, size)) = lock.as_mut() {
+            if file.write_all(line.as_bytes()).is_ok() {
+                *size += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((file, _)) = self.file.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Picks the log level. `RUST_LOG` takes precedence over the configured level; anything that
+/// cannot be parsed falls back to info.
+///
+/// # Arguments
+///
+/// * `configured` - Level from the config file, if any.
+///
+pub fn log_level(configured: &Option<String>) -> LevelFilter {
+    std::env::var("RUST_LOG")
+        .ok()
+        .or(configured.clone())
+        .and_then(|l| LevelFilter::from_str(&l).ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Installs the logger. Replaces `cursive::logger::init`, records still show up in the debug
+/// console.
+///
+/// # Arguments
+///
+/// * `path` - Path of the log file.
+/// * `level` - Most verbose level that is recorded.
+/// * `max_size` - Size in bytes after which the file is rotated.
+///
+pub fn init(path: &Path, level: LevelFilter, max_size: u64) {
+    set_level(level);
+    log::set_boxed_logger(Box::new(FileLogger::new(path, max_size)))
+        .expect("logger was already set");
+}
+
+/// Changes the most verbose level that is recorded, both in the debug console and the log file.
+///
+/// # Arguments
+///
+/// * `level` - Most verbose level that is recorded.
+///
+pub fn set_level(level: LevelFilter) {
+    cursive::logger::set_external_filter_level(level);
+    cursive::logger::set_internal_filter_level(level);
+    log::set_max_level(level);
+}
+
+/// Switches to the next, more verbose log level, going from trace back to error. Returns the new
+/// level.
+pub fn cycle_level() -> LevelFilter {
+    let next = match log::max_level() {
+        LevelFilter::Off | LevelFilter::Trace => LevelFilter::Error,
+        LevelFilter::Error => LevelFilter::Warn,
+        LevelFilter::Warn => LevelFilter::Info,
+        LevelFilter::Info => LevelFilter::Debug,
+        LevelFilter::Debug => LevelFilter::Trace,
+    };
+    set_level(next);
+    next
+}
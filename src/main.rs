@@ -1,19 +1,45 @@
+pub mod cli;
 pub mod config;
 pub mod connection;
 pub mod controller;
+mod credentials;
+pub mod logging;
+#[cfg(test)]
+mod mock_server;
 pub mod model;
 pub mod server;
+mod session;
+#[cfg(test)]
+mod test_utils;
 pub mod ui;
 pub mod utils;
 
+use cli::Opt;
 use config::{read_config, Config, CONFIG_DIRECTORY};
 use controller::{Controller, ControllerMessage};
 use std::env;
 use std::error::Error;
 use std::path::Path;
+use structopt::StructOpt;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::from_args();
+    let home = env::var("HOME").expect("could not read $HOME").to_string();
+
+    let tp = format!("{}{}{}", home, CONFIG_DIRECTORY, "theme.toml");
+    let cp = format!("{}{}{}", home, CONFIG_DIRECTORY, "config.toml");
+    let config: Config = read_config(Path::new(&cp)).expect("Invalid config");
+
+    // scripted runs never touch the terminal interface
+    if opt.is_batch() {
+        if let Err(e) = cli::run(opt, config).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // TODO: move into separate function, work towards supporting mac & win
     if std::env::consts::OS != "linux" {
         println!("Warning: your operating system is not currently supported. You may run into strange bugs and features not working correctly! Press any key to continue.");
@@ -21,13 +47,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
         let _ = std::io::stdin().read_line(&mut s);
     }
 
-    let home = env::var("HOME").expect("could not read $HOME").to_string();
     let t_size = termsize::get().expect("could not read terminal size");
 
-    let tp = format!("{}{}{}", home, CONFIG_DIRECTORY, "theme.toml");
-    let cp = format!("{}{}{}", home, CONFIG_DIRECTORY, "config.toml");
-    let config: Config = read_config(Path::new(&cp)).expect("Invalid config");
-
     let controller = Controller::new(config, Path::new(&cp), Path::new(&tp), t_size);
     match controller {
         Ok(mut controller) => controller.run().await?,
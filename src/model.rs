@@ -1,24 +1,418 @@
 // perhaps rename to Entry?
 
-use atom_syndication::Entry;
+use atom_syndication::{Entry, Feed, TextType};
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::SystemTime;
 use url::Url;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EntryData {
     pub title: String,
     pub details: String,
     pub author: Option<String>,
     pub unsupported: Option<String>,
-    pub downloads: Vec<(Url, String)>,
+    /// link, mime type and, if the feed gives it, size in bytes of every download
+    pub downloads: Vec<(Url, String, Option<u64>)>,
     pub image: Option<Url>,
     pub href: Option<Url>,
+    /// when the entry was first published, if the feed says
+    pub published: Option<DateTime<FixedOffset>>,
+    /// when the entry was added to the catalog or last changed, if the feed says
+    pub updated: Option<DateTime<FixedOffset>>,
+    /// feed listing more books by the author, if the catalog links one
+    pub author_link: Option<Url>,
+    /// title of the group the entry belongs to, if the feed groups its entries
+    pub group: Option<String>,
+    /// text of the entry when the feed carries it inline, i.e. a chapter of a serial; HTML is
+    /// converted to plain text
+    pub content: Option<String>,
+    /// the entry's links as the feed wrote them, kept to tell why an entry is shown wrong
+    pub raw_links: Vec<RawLink>,
+}
+
+/// A link of an entry as the feed wrote it, before it is resolved or sorted into downloads,
+/// covers and feeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawLink {
+    pub rel: String,
+    pub href: String,
+    pub mime_type: Option<String>,
+    pub title: Option<String>,
+    pub length: Option<String>,
 }
 
+/// A download that finished, whether it was saved or not, as listed among the recent downloads.
 #[derive(Debug, Clone)]
+pub struct FinishedDownload {
+    /// name of the saved file, or the URL if nothing was saved
+    pub name: String,
+    /// where the file was saved; missing if it was not
+    pub path: Option<PathBuf>,
+    /// size of the file in bytes, if it was downloaded
+    pub size: Option<u64>,
+    /// why the file was not saved, or what is wrong with the file that was
+    pub problem: Option<String>,
+}
+
+impl FinishedDownload {
+    /// Describes the download in one line, i.e. "✓ moby.epub (1.0 MB) in /tmp/books".
+    pub fn describe(&self) -> String {
+        let Some(path) = &self.path else {
+            return format!(
+                "✗ {}: {}",
+                self.name,
+                self.problem.as_deref().unwrap_or("failed")
+            );
+        };
+
+        let size = self
+            .size
+            .map(|s| format!(" ({})", crate::utils::human_readable_size(s)))
+            .unwrap_or_default();
+        let dir = path.parent().unwrap_or(path).display();
+        match &self.problem {
+            Some(problem) => format!("! {}{} in {}: {}", self.name, size, dir, problem),
+            None => format!("✓ {}{} in {}", self.name, size, dir),
+        }
+    }
+}
+
+/// Details read from the filesystem for local entries. Missing when the metadata could not be
+/// read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// OpenSearch paging hints a feed can carry, i.e. for search results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Paging {
+    pub total_results: Option<u64>,
+    pub start_index: Option<u64>,
+    pub items_per_page: Option<u64>,
+}
+
+impl Paging {
+    /// Describes which results are shown, i.e. "showing 1–20 of 437". Returns none if the feed
+    /// did not say how many results there are.
+    ///
+    /// # Arguments
+    ///
+    /// * `shown` - Number of entries on the page.
+    ///
+    pub fn describe(&self, shown: usize) -> Option<String> {
+        let total = self.total_results?;
+        if shown == 0 {
+            return Some(format!("{} results", total));
+        }
+
+        // OpenSearch counts from 1
+        let start = self.start_index.unwrap_or(1);
+        let end = start + shown as u64 - 1;
+        Some(format!("showing {}–{} of {}", start, end, total))
+    }
+}
+
+/// What the file view is showing, which decides the message below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageState {
+    /// the page is being fetched
+    Loading,
+    /// the page was fetched; the note, if any, says which of the results it holds
+    Loaded(String),
+    /// the page could not be fetched
+    Error(String),
+}
+
+impl PageState {
+    /// Returns the message shown below the file view.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Number of entries on the page.
+    ///
+    pub fn message(&self, entries: usize) -> String {
+        match self {
+            PageState::Loading => "Loading...".to_string(),
+            PageState::Error(err) => format!("Load failed: {}", err),
+            PageState::Loaded(note) if entries == 0 && note.is_empty() => {
+                "No files found.".to_string()
+            }
+            // an empty page of search results still says how many results there are
+            PageState::Loaded(note) => note.clone(),
+        }
+    }
+}
+
+/// The name a feed gives itself, i.e. the name of a catalog or one of its sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedTitle {
+    pub title: String,
+    pub subtitle: Option<String>,
+}
+
+impl FeedTitle {
+    /// Reads the title and subtitle of a feed. Returns none if the feed has no title.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - Parsed feed.
+    ///
+    pub fn from_feed(doc: &Feed) -> Option<FeedTitle> {
+        let title = doc.title().trim();
+        if title.is_empty() {
+            return None;
+        }
+
+        let subtitle = doc
+            .subtitle()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        Some(FeedTitle {
+            title: title.to_string(),
+            subtitle,
+        })
+    }
+
+    /// Joins the title and subtitle into one line, i.e. "Project Gutenberg — Free ebooks".
+    pub fn describe(&self) -> String {
+        match &self.subtitle {
+            Some(s) => format!("{} — {}", self.title, s),
+            None => self.title.clone(),
+        }
+    }
+}
+
+/// Links a feed gives to the catalog around it, i.e. its parent feed or the catalog's root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedLinks {
+    /// the feed one level up, from rel="up"
+    pub up: Option<Url>,
+    /// the root of the catalog, from rel="start"
+    pub start: Option<Url>,
+}
+
+impl FeedLinks {
+    /// Reads the navigation links of a feed. Returns none if it has neither.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - Parsed feed.
+    /// * `base` - URL relative links are resolved against.
+    ///
+    pub fn from_feed(doc: &Feed, base: &Url) -> Option<FeedLinks> {
+        let link = |rel: &str| {
+            doc.links()
+                .iter()
+                .find(|l| l.rel() == rel)
+                .and_then(|l| crate::utils::parse_href(l.href(), base).ok())
+        };
+
+        let links = FeedLinks {
+            up: link("up"),
+            start: link("start"),
+        };
+        (links != FeedLinks::default()).then_some(links)
+    }
+}
+
+/// A way of narrowing a page that the feed offers, i.e. only books in English. See [OPDS
+/// facets](https://specs.opds.io/opds-1.2#4-facets).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Facet {
+    pub title: String,
+    /// group of facets it belongs to, i.e. "Language"
+    pub group: Option<String>,
+    /// the page narrowed by the facet
+    pub href: Url,
+    /// whether the page is already narrowed by it
+    pub active: bool,
+    /// number of entries the narrowed page has, if the feed says
+    pub count: Option<u64>,
+}
+
+impl Facet {
+    /// Describes the facet for the facet menu, i.e. "Language: English (12)".
+    pub fn label(&self) -> String {
+        let mut label = match &self.group {
+            Some(g) => format!("{}: {}", g, self.title),
+            None => self.title.clone(),
+        };
+        if let Some(count) = self.count {
+            label.push_str(&format!(" ({})", count));
+        }
+        if self.active {
+            label.push_str(" ✓");
+        }
+        label
+    }
+}
+
+impl EntryData {
+    /// Whether the entry leads anywhere. Entries without downloads or a feed of their own only
+    /// describe something; some catalogs use them for notices.
+    pub fn is_actionable(&self) -> bool {
+        self.href.is_some() || !self.downloads.is_empty()
+    }
+
+    /// Describes the entry's links as the feed wrote them and what ncopds made of them, to be
+    /// pasted into a bug report. Links are redacted with `redact_href`.
+    pub fn debug_info(&self) -> String {
+        let url = |u: &Option<Url>| {
+            u.as_ref()
+                .map_or("-".to_string(), |u| crate::utils::redact_href(u.as_str()))
+        };
+        let date =
+            |d: &Option<DateTime<FixedOffset>>| d.map_or("-".to_string(), |d| d.to_rfc3339());
+
+        let mut lines = vec![
+            format!("ncopds {} entry: {}", env!("CARGO_PKG_VERSION"), self.title),
+            "links:".to_string(),
+        ];
+        for l in &self.raw_links {
+            let mut line = format!(
+                "  rel={} href={}",
+                l.rel,
+                crate::utils::redact_href(&l.href)
+            );
+            for (name, value) in [
+                ("type", &l.mime_type),
+                ("title", &l.title),
+                ("length", &l.length),
+            ] {
+                if let Some(v) = value {
+                    line.push_str(&format!(" {}={}", name, v));
+                }
+            }
+            lines.push(line);
+        }
+
+        lines.push("parsed:".to_string());
+        lines.push(format!(
+            "  author={}",
+            self.author.as_deref().unwrap_or("-")
+        ));
+        lines.push(format!("  feed={}", url(&self.href)));
+        lines.push(format!("  image={}", url(&self.image)));
+        lines.push(format!("  author_link={}", url(&self.author_link)));
+        for (href, mime, size) in &self.downloads {
+            let size = size.map_or(String::new(), |s| format!(" ({} bytes)", s));
+            lines.push(format!(
+                "  download={} {}{}",
+                mime,
+                crate::utils::redact_href(href.as_str()),
+                size
+            ));
+        }
+        lines.push(format!(
+            "  unsupported={}",
+            self.unsupported.as_deref().unwrap_or("-")
+        ));
+        lines.push(format!("  group={}", self.group.as_deref().unwrap_or("-")));
+        lines.push(format!(
+            "  published={} updated={}",
+            date(&self.published),
+            date(&self.updated)
+        ));
+        lines.push(format!(
+            "  details={} chars content={} chars",
+            self.details.chars().count(),
+            self.content.as_ref().map_or(0, |c| c.chars().count())
+        ));
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum EntryType {
-    File(String, Url),
-    Directory(String, Url),
-    OPDSEntry(EntryData),
+    File(String, Url, Option<FileInfo>),
+    Directory(String, Url, Option<FileInfo>),
+    OPDSEntry(Box<EntryData>),
+    /// title of a group of OPDS entries, shown above them; never selectable
+    Header(String),
+    /// stands in for the entries of a page that are not shown yet; selecting it shows the next
+    /// batch. Holds how many are left.
+    More(usize),
+}
+
+/// Picks the download that should be used when no format was chosen explicitly. Returns the
+/// first download whose mime type contains the earliest matching preference, or the first
+/// download if none match.
+///
+/// # Arguments
+///
+/// * `data` - Entry to pick a download from.
+/// * `preferred` - Format names in order of preference, i.e. "epub" matches
+///   `application/epub+zip`.
+///
+pub fn preferred_download(data: &EntryData, preferred: &[String]) -> Option<Url> {
+    preferred
+        .iter()
+        .find_map(|format| {
+            let format = format.to_lowercase();
+            data.downloads
+                .iter()
+                .find(|(_, mt, _)| mt.to_lowercase().contains(&format))
+        })
+        .or(data.downloads.first())
+        .map(|(href, _, _)| href.clone())
+}
+
+/// Returns the name a download from the URL is saved under when the server does not suggest one,
+/// i.e. the last segment of its path.
+///
+/// # Arguments
+///
+/// * `url` - URL of the download.
+///
+pub fn download_filename(url: &Url) -> Option<String> {
+    url.path_segments()?
+        .next_back()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// Checks whether the entry's preferred download is already in the download directory.
+///
+/// # Arguments
+///
+/// * `data` - Entry to check.
+/// * `preferred` - Format names in order of preference.
+/// * `downloaded` - Names of the files in the download directory.
+///
+pub fn is_downloaded(data: &EntryData, preferred: &[String], downloaded: &HashSet<String>) -> bool {
+    preferred_download(data, preferred)
+        .and_then(|u| download_filename(&u))
+        .is_some_and(|f| downloaded.contains(&f))
+}
+
+/// Reads the text of an Atom summary or content as plain text. HTML is converted, and so is text
+/// marked as plain that still carries tags, which many catalogs send. Entities left in plain text
+/// by escaping it twice are decoded.
+///
+/// # Arguments
+///
+/// * `value` - Text of the element.
+/// * `kind` - Its `type` attribute: "text", "html", "xhtml" or a mime type.
+///
+fn readable_text(value: &str, kind: Option<&str>) -> String {
+    let is_html = match kind {
+        Some("html") | Some("xhtml") => true,
+        Some("text") | None => value.match_indices('<').any(|(i, _)| {
+            value[i + 1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/')
+                && value[i..].contains('>')
+        }),
+        Some(other) => other.contains("html"),
+    };
+
+    if is_html {
+        crate::utils::html_to_text(value)
+    } else {
+        crate::utils::decode_entities(value.trim())
+    }
 }
 
 // add test
@@ -28,7 +422,8 @@ pub enum EntryType {
 /// # Arguments
 ///
 /// * `entry` - Entry to convert.
-/// * `base_url` - Domain of OPDS this entry was retrieved from.
+/// * `base_url` - URL of the feed this entry was retrieved from; relative links are resolved
+///   against it.
 ///
 /// # Errors
 ///
@@ -45,6 +440,10 @@ pub fn process_opds_entry(
 
     let mut entry_details = String::from("");
     let mut author = None;
+    let mut author_link = authors
+        .iter()
+        .find_map(|a| a.uri())
+        .and_then(|u| crate::utils::parse_href(u, base_url).ok());
 
     if !authors.is_empty() {
         author = Some(
@@ -57,11 +456,22 @@ pub fn process_opds_entry(
     }
 
     if let Some(s) = summary {
-        entry_details += &format!("Summary: {0}\n\n", s.to_string());
+        let kind = match s.r#type {
+            TextType::Text => "text",
+            TextType::Html => "html",
+            TextType::Xhtml => "xhtml",
+        };
+        entry_details += &format!("Summary: {0}\n\n", readable_text(s.as_str(), Some(kind)));
     }
 
-    if let Some(c) = content {
-        entry_details += &format!("{}\n", c.value().unwrap());
+    // content with a src attribute lives elsewhere and has no value
+    let text = content.and_then(|c| {
+        let text = readable_text(c.value()?, c.content_type());
+        Some(text).filter(|t| !t.is_empty())
+    });
+
+    if let Some(t) = &text {
+        entry_details += &format!("{}\n", t);
     }
 
     if !categories.is_empty() {
@@ -78,6 +488,19 @@ pub fn process_opds_entry(
 
     let mut f_href = None;
     let mut unsupported = None;
+    let mut group = None;
+
+    let raw_links = entry
+        .links()
+        .iter()
+        .map(|l| RawLink {
+            rel: l.rel().to_string(),
+            href: l.href().to_string(),
+            mime_type: l.mime_type().map(String::from),
+            title: l.title().map(String::from),
+            length: l.length().map(String::from),
+        })
+        .collect();
 
     for link in entry.links() {
         let href = crate::utils::parse_href(&link.href, base_url)?;
@@ -93,9 +516,22 @@ pub fn process_opds_entry(
             unsupported = Some(String::from(rel));
         }
 
-        let mt = link
-            .mime_type()
-            .expect("malformed feed, expected mime-type");
+        // related feeds are usually the author's other books, not the entry itself
+        if rel == "related" {
+            author_link.get_or_insert(href);
+            continue;
+        }
+
+        // the group's own feed lists all of its entries; only its title is needed here
+        if rel == "collection" {
+            group = Some(link.title().map_or(href.to_string(), |t| t.to_string()));
+            continue;
+        }
+
+        // there is no telling what an untyped link leads to
+        let Some(mt) = link.mime_type() else {
+            continue;
+        };
 
         // this makes it into a directory
         if mt.contains("application/atom+xml") {
@@ -103,11 +539,12 @@ pub fn process_opds_entry(
         } else if mt.contains("image") {
             image = Some(href);
         } else {
-            downloads.push((href, String::from(mt)));
+            let size = link.length().and_then(|l| l.parse().ok());
+            downloads.push((href, String::from(mt), size));
         }
     }
 
-    Ok(EntryType::OPDSEntry(EntryData {
+    Ok(EntryType::OPDSEntry(Box::new(EntryData {
         title: entry.title().to_string(),
         author,
         details: entry_details,
@@ -115,7 +552,70 @@ pub fn process_opds_entry(
         downloads,
         image,
         href: f_href,
-    }))
+        published: entry.published().copied(),
+        // atom_syndication fills in the epoch when the feed leaves it out
+        updated: Some(*entry.updated()).filter(|u| u.timestamp() != 0),
+        author_link,
+        group,
+        content: text,
+        raw_links,
+    })))
+}
+
+/// Puts a header above each group of entries, for feeds that group them. Entries that follow a
+/// group without belonging to one are put under "Other".
+///
+/// # Arguments
+///
+/// * `entries` - Entries in the order the feed lists them.
+///
+pub fn with_group_headers(entries: Vec<EntryType>) -> Vec<EntryType> {
+    let grouped = entries
+        .iter()
+        .any(|e| matches!(e, EntryType::OPDSEntry(d) if d.group.is_some()));
+    if !grouped {
+        return entries;
+    }
+
+    let mut current = None;
+    let mut result = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let group = match &entry {
+            EntryType::OPDSEntry(d) => d.group.clone(),
+            _ => None,
+        };
+
+        if group != current {
+            let title = group.clone().unwrap_or(String::from("Other"));
+            result.push(EntryType::Header(title));
+            current = group;
+        }
+        result.push(entry);
+    }
+    result
+}
+
+/// Sorts the entries of a catalog page by when they were added, newest first. Entries that do not
+/// say when they were added are put last, in the order the feed lists them. Groups are not kept
+/// together, so no headers are added. Local directories are left as they are.
+///
+/// # Arguments
+///
+/// * `entries` - Entries in the order the feed lists them.
+///
+pub fn sorted_by_date(mut entries: Vec<EntryType>) -> Vec<EntryType> {
+    let date = |e: &EntryType| match e {
+        EntryType::OPDSEntry(d) => d.updated,
+        _ => None,
+    };
+
+    if !entries.iter().any(|e| matches!(e, EntryType::OPDSEntry(_))) {
+        return entries;
+    }
+
+    // none is smaller than any date, so it ends up last
+    entries.sort_by_key(|e| std::cmp::Reverse(date(e)));
+    entries
 }
 
 /// Convenience method to retrieve the title for an Entry
@@ -126,8 +626,128 @@ pub fn process_opds_entry(
 ///
 pub fn get_title_for_entry(e: &EntryType) -> String {
     match e {
-        EntryType::File(t, _) => t.to_string(),
-        EntryType::Directory(t, _) => t.to_string(),
+        EntryType::File(t, _, _) => t.to_string(),
+        EntryType::Directory(t, _, _) => t.to_string(),
         EntryType::OPDSEntry(data) => data.title.clone(),
+        EntryType::Header(t) => t.to_string(),
+        EntryType::More(left) => format!("Show more ({} left)", left),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::entry_data;
+
+    /// Parses a feed with the given entries and returns its first entry.
+    fn entry(xml: &str) -> Entry {
+        let feed = format!(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>f</id><title>Feed</title><updated>2024-01-01T00:00:00Z</updated>{}</feed>"#,
+            xml
+        );
+        Feed::read_from(feed.as_bytes()).unwrap().entries()[0].clone()
+    }
+
+    #[test]
+    fn mixed_entry_keeps_feed_and_downloads() {
+        let e = entry(
+            r#"<entry><id>1</id><title>Series</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="subsection" href="/opds/series/1" type="application/atom+xml;profile=opds-catalog;kind=acquisition"/>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.epub" type="application/epub+zip"/>
+            </entry>"#,
+        );
+        let base = Url::parse("http://example.com/opds/").unwrap();
+        let data = entry_data(process_opds_entry(&e, &base).unwrap());
+
+        assert_eq!(
+            data.href.unwrap().as_str(),
+            "http://example.com/opds/series/1"
+        );
+        assert_eq!(data.downloads.len(), 1);
+        assert_eq!(
+            data.downloads[0].0.as_str(),
+            "http://example.com/get/1.epub"
+        );
+        assert_eq!(data.downloads[0].1, "application/epub+zip");
+    }
+
+    #[test]
+    fn entries_without_links_keep_their_details() {
+        let e = entry(
+            r#"<entry><id>1</id><title>Maintenance</title><updated>2024-01-01T00:00:00Z</updated>
+            <summary>The catalog is read-only tonight.</summary>
+            <link href="/notice"/>
+            </entry>"#,
+        );
+        let base = Url::parse("http://example.com/opds/").unwrap();
+        let data = entry_data(process_opds_entry(&e, &base).unwrap());
+
+        assert!(!data.is_actionable());
+        assert!(data.href.is_none());
+        assert!(data.downloads.is_empty());
+        assert_eq!(data.title, "Maintenance");
+        assert!(data.details.contains("The catalog is read-only tonight."));
+        assert_eq!(data.raw_links.len(), 1);
+    }
+
+    #[test]
+    fn downloads_keep_their_sizes() {
+        let e = entry(
+            r#"<entry><id>1</id><title>Book</title><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.epub" type="application/epub+zip" length="2048"/>
+            <link rel="http://opds-spec.org/acquisition" href="/get/1.pdf" type="application/pdf"/>
+            </entry>"#,
+        );
+        let base = Url::parse("http://example.com/opds/").unwrap();
+        let data = entry_data(process_opds_entry(&e, &base).unwrap());
+
+        let sizes: Vec<_> = data
+            .downloads
+            .iter()
+            .map(|(_, mt, s)| (mt.as_str(), *s))
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                ("application/epub+zip", Some(2048)),
+                ("application/pdf", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn loading_pages_say_so() {
+        assert_eq!(PageState::Loading.message(0), "Loading...");
+        // entries of the previous page don't make a page loaded
+        assert_eq!(PageState::Loading.message(5), "Loading...");
+    }
+
+    #[test]
+    fn empty_pages_say_no_files_were_found() {
+        assert_eq!(
+            PageState::Loaded(String::new()).message(0),
+            "No files found."
+        );
+    }
+
+    #[test]
+    fn loaded_pages_show_their_note() {
+        assert_eq!(PageState::Loaded(String::new()).message(3), "");
+
+        let note = "Showing 21-40 of 45".to_string();
+        assert_eq!(PageState::Loaded(note.clone()).message(20), note);
+        // a page past the last result still says how many results there are
+        assert_eq!(PageState::Loaded(note.clone()).message(0), note);
+    }
+
+    #[test]
+    fn failed_pages_say_why() {
+        let state = PageState::Error("connection refused".to_string());
+        assert_eq!(state.message(0), "Load failed: connection refused");
+        assert_eq!(state.message(3), "Load failed: connection refused");
+        assert_ne!(
+            state.message(0),
+            PageState::Loaded(String::new()).message(0)
+        );
     }
 }
@@ -1,66 +1,211 @@
-use keyring::{Entry, Error};
+use crate::credentials::CredentialStore;
+use keyring::Error;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error as StdError;
 use std::fmt;
-use url::Url;
+use std::time::Duration;
+use url::{Host, Url};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Server {
     pub username: Option<String>,
     /// the url for the opds catalog, NOT just the domain name i.e https://example.com/opds
     pub base_url: Url,
+    /// overrides the default "ncopds" user-agent for requests made to this server
+    pub user_agent: Option<String>,
+    /// proxy that requests to this server go through, i.e. "http://proxy.lan:3128"
+    pub proxy: Option<String>,
+    /// seconds after which requests to this server give up; they wait indefinitely without one
+    pub timeout_seconds: Option<u64>,
+    /// whether certificates that cannot be verified, i.e. self-signed ones, are accepted; by
+    /// default they only are for servers on this machine
+    pub accept_invalid_certs: Option<bool>,
+    // toml writes tables after plain values, so this has to stay the last field
+    /// extra headers sent with every request to this server, i.e. an API key
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
 }
 
-/// Stores a password for a server in the system keychain.
+/// Stores a password for a server in the credential store.
 ///
 /// # Arguments
 ///
+/// * `store` - Credential store to write to.
 /// * `s` - Server credentials to store the password for.
 /// * `pwd` - Password to store.
 ///
-pub fn store_password(s: &Server, pwd: &Option<String>) {
-    match pwd {
-        Some(p) => match &s.username {
-            Some(u) => {
-                let entry = Entry::new("ncopds", &format!("{}@{}", &u, s.base_url)).unwrap();
-                entry.set_password(p).expect("failed to set password entry");
-            }
-            None => {}
-        },
-        None => {}
+/// # Errors
+///
+/// Errors if the store is unavailable or refuses to store the password.
+///
+pub fn store_password(
+    store: &mut dyn CredentialStore,
+    s: &Server,
+    pwd: &Option<String>,
+) -> Result<(), Error> {
+    if let (Some(p), Some(u)) = (pwd, &s.username) {
+        store.set_password(&format!("{}@{}", &u, s.base_url), p)?;
     }
+    Ok(())
+}
+
+/// Removes the password stored for a server, if there is one.
+///
+/// # Arguments
+///
+/// * `store` - Credential store to remove it from.
+/// * `s` - Server whose password is removed.
+///
+/// # Errors
+///
+/// Errors if the store is unavailable or refuses to remove the password.
+///
+pub fn delete_password(store: &mut dyn CredentialStore, s: &Server) -> Result<(), Error> {
+    if let Some(u) = &s.username {
+        match store.delete_password(&format!("{}@{}", &u, s.base_url)) {
+            Ok(()) | Err(Error::NoEntry) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
 }
 
 impl Server {
-    /// Returns the scheme + domain as a URL type.
+    /// Describes a server by its catalog URL alone, with the defaults of every other setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - URL of the OPDS catalog
+    ///
+    pub fn new(base_url: Url) -> Server {
+        Server {
+            username: None,
+            base_url,
+            user_agent: None,
+            proxy: None,
+            timeout_seconds: None,
+            accept_invalid_certs: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Returns the client requests to this server are sent with. Servers without a proxy or
+    /// timeout of their own use the shared client; the others get a dedicated one.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared` - Client used by every server without overrides.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the proxy is not a valid URL or the client cannot be built.
+    ///
+    pub fn client(&self, shared: &reqwest::Client) -> Result<reqwest::Client, Box<dyn StdError>> {
+        let accept_invalid_certs = self.accept_invalid_certs.unwrap_or(self.is_loopback());
+        if self.proxy.is_none() && self.timeout_seconds.is_none() && !accept_invalid_certs {
+            return Ok(shared.clone());
+        }
+
+        let mut builder = reqwest::Client::builder().user_agent("ncopds");
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|err| format!("Invalid proxy {}: {}", proxy, err))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(seconds) = self.timeout_seconds {
+            builder = builder.timeout(Duration::from_secs(seconds));
+        }
+        if accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Whether the server runs on this machine: its host is `localhost`, a name below it, or a
+    /// loopback address such as `127.0.0.1` or `[::1]`.
+    pub fn is_loopback(&self) -> bool {
+        match self.base_url.host() {
+            Some(Host::Domain(d)) => {
+                let d = d.to_ascii_lowercase();
+                d == "localhost" || d.ends_with(".localhost")
+            }
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        }
+    }
+
+    /// Returns the extra headers that are sent to this server. Headers whose name or value
+    /// cannot be sent over HTTP are left out; see `invalid_headers`.
+    pub fn header_map(&self) -> HeaderMap {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Describes each extra header that is left out of requests to this server, and why.
+    pub fn invalid_headers(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    Some(format!("{:?} is not a valid header name", name))
+                } else if HeaderValue::from_str(value).is_err() {
+                    Some(format!("the value of {} is not a valid header value", name))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        problems.sort();
+        problems
+    }
+
+    /// Returns the scheme, host and port as a URL type. IP addresses work as hosts as well;
+    /// IPv6 addresses keep their brackets.
     ///
     /// # Examples
     ///
     /// ```
-    /// "https://example.com/path/further/down" -> "https://example.com"
+    /// "https://example.com/path/further/down" -> "https://example.com/"
+    /// "http://[::1]:8080/opds" -> "http://[::1]:8080/"
     /// ```
     pub fn get_domain(&self) -> Url {
-        // test
-        Url::parse(&format!(
-            "{0}://{1}",
-            self.base_url.scheme(),
-            self.base_url.domain().unwrap()
-        ))
-        .unwrap()
+        // rebuilding the origin from its parts would have to bracket IPv6 hosts by hand
+        let mut domain = self.base_url.clone();
+        domain.set_path("");
+        domain.set_query(None);
+        domain.set_fragment(None);
+        let _ = domain.set_username("");
+        let _ = domain.set_password(None);
+        domain
     }
 
-    /// Retrieves the password for the username and server from the system's keychain. Servers
+    /// Retrieves the password for the username and server from the credential store. Servers
     /// without usernames do not have passwords associated with them.
     ///
+    /// # Arguments
+    ///
+    /// * `store` - Credential store to read from.
+    ///
     /// # Errors
     ///
-    /// Errors can get thrown if the password has not been stored in the keyring before.
+    /// Errors can get thrown if the password has not been stored before.
     ///
-    pub fn get_password(&self) -> Result<Option<String>, Error> {
+    pub fn get_password(&self, store: &dyn CredentialStore) -> Result<Option<String>, Error> {
         // test
         match &self.username {
             Some(u) => {
-                let entry = Entry::new("ncopds", &format!("{}@{}", &u, self.base_url)).unwrap();
-                let password = entry.get_password()?;
+                let password = store.get_password(&format!("{}@{}", &u, self.base_url))?;
 
                 if password.is_empty() {
                     return Ok(None);
@@ -79,3 +224,52 @@ impl fmt::Display for Server {
         write!(f, "URL: {}\n USER: {:?}\n", self.base_url, self.username)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::MemoryStore;
+
+    fn server(base_url: &str) -> Server {
+        Server::new(Url::parse(base_url).unwrap())
+    }
+
+    #[test]
+    fn servers_with_headers_survive_a_round_trip() {
+        let mut s = server("https://kavita.lan/api/opds");
+        s.accept_invalid_certs = Some(true);
+        s.headers.insert("X-Api-Key".to_string(), "abc".to_string());
+
+        let written = toml::to_string(&s).unwrap();
+        let read: Server = toml::from_str(&written).unwrap();
+
+        assert_eq!(read.base_url, s.base_url);
+        assert_eq!(read.accept_invalid_certs, Some(true));
+        assert_eq!(read.headers, s.headers);
+    }
+
+    #[test]
+    fn ipv6_hosts_keep_brackets_and_port() {
+        let s = server("http://user@[::1]:8080/opds?page=2#top");
+        assert_eq!(s.get_domain().as_str(), "http://[::1]:8080/");
+        assert!(s.is_loopback());
+        assert_eq!(
+            s.get_domain().join("covers/1.jpg").unwrap().as_str(),
+            "http://[::1]:8080/covers/1.jpg"
+        );
+    }
+
+    #[test]
+    fn deleted_passwords_are_gone() {
+        let mut store = MemoryStore::default();
+        let mut s = server("http://calibre.lan/opds");
+        s.username = Some("reader".to_string());
+        store_password(&mut store, &s, &Some("secret".to_string())).unwrap();
+        assert_eq!(s.get_password(&store).unwrap().as_deref(), Some("secret"));
+
+        delete_password(&mut store, &s).unwrap();
+        assert!(matches!(s.get_password(&store), Err(Error::NoEntry)));
+        // nothing left to delete is not an error
+        delete_password(&mut store, &s).unwrap();
+    }
+}
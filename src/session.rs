@@ -0,0 +1,79 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// How many past searches are kept for each connection.
+pub const SEARCH_HISTORY_LENGTH: usize = 20;
+
+/// State that is remembered between runs but is not configuration. Stored as JSON next to the
+/// config file.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Session {
+    /// recent search queries for each connection, most recent first
+    #[serde(default)]
+    pub search_history: HashMap<String, Vec<String>>,
+    /// connection that was open when ncopds was closed; switched back to once it has connected
+    #[serde(default)]
+    pub active_connection: Option<String>,
+    /// whether the details panel was hidden
+    #[serde(default)]
+    pub details_hidden: bool,
+    /// whether catalog pages were sorted by date added rather than shown in feed order
+    #[serde(default)]
+    pub sort_by_date: bool,
+}
+
+impl Session {
+    /// Reads the session file. A missing or unreadable file gives an empty session; losing the
+    /// session is never worth failing over.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the session file.
+    ///
+    pub fn read(path: &Path) -> Session {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the session file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the session file.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the file cannot be written.
+    ///
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds a query to the front of a connection's search history. Repeating the latest query
+    /// does not add it again, and the oldest queries are dropped past the history length.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - Name of the connection that was searched.
+    /// * `query` - What was searched for.
+    ///
+    pub fn remember_search(&mut self, connection: &str, query: &str) {
+        let history = self
+            .search_history
+            .entry(connection.to_string())
+            .or_default();
+
+        if history.first().is_some_and(|q| q == query) {
+            return;
+        }
+
+        history.insert(0, query.to_string());
+        history.truncate(SEARCH_HISTORY_LENGTH);
+    }
+}
@@ -0,0 +1,24 @@
+// Helpers shared by the tests of several modules.
+
+use crate::model::{EntryData, EntryType};
+use std::fs;
+use std::path::PathBuf;
+
+/// A directory of its own for a test, holding empty files with the given names.
+pub fn scratch_dir(name: &str, files: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ncopds-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for file in files {
+        fs::File::create(dir.join(file)).unwrap();
+    }
+    dir
+}
+
+/// Unwraps the data of a catalog entry, failing the test for any other kind of entry.
+pub fn entry_data(entry: EntryType) -> EntryData {
+    match entry {
+        EntryType::OPDSEntry(data) => *data,
+        other => panic!("expected an OPDS entry, got {:?}", other),
+    }
+}
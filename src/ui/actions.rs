@@ -0,0 +1,598 @@
+use crate::controller::Transfer;
+use crate::model::EntryType;
+use crate::ui;
+use crate::ui::dialogs::{entry_dialog, input_dialog, reader};
+use crate::ui::directory_view::{
+    copy_selected_path, delete_selected, mark_selected, open_selected, rename_selected,
+    reveal_selected, selected_entry, toggle_selection,
+};
+use crate::ui::uiroot::UserData;
+use crate::ControllerMessage;
+use cursive::event::{Event, Key};
+use cursive::view::{Nameable, Scrollable};
+use cursive::views::{Dialog, EditView, LinearLayout, ScrollView, SelectView, TextView};
+use cursive::{Cursive, Vec2};
+use std::sync::mpsc;
+use std::sync::Arc;
+
+/// Every action a user can trigger from the UI. Keybindings, the command palette and the help
+/// screen are all built from this list so they cannot drift apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Help,
+    CommandPalette,
+    Search,
+    GoToAddress,
+    PreviousSearchPage,
+    NextSearchPage,
+    ShowFacets,
+    GoBack,
+    GoHome,
+    GoUp,
+    GoStart,
+    Refresh,
+    ClearAllCaches,
+    ToggleAnonymous,
+    ShowDownloadDirectory,
+    AddConnection,
+    QuickOpen,
+    EditTheme,
+    QuickDownload,
+    DownloadAll,
+    ShowEntryDetails,
+    ReadEntry,
+    BrowseAuthor,
+    CopyDebugInfo,
+    OpenFile,
+    RevealFile,
+    CopyPath,
+    DeleteFile,
+    RenameFile,
+    CreateDirectory,
+    ToggleSelection,
+    Cut,
+    Copy,
+    Paste,
+    Undo,
+    DismissNotifications,
+    ToggleDetails,
+    SortByDate,
+    ToggleDebugConsole,
+    CycleLogLevel,
+    ShowRawPage,
+    RecentDownloads,
+    Quit,
+}
+
+/// Where a keybinding for an action is active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// anywhere in the application
+    Global,
+    /// only while the file view has focus
+    FileView,
+}
+
+/// Pairs of keys and the actions they trigger. Shared between the views that need to register
+/// or display them.
+pub type Keybindings = Arc<Vec<(Event, Action)>>;
+
+impl Action {
+    pub const ALL: [Action; 43] = [
+        Action::Help,
+        Action::CommandPalette,
+        Action::Search,
+        Action::GoToAddress,
+        Action::PreviousSearchPage,
+        Action::NextSearchPage,
+        Action::ShowFacets,
+        Action::GoBack,
+        Action::GoHome,
+        Action::GoUp,
+        Action::GoStart,
+        Action::Refresh,
+        Action::ClearAllCaches,
+        Action::ToggleAnonymous,
+        Action::ShowDownloadDirectory,
+        Action::AddConnection,
+        Action::QuickOpen,
+        Action::EditTheme,
+        Action::QuickDownload,
+        Action::DownloadAll,
+        Action::ShowEntryDetails,
+        Action::ReadEntry,
+        Action::BrowseAuthor,
+        Action::CopyDebugInfo,
+        Action::OpenFile,
+        Action::RevealFile,
+        Action::CopyPath,
+        Action::DeleteFile,
+        Action::RenameFile,
+        Action::CreateDirectory,
+        Action::ToggleSelection,
+        Action::Cut,
+        Action::Copy,
+        Action::Paste,
+        Action::Undo,
+        Action::DismissNotifications,
+        Action::ToggleDetails,
+        Action::SortByDate,
+        Action::ToggleDebugConsole,
+        Action::CycleLogLevel,
+        Action::ShowRawPage,
+        Action::RecentDownloads,
+        Action::Quit,
+    ];
+
+    /// Short human readable description of the action.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Help => "Open help screen",
+            Action::CommandPalette => "Open command palette",
+            Action::Search => "Search catalog or directory",
+            Action::GoToAddress => "Type the address of a page to go to",
+            Action::PreviousSearchPage => "Previous page of search results",
+            Action::NextSearchPage => "Next page of search results",
+            Action::ShowFacets => "Narrow the page or search results by a facet",
+            Action::GoBack => "Go back / close popup",
+            Action::GoHome => "Go to the root of the connection",
+            Action::GoUp => "Go to the feed above this one, or back",
+            Action::GoStart => "Go to the catalog's start feed, or its root",
+            Action::Refresh => "Reload current page",
+            Action::ClearAllCaches => "Forget every cached page",
+            Action::ToggleAnonymous => "Browse the catalog with / without credentials",
+            Action::ShowDownloadDirectory => "Show download directory",
+            Action::AddConnection => "Add connection",
+            Action::QuickOpen => "Browse a catalog URL for this session only",
+            Action::EditTheme => "Edit the theme file",
+            Action::QuickDownload => "Download in preferred format",
+            Action::DownloadAll => "Download everything on this page",
+            Action::ShowEntryDetails => "Show all details of the selected book",
+            Action::ReadEntry => "Read the selected entry's text, or download it",
+            Action::BrowseAuthor => "Show more books by the author",
+            Action::CopyDebugInfo => {
+                "Copy the entry's links and how they were read, for bug reports"
+            }
+            Action::OpenFile => "Open file (local view)",
+            Action::RevealFile => "Show file in file manager (local view)",
+            Action::CopyPath => "Copy the file's path to the clipboard (local view)",
+            Action::DeleteFile => "Delete file or selected files (local view)",
+            Action::RenameFile => "Rename file (local view)",
+            Action::CreateDirectory => "Create folder (local view)",
+            Action::ToggleSelection => "Select / unselect file (local view)",
+            Action::Cut => "Mark file or selected files to be moved (local view)",
+            Action::Copy => "Mark file or selected files to be copied (local view)",
+            Action::Paste => "Paste marked files here (local view)",
+            Action::Undo => "Undo last delete, rename or move (local view)",
+            Action::DismissNotifications => "Dismiss notifications",
+            Action::ToggleDetails => "Hide / show the details panel",
+            Action::SortByDate => "Sort books by date added / feed order",
+            Action::ToggleDebugConsole => "Toggle debug console",
+            Action::CycleLogLevel => "Log more (up to trace), then start again at errors",
+            Action::ShowRawPage => "Show what the server sent for this page",
+            Action::RecentDownloads => "Show / hide the downloads that finished",
+            Action::Quit => "Quit",
+        }
+    }
+
+    pub fn scope(&self) -> Scope {
+        match self {
+            Action::QuickDownload
+            | Action::DownloadAll
+            | Action::ShowEntryDetails
+            | Action::ReadEntry
+            | Action::BrowseAuthor
+            | Action::CopyDebugInfo
+            | Action::OpenFile
+            | Action::RevealFile
+            | Action::CopyPath
+            | Action::DeleteFile
+            | Action::RenameFile
+            | Action::CreateDirectory
+            | Action::ToggleSelection
+            | Action::Cut
+            | Action::Copy
+            | Action::Paste
+            | Action::Undo => Scope::FileView,
+            _ => Scope::Global,
+        }
+    }
+}
+
+/// The keybindings ncopds ships with.
+pub fn default_keybindings() -> Keybindings {
+    Arc::new(vec![
+        (Event::Char('?'), Action::Help),
+        (Event::Char(':'), Action::CommandPalette),
+        (Event::CtrlChar('p'), Action::CommandPalette),
+        (Event::Char('/'), Action::Search),
+        (Event::CtrlChar('l'), Action::GoToAddress),
+        (Event::Char('['), Action::PreviousSearchPage),
+        (Event::Char(']'), Action::NextSearchPage),
+        (Event::Char('F'), Action::ShowFacets),
+        (Event::Key(Key::Backspace), Action::GoBack),
+        (Event::Char('H'), Action::GoHome),
+        (Event::Char('U'), Action::GoUp),
+        (Event::Char('S'), Action::GoStart),
+        (Event::Key(Key::F5), Action::Refresh),
+        (Event::Char('0'), Action::ShowDownloadDirectory),
+        (Event::Shift(Key::F5), Action::ClearAllCaches),
+        (Event::Char('A'), Action::ToggleAnonymous),
+        (Event::Char('O'), Action::QuickOpen),
+        (Event::Char('T'), Action::EditTheme),
+        (Event::Char('g'), Action::QuickDownload),
+        (Event::Char('D'), Action::DownloadAll),
+        (Event::Char('v'), Action::ShowEntryDetails),
+        (Event::Char('R'), Action::ReadEntry),
+        (Event::Char('a'), Action::BrowseAuthor),
+        (Event::Char('I'), Action::CopyDebugInfo),
+        (Event::Char('o'), Action::OpenFile),
+        (Event::Char('f'), Action::RevealFile),
+        (Event::Char('y'), Action::CopyPath),
+        (Event::Char('d'), Action::DeleteFile),
+        (Event::Char('r'), Action::RenameFile),
+        (Event::Char('n'), Action::CreateDirectory),
+        (Event::Char(' '), Action::ToggleSelection),
+        (Event::Char('x'), Action::Cut),
+        (Event::Char('c'), Action::Copy),
+        (Event::Char('p'), Action::Paste),
+        (Event::Char('u'), Action::Undo),
+        (Event::Key(Key::Esc), Action::DismissNotifications),
+        (Event::Char('i'), Action::ToggleDetails),
+        (Event::Char('s'), Action::SortByDate),
+        (Event::Char('~'), Action::ToggleDebugConsole),
+        (Event::Char('L'), Action::CycleLogLevel),
+        (Event::Char('X'), Action::ShowRawPage),
+        (Event::Char('w'), Action::RecentDownloads),
+        (Event::Char('q'), Action::Quit),
+    ])
+}
+
+/// Returns a readable label for a key event, i.e. "Ctrl+P" or "Backspace".
+///
+/// # Arguments
+///
+/// * `event` - Event to describe.
+///
+pub fn key_label(event: &Event) -> String {
+    match event {
+        Event::Char(' ') => "Space".to_string(),
+        Event::Char(c) => c.to_string(),
+        Event::CtrlChar(c) => format!("Ctrl+{}", c.to_ascii_uppercase()),
+        Event::AltChar(c) => format!("Alt+{}", c),
+        Event::Key(k) => format!("{:?}", k),
+        Event::Shift(k) => format!("Shift+{:?}", k),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Returns every key bound to the action joined into one string. Unbound actions return an
+/// empty string.
+///
+/// # Arguments
+///
+/// * `bindings` - Keybindings to search.
+/// * `action` - Action to look up.
+///
+pub fn keys_for(bindings: &Keybindings, action: Action) -> String {
+    bindings
+        .iter()
+        .filter(|(_, a)| *a == action)
+        .map(|(e, _)| key_label(e))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Runs an action.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `action` - Action to run.
+/// * `ctx` - Controller message channel.
+/// * `bindings` - Current keybindings, needed by the screens that list actions.
+///
+pub fn perform(
+    s: &mut Cursive,
+    action: Action,
+    ctx: &mpsc::Sender<ControllerMessage>,
+    bindings: &Keybindings,
+) {
+    match action {
+        Action::Help => s.add_layer(ui::uiroot::about_screen(bindings)),
+        Action::CommandPalette => s.add_layer(command_palette(ctx.clone(), bindings.clone())),
+        Action::Search => {
+            // the controller knows the search history, so it opens the dialog
+            ctx.send(ControllerMessage::ShowSearch())
+                .expect("failed to send controller message");
+        }
+        Action::GoToAddress => {
+            // the controller knows where the connection is, so it opens the dialog
+            ctx.send(ControllerMessage::ShowAddressBar())
+                .expect("failed to send controller message");
+        }
+        Action::PreviousSearchPage => {
+            ctx.send(ControllerMessage::SearchPage(false))
+                .expect("failed to send controller message");
+        }
+        Action::NextSearchPage => {
+            ctx.send(ControllerMessage::SearchPage(true))
+                .expect("failed to send controller message");
+        }
+        Action::ShowFacets => {
+            ctx.send(ControllerMessage::ShowFacets())
+                .expect("failed to send controller message");
+        }
+        Action::GoBack => {
+            // check if popup is open first
+            if s.find_name::<SelectView<ControllerMessage>>("popup")
+                .is_some()
+                || s.find_name::<ScrollView<TextView>>("reader").is_some()
+            {
+                s.pop_layer();
+            } else {
+                ctx.send(ControllerMessage::GoBack()).unwrap();
+            }
+        }
+        Action::GoHome => {
+            ctx.send(ControllerMessage::GoHome())
+                .expect("failed to send controller message");
+        }
+        Action::GoUp => {
+            ctx.send(ControllerMessage::GoUp())
+                .expect("failed to send controller message");
+        }
+        Action::GoStart => {
+            ctx.send(ControllerMessage::GoStart())
+                .expect("failed to send controller message");
+        }
+        Action::EditTheme => {
+            ctx.send(ControllerMessage::EditTheme())
+                .expect("failed to send controller message");
+        }
+        Action::Refresh => {
+            ctx.send(ControllerMessage::Refresh())
+                .expect("failed to send controller message");
+        }
+        Action::ClearAllCaches => {
+            ctx.send(ControllerMessage::ClearAllCaches())
+                .expect("failed to send controller message");
+        }
+        Action::ToggleAnonymous => {
+            ctx.send(ControllerMessage::ToggleAnonymous())
+                .expect("failed to send controller message");
+        }
+        Action::ShowDownloadDirectory => {
+            ctx.send(ControllerMessage::ChangeConnection("local".to_string()))
+                .expect("local connection disappeared");
+        }
+        Action::AddConnection => {
+            let diag = ui::serverinfomodal::new(ctx.clone(), None);
+            s.add_layer(diag);
+        }
+        Action::QuickOpen => {
+            let open_ctx = ctx.clone();
+            let d = input_dialog(
+                "Catalog URL (not saved)",
+                move |address| {
+                    open_ctx
+                        .send(ControllerMessage::QuickOpen(address))
+                        .expect("failed to send controller message");
+                },
+                false,
+            );
+            s.add_layer(d);
+        }
+        Action::DownloadAll => {
+            ctx.send(ControllerMessage::ConfirmDownloadAll())
+                .expect("failed to send controller message");
+        }
+        Action::QuickDownload => {
+            if let Some(item) = selected_entry(s) {
+                ctx.send(ControllerMessage::QuickDownload(item.as_ref().clone()))
+                    .expect("failed to send controller message");
+            }
+        }
+        Action::ShowEntryDetails => {
+            let Some(item) = selected_entry(s) else {
+                return;
+            };
+            let EntryType::OPDSEntry(data) = item.as_ref() else {
+                return;
+            };
+
+            // covers get a third of the screen; the rest is left for the text
+            let screen = s.screen_size();
+            let cover_size = Vec2::new(screen.x / 3, screen.y / 2);
+            let user_data: &mut UserData = s.user_data().unwrap();
+            let d = entry_dialog(
+                ctx.clone(),
+                data,
+                user_data.images.get(&data.title),
+                cover_size,
+            );
+            s.add_layer(d);
+        }
+        Action::ReadEntry => {
+            let Some(item) = selected_entry(s) else {
+                return;
+            };
+            match item.as_ref() {
+                EntryType::OPDSEntry(data) if data.content.is_some() => {
+                    let text = data.content.as_deref().unwrap_or_default();
+                    s.add_layer(reader(&data.title, text));
+                }
+                // nothing to read in place; the entry's book is the next best thing
+                entry => ctx
+                    .send(ControllerMessage::QuickDownload(entry.clone()))
+                    .expect("failed to send controller message"),
+            }
+        }
+        Action::BrowseAuthor => {
+            if let Some(item) = selected_entry(s) {
+                ctx.send(ControllerMessage::BrowseAuthor(item.as_ref().clone()))
+                    .expect("failed to send controller message");
+            }
+        }
+        Action::CopyDebugInfo => {
+            if let Some(EntryType::OPDSEntry(data)) = selected_entry(s).as_deref() {
+                ctx.send(ControllerMessage::CopyDebugInfo(data.clone()))
+                    .expect("failed to send controller message");
+            }
+        }
+        Action::OpenFile => open_selected(s, ctx),
+        Action::RevealFile => reveal_selected(s, ctx),
+        Action::CopyPath => copy_selected_path(s, ctx),
+        Action::DeleteFile => delete_selected(s, ctx),
+        Action::RenameFile => rename_selected(s, ctx),
+        Action::ToggleSelection => toggle_selection(s, ctx),
+        Action::Cut => mark_selected(s, ctx, Transfer::Move),
+        Action::Copy => mark_selected(s, ctx, Transfer::Copy),
+        Action::Paste => {
+            ctx.send(ControllerMessage::Paste())
+                .expect("failed to send controller message");
+        }
+        Action::Undo => {
+            ctx.send(ControllerMessage::Undo())
+                .expect("failed to send controller message");
+        }
+        Action::CreateDirectory => {
+            let dir_ctx = ctx.clone();
+            let d = input_dialog(
+                "New folder name",
+                move |name| {
+                    dir_ctx
+                        .send(ControllerMessage::CreateDirectory(name.into()))
+                        .expect("failed to send controller message");
+                },
+                false,
+            );
+            s.add_layer(d);
+        }
+        Action::DismissNotifications => {
+            // notifications belong to the UI loop, which the controller relays this to
+            ctx.send(ControllerMessage::DismissNotifications())
+                .expect("failed to send controller message");
+        }
+        Action::ToggleDetails => {
+            // the layout belongs to the UI loop as well
+            ctx.send(ControllerMessage::ToggleDetails())
+                .expect("failed to send controller message");
+        }
+        Action::SortByDate => {
+            ctx.send(ControllerMessage::ToggleSortByDate())
+                .expect("failed to send controller message");
+        }
+        Action::ToggleDebugConsole => s.toggle_debug_console(),
+        Action::CycleLogLevel => {
+            ctx.send(ControllerMessage::CycleLogLevel())
+                .expect("failed to send controller message");
+        }
+        Action::ShowRawPage => {
+            ctx.send(ControllerMessage::ShowRawPage())
+                .expect("failed to send controller message");
+        }
+        Action::RecentDownloads => {
+            // the controller keeps the list, so it opens the dialog
+            ctx.send(ControllerMessage::ShowRecentDownloads())
+                .expect("failed to send controller message");
+        }
+        Action::Quit => {
+            // running downloads have to be stopped first
+            ctx.send(ControllerMessage::Quit(false))
+                .expect("failed to send controller message");
+        }
+    }
+}
+
+/// Registers the global keybindings on the cursive instance. Bindings scoped to other views are
+/// registered by those views.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel.
+/// * `bindings` - Keybindings to register.
+///
+pub fn register_global_callbacks(
+    s: &mut Cursive,
+    ctx: &mpsc::Sender<ControllerMessage>,
+    bindings: &Keybindings,
+) {
+    for (event, action) in bindings.iter() {
+        if action.scope() != Scope::Global {
+            continue;
+        }
+
+        let action = *action;
+        let a_ctx = ctx.clone();
+        let a_bindings = bindings.clone();
+        s.add_global_callback(event.clone(), move |s| {
+            perform(s, action, &a_ctx, &a_bindings)
+        });
+    }
+}
+
+/// Builds a searchable list of every action along with the keys bound to it. Submitting an entry
+/// closes the palette and runs the action.
+///
+/// # Arguments
+///
+/// * `ctx` - Controller message channel.
+/// * `bindings` - Current keybindings.
+///
+pub fn command_palette(ctx: mpsc::Sender<ControllerMessage>, bindings: Keybindings) -> Dialog {
+    let submit_ctx = ctx.clone();
+    let submit_bindings = bindings.clone();
+    let filter_bindings = bindings.clone();
+    let first_bindings = bindings.clone();
+
+    let mut select = SelectView::<Action>::new().on_submit(move |s, action: &Action| {
+        s.pop_layer();
+        perform(s, *action, &submit_ctx, &submit_bindings);
+    });
+    populate_palette(&mut select, &bindings, "");
+
+    let filter = EditView::new()
+        .on_edit(move |s, query, _| {
+            s.call_on_name("palette_list", |select: &mut SelectView<Action>| {
+                populate_palette(select, &filter_bindings, query);
+            });
+        })
+        .on_submit(move |s, _| {
+            // pressing enter inside the filter runs the first match
+            let first = s
+                .call_on_name("palette_list", |select: &mut SelectView<Action>| {
+                    select.get_item(0).map(|(_, action)| *action)
+                })
+                .flatten();
+
+            if let Some(action) = first {
+                s.pop_layer();
+                perform(s, action, &ctx, &first_bindings);
+            }
+        });
+
+    Dialog::around(
+        LinearLayout::vertical()
+            .child(filter)
+            .child(select.with_name("palette_list").scrollable()),
+    )
+    .title("Commands")
+    .dismiss_button("Close")
+}
+
+/// Fills the palette with every action whose description or keys contain the query.
+fn populate_palette(select: &mut SelectView<Action>, bindings: &Keybindings, query: &str) {
+    let query = query.to_lowercase();
+    select.clear();
+
+    for action in Action::ALL {
+        let keys = keys_for(bindings, action);
+        let label = format!("{:<32}{}", action.description(), keys);
+
+        if label.to_lowercase().contains(&query) {
+            select.add_item(label, action);
+        }
+    }
+}
@@ -1,15 +1,17 @@
+use image::imageops::FilterType;
 use image::DynamicImage;
+use std::sync::{Arc, Mutex};
 
 use cursive::direction::Direction;
 use cursive::event::EventResult;
-use cursive::theme::{Color, ColorStyle};
+use cursive::theme::{Color, ColorStyle, Effect, Style};
 use cursive::view::CannotFocus;
 use cursive::Printer;
 use cursive::Vec2;
 
 /// stolen from https://github.com/lennart-finke/kakikun/blob/main/src/canvas.rs
 /// Renders dynamic images inside a CanvasView
-
+///
 /// In memory representation of the content of the image
 pub struct Board {
     pub size: Vec2,
@@ -41,9 +43,41 @@ pub struct Cell {
     pub symbol: char,
 }
 
+/// Where on screen a canvas drawn with sixel graphics left room for its image. Only part of the
+/// canvas is visible when the panel it is in is scrolled or too narrow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SixelArea {
+    /// screen position of the first visible cell
+    pub pos: Vec2,
+    /// size of the whole canvas, in cells
+    pub board: Vec2,
+    /// first visible cell, counted from the top left of the canvas
+    pub from: Vec2,
+    /// number of visible cells
+    pub size: Vec2,
+}
+
+/// What a canvas drawn with sixel graphics shares with the code that writes the image to the
+/// terminal, which can only happen once cursive has put the frame on screen.
+#[derive(Default)]
+pub struct SixelSlot {
+    /// area reserved for the image in the last frame; None if the canvas was not drawn
+    pub area: Option<SixelArea>,
+    /// image the canvas shows
+    pub image: Option<DynamicImage>,
+    /// changes whenever the image or its size does
+    pub version: u64,
+}
+
 pub struct CanvasView {
     board: Board,
     overlay: Vec<Cell>,
+    /// largest board, in cells, that images are scaled down to fit
+    max_size: Vec2,
+    /// image currently shown, kept so it can be drawn again at a different size
+    image: Option<DynamicImage>,
+    /// set when the image is drawn with sixel graphics instead of colored cells
+    sixel: Option<Arc<Mutex<SixelSlot>>>,
 }
 
 impl CanvasView {
@@ -58,7 +92,39 @@ impl CanvasView {
         ];
         let board = Board::new(size);
 
-        CanvasView { board, overlay }
+        CanvasView {
+            board,
+            overlay,
+            max_size: size,
+            image: None,
+            sixel: None,
+        }
+    }
+
+    /// Makes the canvas leave its area blank for a sixel image, which is written to the terminal
+    /// by whoever holds the other end of the slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - Where the canvas reports its image and position.
+    ///
+    pub fn with_sixel(mut self, slot: Arc<Mutex<SixelSlot>>) -> Self {
+        self.sixel = Some(slot);
+        self
+    }
+
+    /// Sets the largest board, in cells, that images are scaled to fit. Never smaller than a
+    /// single cell. The current image is drawn again if the size changed.
+    pub fn set_max_size(&mut self, size: Vec2) {
+        let size = size.map(|v| v.max(1));
+        if size == self.max_size {
+            return;
+        }
+
+        self.max_size = size;
+        if let Some(img) = self.image.take() {
+            self.from_image(&img);
+        }
     }
 
     /// Sets the canvas to all white pixels.
@@ -77,13 +143,13 @@ impl CanvasView {
     pub fn from_image(&mut self, img: &DynamicImage) {
         let mut overlay_new: Vec<Cell>;
 
-        // don't like these hardcoded values...
-        let rgbimg = DynamicImage::ImageRgb8(img.clone().into_rgb8())
-            .thumbnail(50, 50)
+        // one pixel per cell; the board size already accounts for the shape of the cells
+        let size = board_size(img.width(), img.height(), self.max_size);
+        let rgbimg = img
+            .resize_exact(size.x as u32, size.y as u32, FilterType::Triangle)
             .into_rgb8();
 
-        let (img_w, img_h) = rgbimg.dimensions() as (u32, u32);
-        self.board = Board::new(Vec2::new(img_w as usize, (img_h / 2) as usize));
+        self.board = Board::new(size);
 
         self.clear(); //For quickly resizing the overlay
 
@@ -100,17 +166,73 @@ impl CanvasView {
             let x = (i % self.board.size.x) as u32;
             let y = (i / self.board.size.x) as u32;
 
-            // Only every second line is parsed into the canvas to conserve image aspect ratio.
-            let rgb = rgbimg.get_pixel(x, y * 2);
+            let rgb = rgbimg.get_pixel(x, y);
             overlay_new[i].backcolor = Color::Rgb(rgb[0], rgb[1], rgb[2]);
         }
 
         self.overlay = overlay_new;
+        self.image = Some(img.clone());
+
+        if let Some(slot) = &self.sixel {
+            let mut slot = slot.lock().unwrap();
+            slot.image = Some(img.clone());
+            slot.version += 1;
+        }
     }
 }
 
+/// Works out the size, in cells, an image is drawn at so that it fits into `max` and keeps its
+/// aspect ratio. Terminal cells are about twice as tall as they are wide, so an image needs half
+/// as many rows as it would need columns.
+///
+/// # Arguments
+///
+/// * `width` - Width of the image in pixels.
+/// * `height` - Height of the image in pixels.
+/// * `max` - Largest allowed size in cells.
+///
+pub fn board_size(width: u32, height: u32, max: Vec2) -> Vec2 {
+    if width == 0 || height == 0 {
+        return Vec2::new(1, 1);
+    }
+
+    // columns per row the image takes up on screen
+    let ratio = width as f64 / (height as f64 / 2.0);
+
+    let mut w = max.x as f64;
+    let mut h = w / ratio;
+    if h > max.y as f64 {
+        h = max.y as f64;
+        w = h * ratio;
+    }
+
+    Vec2::new((w.round() as usize).max(1), (h.round() as usize).max(1))
+}
+
 impl cursive::view::View for CanvasView {
     fn draw(&self, printer: &Printer) {
+        if let Some(slot) = &self.sixel {
+            // cursive only rewrites cells that changed, so the reserved cells need a style
+            // nothing else uses; otherwise, cells that look the same once the image is gone are
+            // not rewritten and leave parts of it on screen
+            let reserved = Style::from(ColorStyle::terminal_default()).combine(Effect::Italic);
+            printer.with_style(reserved, |printer| {
+                for y in 0..self.board.size.y {
+                    printer.print_hline((0, y), self.board.size.x, " ");
+                }
+            });
+
+            let from = printer.content_offset.zip_map(self.board.size, usize::min);
+            let size = (self.board.size - from).zip_map(printer.output_size, usize::min);
+            slot.lock().unwrap().area = (size.x > 0 && size.y > 0).then_some(SixelArea {
+                pos: printer.offset,
+                board: self.board.size,
+                from,
+                size,
+            });
+            return;
+        }
+
         for (i, cell) in self.overlay.iter().enumerate() {
             let x = i % self.board.size.x;
             let y = i / self.board.size.x;
@@ -133,3 +255,27 @@ impl cursive::view::View for CanvasView {
         self.board.size.map_x(|x| x)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn covers_keep_their_aspect_ratio() {
+        // a 2:3 cover takes 4:3 cells, which are twice as tall as they are wide
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 300, Rgb([200, 10, 10])));
+        let mut canvas = CanvasView::new(Vec2::new(40, 40));
+        canvas.from_image(&img);
+
+        assert_eq!(canvas.board.size, Vec2::new(40, 30));
+        assert_eq!(canvas.overlay.len(), 40 * 30);
+        assert_eq!(canvas.overlay[0].backcolor, Color::Rgb(200, 10, 10));
+    }
+
+    #[test]
+    fn tall_images_are_limited_by_height() {
+        assert_eq!(board_size(100, 1000, Vec2::new(40, 20)), Vec2::new(4, 20));
+        assert_eq!(board_size(0, 10, Vec2::new(40, 20)), Vec2::new(1, 1));
+    }
+}
@@ -1,26 +1,36 @@
-use cursive::view::Nameable;
-use cursive::views::{Dialog, EditView, LinearLayout, Panel, TextContent, TextView};
-use cursive::{Cursive, CursiveRunner, XY};
+use crate::model::{EntryData, FinishedDownload};
+use crate::ui::canvas::CanvasView;
+use crate::utils::{human_readable_size, parse_href};
+use crate::ControllerMessage;
+use cursive::theme::Effect;
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{
+    Dialog, DummyView, EditView, LinearLayout, Panel, ResizedView, SelectView, TextContent,
+    TextView,
+};
+use cursive::{Cursive, CursiveRunner, Vec2, XY};
+use image::DynamicImage;
 use rand::distributions::{Alphanumeric, DistString};
+use std::sync::mpsc;
+use url::Url;
 
-/// Shows a small panel at the bottom right of the screen containing information. Useful for
-/// letting the user know something is happening without interrupting their workflow. The panel
-/// does not capture any actions, letting the UI continue to work without interruptions.
+/// Shows a small panel containing information. Useful for letting the user know something is
+/// happening without interrupting their workflow. The panel does not capture any actions, letting
+/// the UI continue to work without interruptions. Returns the name of the panel and its size so
+/// the caller can position it.
 ///
 /// # Arguments
 ///
 /// * `siv` - Cursive instance.
 /// * `title` - Title for the panel
 /// * `content` - Content inside the panel.
-/// * `screen_size` - Size of the screen (needed for positioning the notification)
 ///
-
 pub fn notification(
     siv: &mut CursiveRunner<Cursive>,
     title: &str,
     content: &str,
-    screen_size: &XY<usize>,
-) -> String {
+) -> (String, XY<usize>) {
     let uuid = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
 
     let notif = Panel::new(TextView::new_with_content(TextContent::new(content)))
@@ -33,12 +43,17 @@ pub fn notification(
     let front = cursive::views::LayerPosition::FromFront(0);
     siv.screen_mut().set_modal(front, false);
 
-    // moves notification to bottom right corner of the screen
-    siv.screen_mut().reposition_layer(
-        front,
-        cursive::view::Position::absolute((screen_size.x - content.len(), screen_size.y)),
-    );
-    uuid
+    // the panel's borders add two columns and two rows; the title sits in the top border
+    let width = content
+        .lines()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(title.chars().count() + 4)
+        + 2;
+    let height = content.lines().count().max(1) + 2;
+
+    (uuid, XY::new(width, height))
 }
 
 /// Shortcut to write a dialog that asks for text input.
@@ -80,3 +95,343 @@ pub fn input_dialog<F: Fn(String) + std::marker::Sync + std::marker::Send + 'sta
 
     dialog
 }
+
+/// Dialog that asks for a login and a password at once.
+///
+/// # Arguments
+///
+/// * `title` - Title for the dialog.
+/// * `login_label` - Label shown above the login field.
+/// * `password_label` - Label shown above the password field.
+/// * `on_submit` - Function to run once submitted; receives the login and the password.
+///
+pub fn credentials_dialog<
+    F: Fn(String, String) + std::marker::Sync + std::marker::Send + 'static,
+>(
+    title: &str,
+    login_label: &str,
+    password_label: &str,
+    on_submit: F,
+) -> Dialog {
+    let mut dialog = Dialog::around(
+        LinearLayout::new(cursive::direction::Orientation::Vertical)
+            .child(TextView::new_with_content(TextContent::new(login_label)))
+            .child(EditView::new().with_name("login"))
+            .child(TextView::new_with_content(TextContent::new(password_label)))
+            .child(EditView::new().secret().with_name("secret")),
+    )
+    .title(title);
+
+    dialog.add_button("Submit", move |siv| {
+        let login = siv
+            .find_name::<EditView>("login")
+            .expect("edit view disappeared")
+            .get_content()
+            .to_string();
+        let password = siv
+            .find_name::<EditView>("secret")
+            .expect("edit view disappeared")
+            .get_content()
+            .to_string();
+
+        on_submit(login, password);
+        siv.pop_layer();
+    });
+
+    dialog.add_button("Cancel", |siv| {
+        siv.pop_layer();
+    });
+
+    dialog
+}
+
+/// Dialog that asks for a search query. Recent searches are listed below the input; selecting
+/// one searches for it again.
+///
+/// # Arguments
+///
+/// * `ctx` - Controller message channel.
+/// * `history` - Recent queries, most recent first.
+///
+pub fn search_dialog(ctx: mpsc::Sender<ControllerMessage>, history: Vec<String>) -> Dialog {
+    let input_ctx = ctx.clone();
+    let button_ctx = ctx.clone();
+
+    let input = EditView::new()
+        .on_submit(move |siv, query| {
+            siv.pop_layer();
+            input_ctx
+                .send(ControllerMessage::Search(query.to_string()))
+                .expect("Failed to search server.");
+        })
+        .with_name("search_input");
+
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new("Search"))
+        .child(input);
+
+    if !history.is_empty() {
+        let recent = SelectView::<String>::new().with_all_str(history).on_submit(
+            move |siv, query: &String| {
+                siv.pop_layer();
+                ctx.send(ControllerMessage::Search(query.clone()))
+                    .expect("Failed to search server.");
+            },
+        );
+
+        layout = layout
+            .child(TextView::new("\nRecent searches"))
+            .child(recent.scrollable().max_height(10));
+    }
+
+    let mut dialog = Dialog::around(layout);
+
+    dialog.add_button("Submit", move |siv| {
+        let query = siv
+            .find_name::<EditView>("search_input")
+            .expect("edit view disappeared")
+            .get_content()
+            .to_string();
+
+        siv.pop_layer();
+        button_ctx
+            .send(ControllerMessage::Search(query))
+            .expect("Failed to search server.");
+    });
+
+    dialog.add_button("Cancel", |siv| {
+        siv.pop_layer();
+    });
+
+    dialog
+}
+
+/// Asks for the address of a page to go to in the active connection, filled in with the address
+/// it is on. Relative addresses are resolved against that one. Addresses that cannot be parsed,
+/// or that the connection cannot open, are pointed out below the field instead of being visited.
+///
+/// # Arguments
+///
+/// * `ctx` - Controller message channel.
+/// * `current` - Address the connection is on.
+///
+pub fn address_dialog(ctx: mpsc::Sender<ControllerMessage>, current: Url) -> Dialog {
+    let local = current.scheme() == "file";
+    let content = current.to_string();
+
+    let go = move |siv: &mut Cursive| {
+        let address = siv
+            .find_name::<EditView>("address_input")
+            .expect("edit view disappeared")
+            .get_content();
+
+        let problem = match parse_href(address.trim(), &current) {
+            Ok(url) if (url.scheme() == "file") == local => {
+                siv.pop_layer();
+                ctx.send(ControllerMessage::Navigate(url))
+                    .expect("failed to send controller message");
+                return;
+            }
+            Ok(url) if local => format!("{} is not on this computer.", url),
+            Ok(url) => format!("{} cannot be opened in a catalog.", url),
+            Err(err) => format!("Not a valid address: {}.", err),
+        };
+        if let Some(mut view) = siv.find_name::<TextView>("address_error") {
+            view.set_content(problem);
+        }
+    };
+    let on_enter = go.clone();
+    let input = EditView::new()
+        .content(content)
+        .on_submit(move |siv, _| on_enter(siv))
+        .with_name("address_input")
+        .min_width(60);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Go to"))
+        .child(input)
+        .child(TextView::new("").with_name("address_error"));
+
+    Dialog::around(layout)
+        .button("Go", go)
+        .dismiss_button("Cancel")
+}
+
+/// Lists the downloads that finished since ncopds was started. Selecting one opens it; the
+/// buttons open it or show it in the file manager. Filled in by [`fill_recent_downloads`].
+///
+/// # Arguments
+///
+/// * `ctx` - Controller message channel.
+///
+pub fn downloads_dialog(ctx: mpsc::Sender<ControllerMessage>) -> Dialog {
+    // downloads that failed have nothing to open
+    fn saved(d: &FinishedDownload) -> Option<Url> {
+        d.path.as_ref().and_then(|p| Url::from_file_path(p).ok())
+    }
+    fn selected(siv: &mut Cursive) -> Option<Url> {
+        siv.find_name::<SelectView<FinishedDownload>>("recent_downloads")
+            .and_then(|s| s.selection())
+            .and_then(|d| saved(&d))
+    }
+
+    let open_ctx = ctx.clone();
+    let select = SelectView::<FinishedDownload>::new().on_submit(move |_, d| {
+        if let Some(url) = saved(d) {
+            open_ctx
+                .send(ControllerMessage::Open(url))
+                .expect("failed to send controller message");
+        }
+    });
+
+    let button_ctx = ctx.clone();
+
+    Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new("").with_name("recent_downloads_hint"))
+            .child(select.with_name("recent_downloads").scrollable()),
+    )
+    .title("Recent downloads")
+    .button("Open", move |siv| {
+        if let Some(url) = selected(siv) {
+            button_ctx
+                .send(ControllerMessage::Open(url))
+                .expect("failed to send controller message");
+        }
+    })
+    .button("Show in file manager", move |siv| {
+        if let Some(url) = selected(siv) {
+            ctx.send(ControllerMessage::Reveal(url))
+                .expect("failed to send controller message");
+        }
+    })
+    .dismiss_button("Close")
+}
+
+/// Puts the downloads into the list opened by [`downloads_dialog`], keeping the selection on the
+/// same line.
+///
+/// # Arguments
+///
+/// * `siv` - Cursive instance the list is shown in.
+/// * `downloads` - Downloads to list, most recent first.
+///
+pub fn fill_recent_downloads(siv: &mut Cursive, downloads: Vec<FinishedDownload>) {
+    if let Some(mut hint) = siv.find_name::<TextView>("recent_downloads_hint") {
+        hint.set_content(if downloads.is_empty() {
+            "Nothing has been downloaded yet."
+        } else {
+            ""
+        });
+    }
+
+    let Some(mut select) = siv.find_name::<SelectView<FinishedDownload>>("recent_downloads") else {
+        return;
+    };
+    let selected = select.selected_id().unwrap_or(0);
+    select.clear();
+    for d in downloads {
+        select.add_item(d.describe(), d);
+    }
+    // the callback only changes the selection, which needs no redraw of its own
+    let _ = select.set_selection(selected);
+}
+
+/// Shows everything known about an OPDS entry in a full screen dialog: the cover, authors,
+/// dates, the complete description and every format it can be downloaded in. Selecting a format
+/// downloads it.
+///
+/// # Arguments
+///
+/// * `ctx` - Controller message channel.
+/// * `data` - Entry to show.
+/// * `image` - Cover of the entry, if it was loaded.
+/// * `cover_size` - Largest size of the cover, in cells.
+///
+pub fn entry_dialog(
+    ctx: mpsc::Sender<ControllerMessage>,
+    data: &EntryData,
+    image: Option<&DynamicImage>,
+    cover_size: Vec2,
+) -> ResizedView<Dialog> {
+    let mut layout = LinearLayout::vertical();
+
+    if let Some(author) = &data.author {
+        layout.add_child(TextView::new(StyledString::styled(author, Effect::Italic)));
+    }
+
+    let mut dates = vec![];
+    if let Some(p) = data.published {
+        dates.push(format!("Published: {}", p.format("%Y-%m-%d")));
+    }
+    if let Some(u) = data.updated {
+        dates.push(format!("Updated: {}", u.format("%Y-%m-%d")));
+    }
+    if !dates.is_empty() {
+        layout.add_child(TextView::new(dates.join("\n")));
+    }
+
+    if let Some(img) = image {
+        let mut canvas = CanvasView::new(cover_size);
+        canvas.from_image(img);
+        layout.add_child(DummyView::new());
+        layout.add_child(canvas);
+    }
+
+    if !data.details.is_empty() {
+        layout.add_child(DummyView::new());
+        layout.add_child(TextView::new(&data.details));
+    }
+
+    if let Some(rel) = &data.unsupported {
+        layout.add_child(DummyView::new());
+        layout.add_child(TextView::new(format!(
+            "Unsupported acquisition type: {}",
+            rel
+        )));
+    }
+
+    if !data.downloads.is_empty() {
+        let mut formats = SelectView::<Url>::new().on_submit(move |siv, href: &Url| {
+            siv.pop_layer();
+            ctx.send(ControllerMessage::Download(href.clone()))
+                .expect("failed to send controller message");
+        });
+
+        for (href, mt, size) in &data.downloads {
+            let label = match size {
+                Some(size) => format!("{} ({})", mt, human_readable_size(*size)),
+                None => mt.clone(),
+            };
+            formats.add_item(label, href.clone());
+        }
+
+        layout.add_child(TextView::new(StyledString::styled(
+            "\nFormats",
+            Effect::Bold,
+        )));
+        layout.add_child(formats);
+    }
+
+    Dialog::around(layout.scrollable())
+        .title(&data.title)
+        .dismiss_button("Close")
+        .full_screen()
+}
+
+/// Shows the inline text of an entry in a full screen view, for reading it without downloading
+/// anything. Page Up and Page Down move through the text a screen at a time.
+///
+/// # Arguments
+///
+/// * `title` - Title of the entry.
+/// * `text` - Text to read.
+///
+pub fn reader(title: &str, text: &str) -> ResizedView<Dialog> {
+    let view = TextView::new(text).scrollable().with_name("reader");
+
+    Dialog::around(view)
+        .title(title)
+        .dismiss_button("Close")
+        .full_screen()
+}
@@ -1,28 +1,42 @@
+use crate::config::TitleOverflow;
+use crate::controller::Transfer;
 use crate::model::{get_title_for_entry, EntryType};
+use crate::ui::actions::{perform, Keybindings, Scope};
 use crate::ui::dialogs::input_dialog;
 use crate::ui::side_panel::render_entry_in_side_panel;
+use crate::ui::uiroot::UserData;
 use crate::ControllerMessage;
 use cursive::view::Nameable;
 use cursive::views::{
-    LinearLayout, OnEventView, PaddedView, Panel, ScrollView, SelectView, TextView,
+    Dialog, LinearLayout, OnEventView, PaddedView, Panel, ScrollView, SelectView, TextView,
 };
-use image::DynamicImage;
-use std::collections::HashMap;
-use std::sync::mpsc;
+use cursive::Cursive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use url::Url;
 
 /// Panel that is rendered to the left of the screen. Renders entries from the currently visited
 /// connection. Entries can be selected by clicking on them or pressing enter, which either opens a
 /// context menu or navigates to a new page depending on the content of the entry. There are some
-/// shortcuts in file mode as well. You can open files with "o", delete them with "d" and rename
-/// them with "r". These functions are available inside the context menu as well.
+/// shortcuts in file mode as well. You can open files with "o", delete them with "d", rename
+/// them with "r" and create folders with "n". Space selects several files so they can be deleted
+/// or moved together. Most of these functions are available inside the context menu as well.
 ///
 /// # Arguments
 ///
 /// * `ctx` - Controller message channel
+/// * `bindings` - Keybindings; the ones scoped to the file view are registered here
+/// * `overflow` - What to do with titles that are too wide; only scrolling needs a sideways
+///   scroll bar
 ///
-pub fn directory_view(ctx: mpsc::Sender<ControllerMessage>) -> Panel<PaddedView<LinearLayout>> {
+pub fn directory_view(
+    ctx: mpsc::Sender<ControllerMessage>,
+    bindings: Keybindings,
+    overflow: TitleOverflow,
+) -> Panel<PaddedView<LinearLayout>> {
     let select_ctx = ctx.clone();
     let submit_ctx = ctx.clone();
+    let last_selected = Arc::new(AtomicUsize::new(0));
 
     let select = SelectView::<EntryType>::new()
         .on_submit(move |_, item| {
@@ -31,9 +45,25 @@ pub fn directory_view(ctx: mpsc::Sender<ControllerMessage>) -> Panel<PaddedView<
                 .expect("failed to send controller message");
         })
         .on_select(move |s, item| {
+            if let EntryType::Header(_) = item {
+                skip_header(s, &last_selected);
+                return;
+            }
+            if let Some(id) = s
+                .find_name::<SelectView<EntryType>>("file_view")
+                .and_then(|v| v.selected_id())
+            {
+                let user_data: &mut UserData = s.user_data().unwrap();
+                if let Some(&first) = user_data.wrapped_rows.get(&id) {
+                    skip_wrapped_row(s, first, &last_selected);
+                    return;
+                }
+                last_selected.store(id, Ordering::Relaxed);
+            }
+
             // render the item in the side view
-            let image_data: &mut HashMap<String, DynamicImage> = s.user_data().unwrap();
-            let image = image_data.get(&get_title_for_entry(item));
+            let user_data: &mut UserData = s.user_data().unwrap();
+            let image = user_data.images.get(&get_title_for_entry(item));
 
             if image.is_none() {
                 select_ctx
@@ -51,69 +81,33 @@ pub fn directory_view(ctx: mpsc::Sender<ControllerMessage>) -> Panel<PaddedView<
     msg_view.get_mut().set_style(cursive::theme::Effect::Italic);
     //mv.h_align(cursive::align::HAlign::Center);
 
-    let file_view = ScrollView::new(select).scroll_x(true);
+    let mut selection_view = TextView::new("").with_name("selection_view");
+    selection_view
+        .get_mut()
+        .set_style(cursive::theme::Effect::Italic);
 
-    let open_ctx = ctx.clone();
-    let delete_ctx = ctx.clone();
+    let mut refresh_view = TextView::new("").with_name("refresh_view");
+    refresh_view
+        .get_mut()
+        .set_style(cursive::theme::Effect::Italic);
 
-    // maybe show notification when trying hotkeys on invalid entries?
-    let fv = OnEventView::new(file_view)
-        .on_event('o', move |s| {
-            let select_view = s
-                .find_name::<SelectView<EntryType>>("file_view")
-                .expect("select view disappeared");
+    let file_view = ScrollView::new(select).scroll_x(overflow == TitleOverflow::Scroll);
 
-            let binding = select_view.selection().unwrap();
-            let item = binding.as_ref();
+    let mut fv = OnEventView::new(file_view);
 
-            if let EntryType::File(_, p) = item {
-                open_ctx
-                    .send(ControllerMessage::Open(p.clone()))
-                    .expect("failed to send controller message");
-            }
-        })
-        .on_event('d', move |s| {
-            let select_view = s
-                .find_name::<SelectView<EntryType>>("file_view")
-                .expect("select view disappeared");
-
-            let binding = select_view.selection().unwrap();
-            let item = binding.as_ref();
-            match item {
-                EntryType::File(_, p) | EntryType::Directory(_, p) => {
-                    delete_ctx
-                        .send(ControllerMessage::Delete(p.clone()))
-                        .expect("failed to send controller message");
-                }
-                _ => {}
-            }
-        })
-        .on_event('r', move |s| {
-            let select_view = s
-                .find_name::<SelectView<EntryType>>("file_view")
-                .expect("select view disappeared");
-
-            let binding = select_view.selection().unwrap();
-            let item = binding.as_ref();
-            match item {
-                EntryType::File(_, p) | EntryType::Directory(_, p) => {
-                    let fp = p.to_file_path().unwrap().clone();
-
-                    let r_ctx = ctx.clone();
-                    let d = input_dialog(
-                        "Rename file",
-                        move |new_name| {
-                            r_ctx
-                                .send(ControllerMessage::Rename(fp.clone(), new_name.into()))
-                                .expect("failed to send controller message");
-                        },
-                        false,
-                    );
-                    s.add_layer(d);
-                }
-                _ => {}
-            };
+    // bindings for the rest of the actions are registered globally
+    for (event, action) in bindings.iter() {
+        if action.scope() != Scope::FileView {
+            continue;
+        }
+
+        let action = *action;
+        let a_ctx = ctx.clone();
+        let a_bindings = bindings.clone();
+        fv.set_on_event(event.clone(), move |s| {
+            perform(s, action, &a_ctx, &a_bindings)
         });
+    }
 
     Panel::new(PaddedView::lrtb(
         2,
@@ -123,6 +117,279 @@ pub fn directory_view(ctx: mpsc::Sender<ControllerMessage>) -> Panel<PaddedView<
         LinearLayout::vertical()
             .child(title_view)
             .child(fv)
-            .child(msg_view),
+            .child(msg_view)
+            .child(selection_view)
+            .child(refresh_view),
     ))
 }
+
+/// Moves the selection off a group header, onward in the direction it was moving. Headers at
+/// the ends of the list send it back the other way.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `last_selected` - Index of the entry selected before the header.
+///
+fn skip_header(s: &mut Cursive, last_selected: &AtomicUsize) {
+    let mut select = s
+        .find_name::<SelectView<EntryType>>("file_view")
+        .expect("select view disappeared");
+    let Some(id) = select.selected_id() else {
+        return;
+    };
+
+    let forward = id >= last_selected.load(Ordering::Relaxed);
+    let cb = if (forward && id + 1 < select.len()) || id == 0 {
+        select.select_down(1)
+    } else {
+        select.select_up(1)
+    };
+
+    drop(select);
+    cb(s);
+}
+
+/// Moves the selection off a row that goes on with a wrapped title: past the rest of the title
+/// when stepping down from its first row, onto its first row otherwise.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `first` - Row the title starts on.
+/// * `last_selected` - Index of the entry selected before the row.
+///
+fn skip_wrapped_row(s: &mut Cursive, first: usize, last_selected: &AtomicUsize) {
+    let user_data: &mut UserData = s.user_data().unwrap();
+    let mut next = first + 1;
+    while user_data.wrapped_rows.get(&next) == Some(&first) {
+        next += 1;
+    }
+
+    let mut select = s
+        .find_name::<SelectView<EntryType>>("file_view")
+        .expect("select view disappeared");
+    let target = if last_selected.load(Ordering::Relaxed) == first && next < select.len() {
+        next
+    } else {
+        first
+    };
+    let cb = select.set_selection(target);
+
+    drop(select);
+    cb(s);
+}
+
+/// Returns the entry currently selected in the file view, if any.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+///
+pub fn selected_entry(s: &mut Cursive) -> Option<Arc<EntryType>> {
+    s.find_name::<SelectView<EntryType>>("file_view")
+        .expect("select view disappeared")
+        .selection()
+}
+
+// maybe show notification when trying hotkeys on invalid entries?
+/// Opens the selected file using the OS handler.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn open_selected(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    if let Some(item) = selected_entry(s) {
+        if let EntryType::File(_, p, _) = item.as_ref() {
+            ctx.send(ControllerMessage::Open(p.clone()))
+                .expect("failed to send controller message");
+        }
+    }
+}
+
+/// Opens the folder containing the selected file or directory in the OS file manager.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn reveal_selected(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    if let Some(item) = selected_entry(s) {
+        if let EntryType::File(_, p, _) | EntryType::Directory(_, p, _) = item.as_ref() {
+            ctx.send(ControllerMessage::Reveal(p.clone()))
+                .expect("failed to send controller message");
+        }
+    }
+}
+
+/// Copies the filesystem path of the selected file or directory to the clipboard.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn copy_selected_path(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    if let Some(item) = selected_entry(s) {
+        if let EntryType::File(_, p, _) | EntryType::Directory(_, p, _) = item.as_ref() {
+            ctx.send(ControllerMessage::CopyPath(p.clone()))
+                .expect("failed to send controller message");
+        }
+    }
+}
+
+/// Returns the local files and folders selected for a batch operation.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+///
+fn selection(s: &mut Cursive) -> Vec<Url> {
+    let user_data: &mut UserData = s.user_data().unwrap();
+    let mut urls: Vec<Url> = user_data.selection.iter().cloned().collect();
+    urls.sort();
+    urls
+}
+
+/// Empties the selection once a batch operation has been started.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+fn clear_selection(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    let user_data: &mut UserData = s.user_data().unwrap();
+    user_data.selection.clear();
+    ctx.send(ControllerMessage::SelectionChanged())
+        .expect("failed to send controller message");
+}
+
+/// Adds the file or directory under the cursor to the selection, or removes it if it is already
+/// selected.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn toggle_selection(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    let Some(item) = selected_entry(s) else {
+        return;
+    };
+    let (EntryType::File(_, p, _) | EntryType::Directory(_, p, _)) = item.as_ref() else {
+        return;
+    };
+
+    let user_data: &mut UserData = s.user_data().unwrap();
+    if !user_data.selection.remove(p) {
+        user_data.selection.insert(p.clone());
+    }
+
+    // the labels are drawn by the UI loop, which the controller relays this to
+    ctx.send(ControllerMessage::SelectionChanged())
+        .expect("failed to send controller message");
+}
+
+/// Deletes the selected files and directories after asking once, or the one under the cursor if
+/// nothing is selected.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn delete_selected(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    let urls = selection(s);
+    if !urls.is_empty() {
+        let d_ctx = ctx.clone();
+        let dialog = Dialog::text(format!("Delete {} selected files?", urls.len()))
+            .title("Delete")
+            .button("Yes", move |s| {
+                for url in &urls {
+                    d_ctx
+                        .send(ControllerMessage::Delete(url.clone()))
+                        .expect("failed to send controller message");
+                }
+                clear_selection(s, &d_ctx);
+                s.pop_layer();
+            })
+            .dismiss_button("No");
+        s.add_layer(dialog);
+        return;
+    }
+
+    if let Some(item) = selected_entry(s) {
+        match item.as_ref() {
+            EntryType::File(_, p, _) | EntryType::Directory(_, p, _) => {
+                ctx.send(ControllerMessage::Delete(p.clone()))
+                    .expect("failed to send controller message");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Opens a dialog asking for a new name for the selected file or directory.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+///
+pub fn rename_selected(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>) {
+    if let Some(item) = selected_entry(s) {
+        match item.as_ref() {
+            EntryType::File(_, p, _) | EntryType::Directory(_, p, _) => {
+                let fp = p.to_file_path().unwrap().clone();
+
+                let r_ctx = ctx.clone();
+                let d = input_dialog(
+                    "Rename file",
+                    move |new_name| {
+                        r_ctx
+                            .send(ControllerMessage::Rename(fp.clone(), new_name.into()))
+                            .expect("failed to send controller message");
+                    },
+                    false,
+                );
+                s.add_layer(d);
+            }
+            _ => {}
+        };
+    }
+}
+
+/// Puts the selected files and directories on the controller's clipboard, or the one under the
+/// cursor if nothing is selected.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance.
+/// * `ctx` - Controller message channel
+/// * `transfer` - Whether the files should be moved or copied once pasted.
+///
+pub fn mark_selected(s: &mut Cursive, ctx: &mpsc::Sender<ControllerMessage>, transfer: Transfer) {
+    let urls = selection(s);
+    if !urls.is_empty() {
+        let paths = urls.iter().filter_map(|u| u.to_file_path().ok()).collect();
+        ctx.send(ControllerMessage::Mark(paths, transfer))
+            .expect("failed to send controller message");
+        clear_selection(s, ctx);
+        return;
+    }
+
+    if let Some(item) = selected_entry(s) {
+        match item.as_ref() {
+            EntryType::File(_, p, _) | EntryType::Directory(_, p, _) => {
+                let path = p.to_file_path().unwrap();
+                ctx.send(ControllerMessage::Mark(vec![path], transfer))
+                    .expect("failed to send controller message");
+            }
+            _ => {}
+        }
+    }
+}
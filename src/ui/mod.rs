@@ -1,6 +1,9 @@
+pub mod actions;
 pub mod canvas;
 pub mod dialogs;
 pub mod directory_view;
 pub mod serverinfomodal;
 pub mod side_panel;
+pub mod sixel;
+pub mod themes;
 pub mod uiroot;
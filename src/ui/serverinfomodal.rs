@@ -13,8 +13,11 @@ use url::Url;
 /// # Arguments
 ///
 /// * `sender` - Controller message channel.
+/// * `editing` - Name of the connection being edited; None when adding a new one.
 ///
-pub fn new(sender: mpsc::Sender<ControllerMessage>) -> Dialog {
+pub fn new(sender: mpsc::Sender<ControllerMessage>, editing: Option<String>) -> Dialog {
+    let test_sender = sender.clone();
+
     Dialog::new()
         .title("Enter server information")
         .content(
@@ -28,53 +31,113 @@ pub fn new(sender: mpsc::Sender<ControllerMessage>) -> Dialog {
                 .child(TextView::new_with_content(TextContent::new("Username")))
                 .child(EditView::new().with_name("username"))
                 .child(TextView::new_with_content(TextContent::new("Password")))
-                .child(EditView::new().secret().with_name("password")),
+                .child(EditView::new().secret().with_name("password"))
+                .child(TextView::new_with_content(TextContent::new(
+                    "User-Agent (optional)",
+                )))
+                .child(EditView::new().with_name("user_agent"))
+                .child(TextView::new("Proxy (optional)"))
+                .child(EditView::new().with_name("proxy"))
+                .child(TextView::new("Timeout in seconds (optional)"))
+                .child(EditView::new().with_name("timeout"))
+                .child(TextView::new("").with_name("test_status")),
         )
         .button("Ok", move |s| {
             let name = s.find_name::<EditView>("name").unwrap().get_content();
-            let url = s.find_name::<EditView>("url").unwrap().get_content();
-
-            let username = s
-                .find_name::<EditView>("username")
-                .unwrap()
-                .get_content()
-                .to_string();
-
-            let password = s
-                .find_name::<EditView>("password")
-                .unwrap()
-                .get_content()
-                .to_string();
-
-            // move to fn, test
-            if !name.is_empty() && !url.is_empty() {
-                let res = Url::parse(&url);
-                match res {
-                    Ok(parsed_url) => {
-                        sender
-                            .send(ControllerMessage::AddConnection(
-                                name.to_string(),
-                                Server {
-                                    base_url: parsed_url,
-                                    username: (!username.is_empty()).then_some(username),
-                                },
-                                (!password.is_empty()).then_some(password),
-                            ))
-                            .expect("failed to send UI message");
-                    }
-                    Err(err) => {
-                        Dialog::info(err.to_string());
-                    }
-                }
+            if name.is_empty() {
+                show_status(s, "Name and URL fields cannot be empty!");
+                return;
+            }
 
-                close(s);
-            } else {
-                Dialog::info("Name and URL fields cannot be empty!");
+            match read_server(s) {
+                Ok((server, password)) => {
+                    sender
+                        .send(ControllerMessage::SaveConnection(
+                            editing.clone(),
+                            name.to_string(),
+                            server,
+                            password,
+                        ))
+                        .expect("failed to send UI message");
+                    close(s);
+                }
+                Err(err) => show_status(s, &err),
+            }
+        })
+        .button("Test", move |s| match read_server(s) {
+            // nothing is saved; the result is shown below the fields
+            Ok((server, password)) => {
+                test_sender
+                    .send(ControllerMessage::TestConnection(server, password))
+                    .expect("failed to send controller message");
             }
+            Err(err) => show_status(s, &err),
         })
         .button("Cancel", close)
 }
 
+/// Builds the server from the fields of the dialog.
+///
+/// # Arguments
+///
+/// * `s` - Cursive instance
+///
+/// # Errors
+///
+/// Errors if the URL is missing or cannot be parsed.
+///
+fn read_server(s: &mut Cursive) -> Result<(Server, Option<String>), String> {
+    let url = s.find_name::<EditView>("url").unwrap().get_content();
+    if url.is_empty() {
+        return Err("Name and URL fields cannot be empty!".to_string());
+    }
+
+    let username = s
+        .find_name::<EditView>("username")
+        .unwrap()
+        .get_content()
+        .to_string();
+
+    let password = s
+        .find_name::<EditView>("password")
+        .unwrap()
+        .get_content()
+        .to_string();
+
+    let user_agent = s
+        .find_name::<EditView>("user_agent")
+        .unwrap()
+        .get_content()
+        .to_string();
+
+    let proxy = s
+        .find_name::<EditView>("proxy")
+        .unwrap()
+        .get_content()
+        .trim()
+        .to_string();
+
+    let timeout = s.find_name::<EditView>("timeout").unwrap().get_content();
+    let timeout_seconds = match timeout.trim() {
+        "" => None,
+        t => Some(
+            t.parse::<u64>()
+                .map_err(|_| "The timeout must be a whole number of seconds.".to_string())?,
+        ),
+    };
+
+    // certificates and headers are not editable here; an edited connection keeps the ones from
+    // the config file
+    let server = Server {
+        username: (!username.is_empty()).then_some(username),
+        user_agent: (!user_agent.is_empty()).then_some(user_agent),
+        proxy: (!proxy.is_empty()).then_some(proxy),
+        timeout_seconds,
+        ..Server::new(Url::parse(&url).map_err(|err| err.to_string())?)
+    };
+    Ok((server, (!password.is_empty()).then_some(password)))
+}
+
 /// Meant to be called after a ServerInfoModal is created. Populates the fields of the modal with
 /// information to make editing existing connections easier.
 ///
@@ -93,20 +156,39 @@ pub fn populate_fields(s: &mut Cursive, name: &str, server: &Server, pwd: Option
         .unwrap()
         .set_content(server.base_url.to_string());
 
-    match &server.username {
-        Some(u) => {
-            s.find_name::<EditView>("username")
-                .unwrap()
-                .set_content(u.to_string());
-        }
-        None => {}
+    if let Some(u) = &server.username {
+        s.find_name::<EditView>("username")
+            .unwrap()
+            .set_content(u.to_string());
     }
 
-    match &pwd {
-        Some(p) => {
-            s.find_name::<EditView>("password").unwrap().set_content(p);
-        }
-        None => {}
+    if let Some(p) = &pwd {
+        s.find_name::<EditView>("password").unwrap().set_content(p);
+    }
+
+    if let Some(ua) = &server.user_agent {
+        s.find_name::<EditView>("user_agent")
+            .unwrap()
+            .set_content(ua.to_string());
+    }
+
+    if let Some(proxy) = &server.proxy {
+        s.find_name::<EditView>("proxy")
+            .unwrap()
+            .set_content(proxy.to_string());
+    }
+
+    if let Some(t) = server.timeout_seconds {
+        s.find_name::<EditView>("timeout")
+            .unwrap()
+            .set_content(t.to_string());
+    }
+}
+
+/// Shows a message below the fields of the dialog.
+fn show_status(s: &mut Cursive, msg: &str) {
+    if let Some(mut status) = s.find_name::<TextView>("test_status") {
+        status.set_content(msg);
     }
 }
 
@@ -1,14 +1,16 @@
-use crate::model::EntryType;
-use crate::ui::canvas::CanvasView;
+use crate::model::{get_title_for_entry, EntryType, FileInfo};
+use crate::ui::canvas::{CanvasView, SixelSlot};
+use crate::ui::uiroot::UserData;
+use crate::utils::human_readable_size;
 
+use chrono::{DateTime, Local};
 use cursive::view::Nameable;
 use cursive::views::{
     DummyView, HideableView, LinearLayout, PaddedView, Panel, ResizedView, ScrollView, TextView,
 };
 use cursive::Cursive;
 use cursive::Vec2;
-use image::DynamicImage;
-use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 /// This is the panel rendered to the right of the screen which is responsible for showing details
 /// about an entry. It includes a few TextViews and a canvas view used for rendering the book's
@@ -16,11 +18,20 @@ use std::collections::HashMap;
 ///
 /// # Arguments
 ///
-/// * `width` - Initial size of the panel.
+/// * `width` - Initial width of the panel.
+/// * `cover_size` - Configured cover width, if any.
+/// * `sixel` - Set if the cover is drawn with sixel graphics.
 ///
-pub fn side_panel(width: usize) -> Panel<PaddedView<ScrollView<LinearLayout>>> {
-    let canvas =
-        HideableView::new(CanvasView::new(Vec2::new(width / 3, 10))).with_name("side_panel_canvas");
+pub fn side_panel(
+    width: usize,
+    cover_size: Option<usize>,
+    sixel: Option<Arc<Mutex<SixelSlot>>>,
+) -> Panel<PaddedView<ScrollView<LinearLayout>>> {
+    let mut canvas = CanvasView::new(max_cover_size(width, cover_size));
+    if let Some(slot) = sixel {
+        canvas = canvas.with_sixel(slot);
+    }
+    let canvas = HideableView::new(canvas).with_name("side_panel_canvas");
 
     let padding_left = ResizedView::with_full_width(DummyView::new());
     let padding_right = ResizedView::with_full_width(DummyView::new());
@@ -53,6 +64,22 @@ pub fn side_panel(width: usize) -> Panel<PaddedView<ScrollView<LinearLayout>>> {
     ))
 }
 
+/// Returns the largest size, in cells, a cover may take up in a side panel of the given width.
+/// Covers fill the panel unless a smaller width is configured, and are never taller than they
+/// are wide so tall images do not push the details out of view.
+///
+/// # Arguments
+///
+/// * `width` - Width of the side panel.
+/// * `cover_size` - Configured cover width, if any.
+///
+pub fn max_cover_size(width: usize, cover_size: Option<usize>) -> Vec2 {
+    // borders and padding of the panel
+    let available = width.saturating_sub(6).max(1);
+    let w = cover_size.map_or(available, |c| c.clamp(1, available));
+    Vec2::new(w, w)
+}
+
 /// Updates the side panel with the contents of an entry.
 ///
 /// # Arguments
@@ -69,13 +96,20 @@ pub fn render_entry_in_side_panel(s: &mut Cursive, entry: &EntryType) {
         .unwrap();
 
     match entry {
-        EntryType::File(fname, _url) | EntryType::Directory(fname, _url) => {
-            title.set_content(fname);
+        EntryType::Header(_) | EntryType::More(_) => {
+            title.set_content(get_title_for_entry(entry));
             canvas_wrapper.hide();
 
             author_view.set_content("");
             details.set_content("");
         }
+        EntryType::File(fname, _url, info) | EntryType::Directory(fname, _url, info) => {
+            title.set_content(fname);
+            canvas_wrapper.hide();
+
+            author_view.set_content("");
+            details.set_content(file_details(entry, info));
+        }
         EntryType::OPDSEntry(data) => {
             title.set_content(&data.title);
 
@@ -84,10 +118,17 @@ pub fn render_entry_in_side_panel(s: &mut Cursive, entry: &EntryType) {
                 None => author_view.set_content(""),
             }
 
-            details.set_content(&data.details);
+            match data.updated {
+                Some(u) => details.set_content(format!(
+                    "Updated {}\n\n{}",
+                    u.format("%Y-%m-%d"),
+                    data.details
+                )),
+                None => details.set_content(&data.details),
+            }
 
-            let image_data: &mut HashMap<String, DynamicImage> = s.user_data().unwrap();
-            let image = image_data.get(&data.title);
+            let user_data: &mut UserData = s.user_data().unwrap();
+            let image = user_data.images.get(&data.title);
             match image {
                 Some(im) => {
                     canvas_wrapper.unhide();
@@ -101,3 +142,35 @@ pub fn render_entry_in_side_panel(s: &mut Cursive, entry: &EntryType) {
         }
     }
 }
+
+/// Builds the size and modification date lines shown for local entries. Anything that could not
+/// be read is shown as "—".
+///
+/// # Arguments
+///
+/// * `entry` - Entry to describe.
+/// * `info` - Metadata read from disk.
+///
+fn file_details(entry: &EntryType, info: &Option<FileInfo>) -> String {
+    let modified = info
+        .as_ref()
+        .and_then(|i| i.modified)
+        .map(|m| {
+            DateTime::<Local>::from(m)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or(String::from("—"));
+
+    match entry {
+        // the size of a directory entry says nothing about its contents
+        EntryType::Directory(..) => format!("Modified: {}", modified),
+        _ => {
+            let size = info
+                .as_ref()
+                .map(|i| human_readable_size(i.size))
+                .unwrap_or(String::from("—"));
+            format!("Size: {}\nModified: {}", size, modified)
+        }
+    }
+}
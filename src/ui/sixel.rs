@@ -0,0 +1,357 @@
+use crate::ui::canvas::{SixelArea, SixelSlot};
+
+use cursive::views::LayerPosition;
+use cursive::{Cursive, CursiveRunner, Vec2};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Cell size assumed when the terminal does not report one, in pixels.
+const DEFAULT_CELL_SIZE: Vec2 = Vec2::new(10, 20);
+
+/// How long the terminal gets to answer before it is assumed not to.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What the terminal reported about its graphics before the interface was started.
+#[derive(Default)]
+pub struct TerminalGraphics {
+    /// whether sixel graphics are listed among the terminal's features
+    pub sixel: bool,
+    /// size of a cell in pixels, if the terminal reported it
+    pub cell_size: Option<Vec2>,
+}
+
+/// Asks the terminal for its features (primary device attributes) and the size of its cells.
+/// Has to be called before cursive takes over the terminal, since the answers arrive as input.
+pub fn query_terminal() -> TerminalGraphics {
+    // the device attributes come last, since every terminal answers them
+    let Some(answer) = ask_terminal(b"\x1b[16t\x1b[c") else {
+        return TerminalGraphics::default();
+    };
+    let answer = String::from_utf8_lossy(&answer);
+
+    // "ESC [ ? 62 ; 4 ; 22 c", where 4 stands for sixel graphics
+    let sixel = answer.split("\x1b[?").nth(1).is_some_and(|attributes| {
+        attributes
+            .split('c')
+            .next()
+            .unwrap_or_default()
+            .split(';')
+            .any(|a| a == "4")
+    });
+
+    // "ESC [ 6 ; height ; width t"
+    let cell_size = answer.split("\x1b[6;").nth(1).and_then(|size| {
+        let size = size.split('t').next()?;
+        let (h, w) = size.split_once(';')?;
+        Some(Vec2::new(w.parse().ok()?, h.parse().ok()?))
+    });
+
+    TerminalGraphics { sixel, cell_size }
+}
+
+/// Writes the query to the terminal and collects its answer, up to the end of the device
+/// attributes. Returns None if standard input or output is not a terminal, or nothing was
+/// answered in time.
+#[cfg(unix)]
+fn ask_terminal(query: &[u8]) -> Option<Vec<u8>> {
+    // SAFETY: the termios struct is filled in by tcgetattr before it is used, and poll and read
+    // are only given buffers that outlive the calls
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) != 1 || libc::isatty(libc::STDOUT_FILENO) != 1 {
+            return None;
+        }
+
+        let mut original: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+            return None;
+        }
+        // the answer is not echoed and can be read before a newline arrives
+        let mut raw = original;
+        libc::cfmakeraw(&mut raw);
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+
+        let mut answer = vec![];
+        let mut stdout = std::io::stdout();
+        if stdout.write_all(query).and_then(|_| stdout.flush()).is_ok() {
+            let deadline = Instant::now() + QUERY_TIMEOUT;
+            let mut fd = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let mut buf = [0u8; 256];
+
+            while !answered(&answer) {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() || libc::poll(&mut fd, 1, left.as_millis() as i32) <= 0 {
+                    break;
+                }
+                let n = libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len());
+                if n <= 0 {
+                    break;
+                }
+                answer.extend_from_slice(&buf[..n as usize]);
+            }
+        }
+
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+        (!answer.is_empty()).then_some(answer)
+    }
+}
+
+#[cfg(not(unix))]
+fn ask_terminal(_query: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+/// Whether the answer contains the whole device attributes report.
+fn answered(answer: &[u8]) -> bool {
+    answer
+        .windows(3)
+        .position(|w| w == b"\x1b[?")
+        .is_some_and(|start| answer[start..].contains(&b'c'))
+}
+
+/// Returns the size of a cell in pixels, as the terminal currently reports it.
+#[cfg(unix)]
+fn window_cell_size() -> Option<Vec2> {
+    // SAFETY: the winsize struct is only read after ioctl filled it in
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 {
+            return None;
+        }
+        if size.ws_xpixel == 0 || size.ws_ypixel == 0 || size.ws_col == 0 || size.ws_row == 0 {
+            return None;
+        }
+        Some(Vec2::new(
+            (size.ws_xpixel / size.ws_col) as usize,
+            (size.ws_ypixel / size.ws_row) as usize,
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+fn window_cell_size() -> Option<Vec2> {
+    None
+}
+
+/// Encodes an image as sixel graphics. Colors are reduced to a 6x6x6 color cube, which fits into
+/// the 256 color registers most terminals have.
+pub fn encode(img: &DynamicImage) -> String {
+    let rgb = img.to_rgb8();
+    let level = |v: u8| (v as usize * 5 + 127) / 255;
+
+    // the second parameter keeps pixels that are not set from being painted over
+    let mut out = format!("\x1bP0;1q\"1;1;{};{}", rgb.width(), rgb.height());
+    for color in 0..216 {
+        let (r, g, b) = (color / 36, color / 6 % 6, color % 6);
+        let _ = write!(out, "#{};2;{};{};{}", color, r * 20, g * 20, b * 20);
+    }
+
+    // every character covers a column of six pixels; each color of a band is drawn in turn
+    for top in (0..rgb.height()).step_by(6) {
+        let mut band: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for dy in 0..6.min(rgb.height() - top) {
+            for x in 0..rgb.width() {
+                let p = rgb.get_pixel(x, top + dy);
+                let color = level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]);
+                band.entry(color)
+                    .or_insert_with(|| vec![0; rgb.width() as usize])[x as usize] |= 1 << dy;
+            }
+        }
+
+        for (color, sixels) in band {
+            let _ = write!(out, "#{}", color);
+            push_runs(&mut out, &sixels);
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+/// Appends the sixels, shortening runs of the same one.
+fn push_runs(out: &mut String, sixels: &[u8]) {
+    let mut i = 0;
+    while i < sixels.len() {
+        let run = sixels[i..].iter().take_while(|&&s| s == sixels[i]).count();
+        let c = (63 + sixels[i]) as char;
+        if run > 3 {
+            let _ = write!(out, "!{}{}", run, c);
+        } else {
+            (0..run).for_each(|_| out.push(c));
+        }
+        i += run;
+    }
+}
+
+/// Writes the cover of the details panel to the terminal with sixel graphics, on top of the cells
+/// its canvas leaves blank.
+pub struct SixelRenderer {
+    /// shared with the canvas
+    slot: Arc<Mutex<SixelSlot>>,
+    /// cell size the terminal reported when ncopds started
+    cell_size: Option<Vec2>,
+    /// area and version of the image that is on screen
+    shown: Option<(SixelArea, u64)>,
+    /// terminal size the image was written at; resizing redraws the whole screen
+    screen: Vec2,
+    /// last image encoded: its version, area and cell size, and the sixel data
+    encoded: Option<(u64, SixelArea, Vec2, String)>,
+}
+
+impl SixelRenderer {
+    /// # Arguments
+    ///
+    /// * `cell_size` - Cell size the terminal reported, in pixels.
+    ///
+    pub fn new(cell_size: Option<Vec2>) -> Self {
+        SixelRenderer {
+            slot: Arc::new(Mutex::new(SixelSlot::default())),
+            cell_size,
+            shown: None,
+            screen: Vec2::zero(),
+            encoded: None,
+        }
+    }
+
+    /// Makes the next render write the image again, for when the whole terminal was cleared,
+    /// i.e. by loading a theme or taking the terminal back from another program.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+        self.screen = Vec2::zero();
+    }
+
+    /// Returns the slot the canvas is given.
+    pub fn slot(&self) -> Arc<Mutex<SixelSlot>> {
+        Arc::clone(&self.slot)
+    }
+
+    /// Writes the image again if it moved, changed or was painted over. Meant to be called after
+    /// every step of the interface. Images are left out while a dialog, menu or notification
+    /// overlaps them, since they would be drawn on top of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `siv` - Cursive instance that just drew the screen.
+    /// * `changed` - Whether anything happened that may have changed the screen.
+    ///
+    pub fn render(&mut self, siv: &mut CursiveRunner<Cursive>, changed: bool) {
+        let screen = siv.screen_size();
+        if !changed && screen == self.screen {
+            return;
+        }
+
+        // the canvas reports where it is on the frame that is drawn now
+        self.slot.lock().unwrap().area = None;
+        siv.refresh();
+
+        let slot = self.slot.lock().unwrap();
+        let area = slot
+            .area
+            .filter(|a| !covered(siv, a) && a.pos.y + a.size.y < screen.y);
+        let want = area.map(|a| (a, slot.version));
+        if want == self.shown && screen == self.screen {
+            return;
+        }
+        self.shown = want;
+        self.screen = screen;
+
+        let (Some((area, version)), Some(img)) = (want, &slot.image) else {
+            return;
+        };
+        let cell = window_cell_size()
+            .or(self.cell_size)
+            .unwrap_or(DEFAULT_CELL_SIZE);
+        if !matches!(&self.encoded, Some((v, a, c, _)) if *v == version && *a == area && *c == cell)
+        {
+            // the image fills the whole canvas; only the part that is visible is written
+            let visible = img
+                .resize_exact(
+                    (area.board.x * cell.x) as u32,
+                    (area.board.y * cell.y) as u32,
+                    FilterType::Triangle,
+                )
+                .crop_imm(
+                    (area.from.x * cell.x) as u32,
+                    (area.from.y * cell.y) as u32,
+                    (area.size.x * cell.x) as u32,
+                    (area.size.y * cell.y) as u32,
+                );
+            self.encoded = Some((version, area, cell, encode(&visible)));
+        }
+        drop(slot);
+
+        let (_, _, _, data) = self.encoded.as_ref().unwrap();
+        // saving and restoring the cursor also keeps the colors cursive last set
+        let mut stdout = std::io::stdout().lock();
+        let res = write!(
+            stdout,
+            "\x1b7\x1b[{};{}H{}\x1b8",
+            area.pos.y + 1,
+            area.pos.x + 1,
+            data
+        )
+        .and_then(|_| stdout.flush());
+        if let Err(err) = res {
+            log::error!("could not draw the cover: {}", err);
+        }
+    }
+}
+
+/// Whether a layer above the main view, such as a dialog, menu or notification, overlaps the
+/// area.
+///
+/// # Arguments
+///
+/// * `siv` - Cursive instance.
+/// * `area` - Area of the image.
+///
+fn covered(siv: &mut CursiveRunner<Cursive>, area: &SixelArea) -> bool {
+    let (pos, size) = (area.pos, area.size);
+    let screen = siv.screen();
+    screen
+        .layer_sizes()
+        .iter()
+        .enumerate()
+        .skip(1)
+        .any(|(i, layer_size)| {
+            let Some(offset) = screen.layer_offset(LayerPosition::FromBack(i)) else {
+                return false;
+            };
+            // layers are placed below the menu bar; their shadows reach a cell further
+            let (top, bottom) = (offset.y, offset.y + layer_size.y + 2);
+            let (left, right) = (offset.x, offset.x + layer_size.x + 1);
+            top < pos.y + size.y && pos.y < bottom && left < pos.x + size.x && pos.x < right
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalidated_images_are_written_again() {
+        let mut renderer = SixelRenderer::new(None);
+        let area = SixelArea {
+            pos: Vec2::new(50, 2),
+            board: Vec2::new(20, 10),
+            from: Vec2::zero(),
+            size: Vec2::new(20, 10),
+        };
+        renderer.shown = Some((area, 1));
+        renderer.screen = Vec2::new(100, 40);
+
+        renderer.invalidate();
+        assert_eq!(renderer.shown, None);
+        assert_eq!(renderer.screen, Vec2::zero());
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+use toml::Value;
+
+/// Themes built into ncopds as (name, label, theme) triples. The name is what `theme` in the
+/// config refers to.
+pub const PRESETS: [(&str, &str, &str); 3] = [
+    ("dark", "Dark", include_str!("themes/dark.toml")),
+    ("light", "Light", include_str!("themes/light.toml")),
+    (
+        "high-contrast",
+        "High contrast",
+        include_str!("themes/high_contrast.toml"),
+    ),
+];
+
+/// Builds the theme ncopds is drawn with: the preset with the given name, with the user's theme
+/// file applied on top so that anything set there wins. Without a preset, only the theme file is
+/// used. Unknown presets are ignored.
+///
+/// # Arguments
+///
+/// * `preset` - Name of a preset, i.e. "dark".
+/// * `theme_path` - Location of the user's theme file.
+///
+/// # Errors
+///
+/// Errors if the theme file cannot be read or is not valid TOML.
+///
+pub fn theme_toml(
+    preset: Option<&str>,
+    theme_path: &Path,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let user = std::fs::read_to_string(theme_path)?;
+
+    let base = match preset {
+        Some(name) => match PRESETS.iter().find(|(n, _, _)| *n == name) {
+            Some((_, _, toml)) => *toml,
+            None => {
+                log::warn!("unknown theme preset {}", name);
+                return Ok(user);
+            }
+        },
+        None => return Ok(user),
+    };
+
+    let mut theme: Value = toml::from_str(base)?;
+    let overrides: Value = toml::from_str(&user)?;
+    merge(&mut theme, overrides);
+    Ok(toml::to_string(&theme)?)
+}
+
+/// Copies every value of `overrides` into `base`, descending into tables both have.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Table(base), Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
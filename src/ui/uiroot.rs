@@ -1,24 +1,59 @@
-use crate::model::{get_title_for_entry, EntryType};
+use crate::config::{Config, Layout, RenderBackend, TitleOverflow};
+use crate::connection::{AuthenticationRequired, Health};
+use crate::logging;
+use crate::model::{
+    get_title_for_entry, is_downloaded, sorted_by_date, with_group_headers, EntryType,
+    FinishedDownload, PageState,
+};
 use crate::server::Server;
 use crate::ui;
+use crate::ui::actions::{
+    default_keybindings, keys_for, perform, register_global_callbacks, Action, Keybindings,
+};
 use crate::ui::canvas::CanvasView;
-use crate::ui::dialogs::{input_dialog, notification};
+use crate::ui::dialogs::{
+    address_dialog, credentials_dialog, downloads_dialog, fill_recent_downloads, input_dialog,
+    notification, reader, search_dialog,
+};
 use crate::ui::directory_view::directory_view;
-use crate::ui::side_panel::side_panel;
-use cursive::view::{Nameable, SizeConstraint};
+use crate::ui::side_panel::{max_cover_size, side_panel};
+use crate::ui::sixel::{query_terminal, SixelRenderer, TerminalGraphics};
+use crate::ui::themes::{theme_toml, PRESETS};
+use crate::utils::{
+    clipboard_command, copy_to_clipboard, ellipsize, time_ago, wrap_title, Periodic,
+};
+use chrono::{DateTime, Utc};
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::{BaseColor, Color, ColorStyle, Effect, Style};
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, SizeConstraint, View};
 use cursive::views::{
-    Dialog, HideableView, LinearLayout, NamedView, PaddedView, Panel, ResizedView, ScrollView,
-    SelectView, TextContent, TextView,
+    Dialog, HideableView, LinearLayout, NamedView, OnEventView, PaddedView, Panel, ResizedView,
+    ScrollView, SelectView, TextContent, TextView,
 };
-use cursive::Cursive;
+use cursive::{Cursive, Vec2, XY};
 
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::ControllerMessage;
 use image::DynamicImage;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use termsize;
+use url::Url;
+
+/// State the views' callbacks need, kept in cursive's user data.
+#[derive(Default)]
+pub struct UserData {
+    /// covers that were already downloaded, by entry title
+    pub images: HashMap<String, DynamicImage>,
+    /// local files and folders picked for a batch operation
+    pub selection: HashSet<Url>,
+    /// rows of the file view that go on with a wrapped title, mapped to the row its entry
+    /// starts on
+    pub wrapped_rows: HashMap<usize, usize>,
+}
 
 pub struct UIRoot {
     pub cursive: cursive::CursiveRunner<Cursive>,
@@ -29,26 +64,173 @@ pub struct UIRoot {
     width: usize,
     /// height of screen; used for resizing
     height: usize,
-    notifications: Vec<(u32, String)>,
+    /// notifications on screen, oldest first
+    notifications: Vec<Notification>,
+    /// keys bound to each action
+    bindings: Keybindings,
+    /// state of each online connection as shown in the View menu
+    connections: HashMap<String, ConnectionState>,
+    /// whether a connection from the connection dialog is being tested
+    testing_connection: bool,
+    /// names of the connections in the order they were added to the View menu; the first nine
+    /// can be switched to with the number keys
+    connection_order: Vec<String>,
+    /// configured cover width, in characters
+    cover_size: Option<usize>,
+    /// how the file view and the details panel are arranged
+    layout: Layout,
+    /// share of the screen given to the file view
+    panel_ratio: f32,
+    /// how titles wider than the file view are shown
+    title_overflow: TitleOverflow,
+    /// room for titles in the file view, in cells
+    title_width: usize,
+    /// whether the details panel is hidden, giving the file view the whole screen
+    details_hidden: bool,
+    /// whether catalog pages are sorted by date added instead of shown in feed order
+    sort_by_date: bool,
+    /// formats downloads are picked from, in order of preference
+    preferred_formats: Vec<String>,
+    /// names of the files in the download directory
+    downloaded: HashSet<String>,
+    /// location of the user's theme file, applied on top of the chosen preset
+    theme_path: std::path::PathBuf,
+    /// entries of the current page that are not in the file view yet
+    pending_entries: Option<PendingEntries>,
+    /// whether the current page is loading, loaded or failed to load
+    page_state: PageState,
+    /// entries of the current page held back until "Show more" is selected
+    hidden_entries: Vec<EntryType>,
+    /// how many entries are shown at a time; 0 shows every entry
+    max_visible_entries: usize,
+    /// draws the cover with sixel graphics; covers are drawn with colored cells without it
+    sixel: Option<SixelRenderer>,
+    /// when the UI was set up; spinners turn with the time since
+    started: Instant,
+    /// spinner frame that is on screen
+    spinner: char,
+    /// redraws the screen now and then when it is only redrawn on changes
+    fallback_redraw: Periodic,
+}
+
+/// The details panel as built by `side_panel`.
+type DetailsPanel = Panel<PaddedView<ScrollView<LinearLayout>>>;
+
+/// A notification that is currently on screen.
+struct Notification {
+    /// when the notification was shown
+    shown_at: Instant,
+    /// name of the notification's layer
+    id: String,
+    size: XY<usize>,
+}
+
+/// What the View menu shows about an online connection.
+struct ConnectionState {
+    status: ConnectionStatus,
+    /// result of the latest health check, if there was one
+    health: Option<Health>,
+    /// whether the catalog can be searched; only known once it is connected
+    searchable: bool,
+    /// label the connection currently has in the menu
+    label: String,
 }
 
+/// A page whose entries are added to the file view a few at a time, so large feeds do not
+/// freeze the screen.
+struct PendingEntries {
+    entries: std::vec::IntoIter<EntryType>,
+    total: usize,
+    /// message shown below the file view once every entry is in
+    msg: String,
+}
+
+/// How far along an online connection is. Shown next to its name in the View menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Ready,
+    Failed,
+}
+
+// frames shown next to connections that are still connecting
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+// how long each frame of the spinner is shown
+const SPINNER_STEP: Duration = Duration::from_millis(150);
+
+// how long notifications stay on screen
+const NOTIFICATION_TIME: Duration = Duration::from_secs(5);
+
+// entries added to the file view each frame
+const ENTRIES_PER_FRAME: usize = 250;
+
 #[derive(Debug)]
 pub enum UIMessage {
     /// populates the View and Edit trees with a new connection
     AddConnection(String, Server, Option<String>),
+    /// gives a connection's place in the View menu and its number key to its new name, and
+    /// removes it from the Edit menu until it is added under the new name
+    RenameConnection(String, String),
     /// changes the entries rendered inside the left panel
-    UpdateDirectoryView(String, Vec<EntryType>, String),
+    UpdateDirectoryView(String, Vec<EntryType>, PageState),
+    /// replaces the message below the file view while the page at the address is still loading
+    LoadProgress(String, String),
     /// shows a dialog box with a title and message
     ShowInfo(String, String),
     /// opens a small menu with entries labeled with the string and hooked up to a controller event
     ShowContextMenu(String, Vec<(String, ControllerMessage)>),
     /// saves an image into memory for display
     StoreImage(String, DynamicImage),
-    /// shows a password prompt which updates the password for a given server
-    PasswordPrompt(String, Server),
+    /// shows a password prompt which updates the password for a given server; if the server sent
+    /// an authentication document, its labels are used in the prompt
+    PasswordPrompt(String, Server, Option<AuthenticationRequired>),
     /// displays a small popup in the bottom right corner of the screen with a given title and
     /// content
     ShowNotification(String, String),
+    /// asks the user a yes / no question; the message is sent to the controller on yes
+    Confirm(String, String, ControllerMessage),
+    /// updates the status shown next to a connection in the View menu
+    ConnectionStatus(String, ConnectionStatus),
+    /// asks for the passphrase of the credentials file; true if the file already exists
+    PassphrasePrompt(bool),
+    /// shows when the active connection last fetched a page and whether it browses
+    /// anonymously
+    LastRefresh(Option<DateTime<Utc>>, bool),
+    /// updates the health dot shown next to a connection in the View menu
+    ConnectionHealth(String, Health),
+    /// marks a connection in the View menu as searchable, or not
+    ConnectionSearchable(String, bool),
+    /// opens the search dialog with the active connection's recent searches
+    SearchPrompt(Vec<String>),
+    /// opens the address bar, filled in with the active connection's current address
+    AddressPrompt(Url),
+    /// removes every notification from the screen
+    DismissNotifications(),
+    /// hides the details panel, or shows it again
+    ToggleDetails(),
+    /// sorts catalog pages by date added (true) or shows them in feed order (false)
+    SortByDate(bool),
+    /// updates the names of the files in the download directory
+    DownloadedFiles(HashSet<String>),
+    /// closes the interface, ending the main loop
+    Quit(),
+    /// shows that a connection is being tested (None) or how the test went
+    ConnectionTest(Option<Result<String, String>>),
+    /// redraws the file view after files were selected or unselected
+    SelectionChanged(),
+    /// replaces the "Show more" entry with the next entries of the page
+    ShowMoreEntries(),
+    /// shows the body the server sent for the page at the URL
+    ShowRawPage(Url, String),
+    /// lists the recent downloads, most recent first; opens or closes the list if true, otherwise
+    /// only updates it if it is open
+    RecentDownloads(Vec<FinishedDownload>, bool),
+    /// redraws the interface with the named preset theme, or with none
+    ApplyTheme(Option<String>),
+    /// hands the terminal to the user's editor to change the theme file, then reloads the theme
+    /// on top of the given preset
+    EditTheme(Option<String>),
 }
 
 impl UIRoot {
@@ -61,23 +243,39 @@ impl UIRoot {
     /// * `controller_tx` - Message channel to controller
     /// * `theme_path` - Path to theme file
     /// * `t_size` - terminal size
+    /// * `config` - Config struct
     ///
     pub fn new(
         controller_tx: mpsc::Sender<ControllerMessage>,
         theme_path: &std::path::Path,
         t_size: termsize::Size,
+        config: &Config,
     ) -> UIRoot {
+        // the terminal can only be asked before cursive reads its input
+        let graphics = match config.render_backend {
+            Some(RenderBackend::Ascii) => TerminalGraphics::default(),
+            _ => query_terminal(),
+        };
+        let backend = config.render_backend.unwrap_or(if graphics.sixel {
+            RenderBackend::Sixel
+        } else {
+            RenderBackend::Ascii
+        });
+
         let mut cursive =
             cursive::CursiveRunner::new(Cursive::new(), cursive::backends::try_default().unwrap());
 
-        // UI refreshes on its own so you don't have to hit the keys
-        cursive.set_autorefresh(true);
+        // UI refreshes on its own so you don't have to hit the keys; without it, step() redraws
+        // whenever something changed
+        cursive.set_fps(config.redraw_rate);
 
-        // only show info
-        cursive::logger::set_external_filter_level(cursive::reexports::log::LevelFilter::Info);
-        cursive::logger::set_internal_filter_level(cursive::reexports::log::LevelFilter::Info);
-        // init logger
-        cursive::logger::init();
+        // init logger; records go to the debug console and the log file
+        logging::init(
+            &config.log_path(),
+            logging::log_level(&config.log_level),
+            config.max_log_size,
+        );
+        log::info!("drawing covers with {:?}", backend);
 
         // load theme
         if theme_path.metadata().is_err() {
@@ -85,9 +283,9 @@ impl UIRoot {
         }
 
         // https://docs.rs/cursive/latest/cursive/theme/index.html
-        cursive
-            .load_toml(&std::fs::read_to_string(theme_path).expect("could not open theme file"))
-            .expect("couldn't read theme");
+        let theme =
+            theme_toml(config.theme.as_deref(), theme_path).expect("could not open theme file");
+        cursive.load_toml(&theme).expect("couldn't read theme");
 
         let (ui_tx, ui_rx) = mpsc::channel::<UIMessage>();
         let mut ui = UIRoot {
@@ -98,91 +296,129 @@ impl UIRoot {
             width: t_size.cols.into(),
             height: t_size.rows.into(),
             notifications: vec![],
+            bindings: default_keybindings(),
+            connections: HashMap::new(),
+            connection_order: vec![],
+            testing_connection: false,
+            cover_size: config.cover_size,
+            layout: config.layout,
+            panel_ratio: config.panel_ratio.clamp(0.2, 0.8),
+            title_overflow: config.title_overflow,
+            title_width: 0,
+            details_hidden: false,
+            sort_by_date: false,
+            preferred_formats: config.preferred_formats.clone(),
+            downloaded: HashSet::new(),
+            theme_path: theme_path.to_path_buf(),
+            pending_entries: None,
+            page_state: PageState::Loaded(String::new()),
+            hidden_entries: vec![],
+            max_visible_entries: config.max_visible_entries,
+            sixel: (backend == RenderBackend::Sixel)
+                .then(|| SixelRenderer::new(graphics.cell_size)),
+            started: Instant::now(),
+            spinner: SPINNER[0],
+            fallback_redraw: Periodic::new(Duration::from_secs(1)),
         };
 
-        ui.cursive
-            .set_user_data(HashMap::<String, DynamicImage>::new());
+        ui.cursive.set_user_data(UserData::default());
 
-        let side_panel = NamedView::new(
+        // the sizes are set by resize_panels below
+        let side_panel = HideableView::new(NamedView::new(
             "size_detail_panel",
-            ResizedView::with_fixed_width(ui.width / 2, side_panel(ui.width)),
-        );
+            ResizedView::with_full_screen(side_panel(
+                ui.width / 2,
+                ui.cover_size,
+                ui.sixel.as_ref().map(SixelRenderer::slot),
+            )),
+        ))
+        .with_name("details_panel");
 
         let file_view = NamedView::new(
             "size_file_view",
-            ResizedView::with_fixed_width(ui.width / 2, directory_view(controller_tx.clone())),
+            ResizedView::with_full_screen(directory_view(
+                controller_tx.clone(),
+                ui.bindings.clone(),
+                ui.title_overflow,
+            )),
         );
 
+        let panels = match ui.layout {
+            Layout::Horizontal => LinearLayout::horizontal(),
+            Layout::Vertical => LinearLayout::vertical(),
+        };
+
         let main_view = ResizedView::new(
             SizeConstraint::Full,
             SizeConstraint::Full,
-            LinearLayout::horizontal()
-                .child(file_view)
-                .child(side_panel),
+            panels.child(file_view).child(side_panel),
         );
 
         ui.cursive.add_fullscreen_layer(main_view);
-        ui.cursive.add_global_callback('q', Cursive::quit);
-        ui.cursive
-            .add_global_callback('~', Cursive::toggle_debug_console);
-
-        ui.cursive.add_global_callback('?', move |s| {
-            let d = about_screen();
-            s.add_layer(d);
-        });
-
-        let search_ctx = controller_tx.clone();
-        ui.cursive.add_global_callback('/', move |s| {
-            let ss = search_ctx.clone();
-            let d = input_dialog(
-                "Search",
-                move |query| {
-                    ss.send(ControllerMessage::Search(query))
-                        .expect("Failed to search server.");
-                },
-                false,
-            );
-            s.add_layer(d);
-        });
-
-        let backctx = controller_tx.clone();
-        ui.cursive
-            .add_global_callback(cursive::event::Key::Backspace, move |s| {
-                // check if popup is open first
-                if s.find_name::<SelectView<ControllerMessage>>("popup")
-                    .is_some()
-                {
-                    s.pop_layer();
-                } else {
-                    backctx.clone().send(ControllerMessage::GoBack()).unwrap();
-                }
-            });
+        ui.resize_panels();
+        register_global_callbacks(&mut ui.cursive, &controller_tx, &ui.bindings);
 
-        let add_ctx = controller_tx.clone();
+        let about_ctx = controller_tx.clone();
+        let about_bindings = ui.bindings.clone();
         let local_ctx = controller_tx.clone();
+        let local_bindings = ui.bindings.clone();
+        let add_ctx = controller_tx.clone();
+        let add_bindings = ui.bindings.clone();
+        let clear_ctx = controller_tx.clone();
+        let clear_bindings = ui.bindings.clone();
 
         // adding a delimiter to the menu bar crashes it?
         ui.cursive
             .menubar()
-            .add_leaf("ncopds", |s| {
-                let d = about_screen();
-                s.add_layer(d);
+            .add_leaf("ncopds", move |s| {
+                perform(s, Action::Help, &about_ctx, &about_bindings);
             })
             .add_subtree(
                 "View",
                 cursive::menu::Tree::new()
-                    .leaf("Download directory", move |_| {
-                        local_ctx
-                            .send(ControllerMessage::ChangeConnection("local".to_string()))
-                            .expect("local connection disappeared");
+                    .leaf("Download directory", move |s| {
+                        perform(
+                            s,
+                            Action::ShowDownloadDirectory,
+                            &local_ctx,
+                            &local_bindings,
+                        );
                     })
                     .leaf("Add connection", move |s| {
-                        let diag = ui::serverinfomodal::new(add_ctx.clone());
-                        s.add_layer(diag);
+                        perform(s, Action::AddConnection, &add_ctx, &add_bindings);
                     })
                     .delimiter(),
             )
-            .add_subtree("Edit", cursive::menu::Tree::new());
+            .add_subtree(
+                "Edit",
+                cursive::menu::Tree::new()
+                    .leaf("Clear all caches", move |s| {
+                        perform(s, Action::ClearAllCaches, &clear_ctx, &clear_bindings);
+                    })
+                    .delimiter(),
+            );
+
+        let edit_theme_ctx = controller_tx.clone();
+        let edit_theme_bindings = ui.bindings.clone();
+        let default_ctx = controller_tx.clone();
+        let mut themes = cursive::menu::Tree::new().leaf("Default", move |_| {
+            default_ctx
+                .send(ControllerMessage::SetTheme(None))
+                .expect("failed to send controller message");
+        });
+        for (name, label, _) in PRESETS {
+            let theme_ctx = controller_tx.clone();
+            themes.add_leaf(label, move |_| {
+                theme_ctx
+                    .send(ControllerMessage::SetTheme(Some(name.to_string())))
+                    .expect("failed to send controller message");
+            });
+        }
+        themes.add_delimiter();
+        themes.add_leaf("Edit theme file", move |s| {
+            perform(s, Action::EditTheme, &edit_theme_ctx, &edit_theme_bindings);
+        });
+        ui.cursive.menubar().add_subtree("Theme", themes);
         ui.cursive.set_autohide_menu(false);
 
         ui
@@ -198,34 +434,473 @@ impl UIRoot {
     ///
     fn update_size(&mut self, width: usize, height: usize) {
         if self.width != width || self.height != height {
-            let file_view = self
+            self.width = width;
+            self.height = height;
+            self.resize_panels();
+            self.layout_notifications();
+        }
+    }
+
+    /// Splits the screen between the file view and the details panel according to the layout,
+    /// and fits the cover into the details panel.
+    fn resize_panels(&mut self) {
+        self.cursive
+            .find_name::<HideableView<NamedView<ResizedView<DetailsPanel>>>>("details_panel")
+            .expect("details panel missing")
+            .set_visible(!self.details_hidden);
+
+        let file_view = self
+            .cursive
+            .find_name::<ResizedView<Panel<PaddedView<LinearLayout>>>>("size_file_view");
+
+        let details_panel = self
+            .cursive
+            .find_name::<ResizedView<DetailsPanel>>("size_detail_panel");
+
+        // the menu bar takes up the first line
+        let height = self.height.saturating_sub(1);
+        let share = |total: usize| (total as f32 * self.panel_ratio).round() as usize;
+        let (file_size, details_size) = match self.layout {
+            _ if self.details_hidden => (Vec2::new(self.width, height), Vec2::zero()),
+            Layout::Horizontal => (
+                Vec2::new(share(self.width), height),
+                Vec2::new(self.width - share(self.width), height),
+            ),
+            Layout::Vertical => (
+                Vec2::new(self.width, share(height)),
+                Vec2::new(self.width, height - share(height)),
+            ),
+        };
+
+        if let Some(mut fv) = file_view {
+            fv.set_constraints(
+                SizeConstraint::Fixed(file_size.x),
+                SizeConstraint::Fixed(file_size.y),
+            );
+        }
+
+        // borders, padding and the scroll bar
+        let title_width = file_size.x.saturating_sub(8).max(1);
+        if title_width != self.title_width {
+            self.title_width = title_width;
+            if self.title_overflow != TitleOverflow::Scroll {
+                self.relabel_entries();
+            }
+        }
+
+        if self.details_hidden {
+            // the cover keeps its size so it comes back as it was
+            return;
+        }
+
+        if let Some(mut dp) = details_panel {
+            dp.set_constraints(
+                SizeConstraint::Fixed(details_size.x),
+                SizeConstraint::Fixed(details_size.y),
+            );
+        }
+
+        let mut cover = max_cover_size(details_size.x, self.cover_size);
+        if self.layout == Layout::Vertical {
+            // borders, title and author
+            cover.y = cover.y.min(details_size.y.saturating_sub(4).max(1));
+        }
+
+        if let Some(mut canvas) = self
+            .cursive
+            .find_name::<HideableView<CanvasView>>("side_panel_canvas")
+        {
+            canvas.get_inner_mut().set_max_size(cover);
+        }
+    }
+
+    /// Stacks the notifications in the bottom right corner of the screen, the oldest at the
+    /// bottom. Notifications that would run off the top of the screen stay just below the menu
+    /// bar.
+    fn layout_notifications(&mut self) {
+        let screen = self.cursive.screen_mut();
+        let mut bottom = self.height;
+
+        for n in &self.notifications {
+            let Some(pos) = screen.find_layer_from_name(&n.id) else {
+                continue;
+            };
+
+            let x = self.width.saturating_sub(n.size.x);
+            let y = bottom.saturating_sub(n.size.y).max(1);
+            screen.reposition_layer(pos, cursive::view::Position::absolute((x, y)));
+            bottom = y;
+        }
+    }
+
+    /// Relabels a connection in the View menu to reflect its status. Connections that are still
+    /// connecting get a spinner that turns as time passes.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection
+    /// * `status` - New status of the connection
+    ///
+    fn set_connection_status(&mut self, name: &str, status: ConnectionStatus) {
+        let health = self.connections.get(name).and_then(|c| c.health);
+        self.relabel_connection(name, status, health);
+    }
+
+    /// Updates the dot next to a connection in the View menu. Changes are logged, but otherwise
+    /// checks are silent.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection
+    /// * `health` - Result of the latest health check
+    ///
+    fn set_connection_health(&mut self, name: &str, health: Health) {
+        let (status, old) = match self.connections.get(name) {
+            Some(c) => (c.status, c.health),
+            None => (ConnectionStatus::Ready, None),
+        };
+
+        if old.is_some_and(|h| h != health) {
+            log::warn!("{} is now {}", name, health);
+        }
+        self.relabel_connection(name, status, Some(health));
+    }
+
+    /// Shows whether a connection can be searched next to its name in the View menu, so a search
+    /// does not have to be tried to find out.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the connection
+    /// * `searchable` - Whether the catalog offers a search
+    ///
+    fn set_connection_searchable(&mut self, name: &str, searchable: bool) {
+        let Some(state) = self.connections.get_mut(name) else {
+            return;
+        };
+        state.searchable = searchable;
+
+        let (status, health) = (state.status, state.health);
+        self.relabel_connection(name, status, health);
+    }
+
+    fn relabel_connection(&mut self, name: &str, status: ConnectionStatus, health: Option<Health>) {
+        let searchable = self.connections.get(name).is_some_and(|c| c.searchable);
+        let numbered = self.numbered_name(name);
+        let mut label = StyledString::plain(match status {
+            ConnectionStatus::Connecting => format!("{} (connecting {})", numbered, self.spinner),
+            ConnectionStatus::Ready => numbered,
+            ConnectionStatus::Failed => format!("{} (failed)", numbered),
+        });
+
+        if searchable {
+            label.append_plain(" 🔍");
+        }
+
+        if let Some(h) = health {
+            let color = match h {
+                Health::Reachable => BaseColor::Green,
+                Health::Unreachable => BaseColor::Red,
+                Health::AuthError => BaseColor::Yellow,
+            };
+            label.append_plain(" ");
+            label.append_styled("●", Color::Dark(color));
+        }
+
+        let old_label = self.connection_label(name);
+        if old_label != label.source() {
+            let st = self
                 .cursive
-                .find_name::<ResizedView<Panel<PaddedView<LinearLayout>>>>("size_file_view");
+                .menubar()
+                .get_subtree(1)
+                .expect("View tree missing!");
 
-            let details_panel = self.cursive.find_name::<ResizedView<
-                Panel<PaddedView<ScrollView<LinearLayout>>>,
-            >>("size_detail_panel");
+            if let Some(cursive::menu::Item::Leaf { label: l, .. }) = st.find_item(&old_label) {
+                *l = label.clone();
+            }
+        }
+
+        self.connections.insert(
+            name.to_string(),
+            ConnectionState {
+                status,
+                health,
+                searchable,
+                label: label.source().to_string(),
+            },
+        );
+    }
 
-            if let Some(mut fv) = file_view {
-                fv.set_width(SizeConstraint::Fixed(width / 2));
+    /// Returns the label of an entry in the file view, one line for each row it takes up. OPDS
+    /// entries that were already downloaded are marked with a check. Entries are colored by kind
+    /// using the theme's palette: directories and navigation entries in the secondary color,
+    /// books that can be downloaded in the tertiary color and books that cannot be acquired
+    /// dimmed. Entries without any links are shown in italics.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Entry to label.
+    ///
+    fn entry_label(&self, entry: &EntryType, selection: &HashSet<Url>) -> Vec<StyledString> {
+        let label = match entry {
+            EntryType::OPDSEntry(e)
+                if is_downloaded(e, &self.preferred_formats, &self.downloaded) =>
+            {
+                format!("✓ {}", e.title)
             }
+            EntryType::File(name, p, _) | EntryType::Directory(name, p, _)
+                if selection.contains(p) =>
+            {
+                format!("● {}", name)
+            }
+            _ => get_title_for_entry(entry),
+        };
 
-            if let Some(mut dp) = details_panel {
-                dp.set_width(SizeConstraint::Fixed(width / 2));
+        let lines = match (self.title_overflow, entry) {
+            (TitleOverflow::Scroll, _) => vec![label],
+            // headers and "Show more" are never selected, so they keep to a single row
+            (TitleOverflow::Ellipsize, _) | (_, EntryType::Header(_) | EntryType::More(_)) => {
+                vec![ellipsize(&label, self.title_width)]
             }
+            (TitleOverflow::Wrap, _) => wrap_title(&label, self.title_width),
+        };
 
-            self.width = width;
-            self.height = height;
+        let style: Style = match entry {
+            EntryType::File(..) => return lines.into_iter().map(StyledString::plain).collect(),
+            EntryType::Header(_) => Style::from(ColorStyle::title_primary()).combine(Effect::Bold),
+            EntryType::Directory(..) => ColorStyle::secondary().into(),
+            EntryType::More(_) => Effect::Bold.into(),
+            EntryType::OPDSEntry(e) if e.unsupported.is_some() => Effect::Dim.into(),
+            EntryType::OPDSEntry(e) if !e.is_actionable() => Effect::Italic.into(),
+            EntryType::OPDSEntry(e) if e.downloads.is_empty() => ColorStyle::secondary().into(),
+            EntryType::OPDSEntry(_) => ColorStyle::tertiary().into(),
+        };
+        lines
+            .into_iter()
+            .map(|l| StyledString::styled(l, style))
+            .collect()
+    }
+
+    /// Adds an entry to the end of the file view, one row for each line of its label.
+    ///
+    /// # Arguments
+    ///
+    /// * `select` - The file view.
+    /// * `entry` - Entry to add.
+    /// * `selection` - Files and folders picked for a batch operation.
+    /// * `wrapped_rows` - Rows that go on with a wrapped title; the entry's are added.
+    ///
+    fn add_entry(
+        &self,
+        select: &mut SelectView<EntryType>,
+        entry: EntryType,
+        selection: &HashSet<Url>,
+        wrapped_rows: &mut HashMap<usize, usize>,
+    ) {
+        let first = select.len();
+        for (i, line) in self.entry_label(&entry, selection).into_iter().enumerate() {
+            if i > 0 {
+                wrapped_rows.insert(select.len(), first);
+            }
+            select.add_item(line, entry.clone());
         }
     }
 
-    /// Main UI loop. Listens to messages from controller and updates UI accordingly.
+    /// Rebuilds the labels of the entries in the file view, i.e. after the downloads or the
+    /// width of the view changed. The same entry stays selected.
+    fn relabel_entries(&mut self) {
+        let Some(mut select) = self.cursive.find_name::<SelectView<EntryType>>("file_view") else {
+            return;
+        };
+
+        let selection = self.selection();
+        let old_rows = self
+            .cursive
+            .user_data::<UserData>()
+            .map(|d| std::mem::take(&mut d.wrapped_rows))
+            .unwrap_or_default();
+        let selected = select
+            .selected_id()
+            .map(|id| old_rows.get(&id).copied().unwrap_or(id));
+
+        // the rows that go on with a title are made again from its entry
+        let entries: Vec<(usize, EntryType)> = select
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !old_rows.contains_key(i))
+            .map(|(i, (_, entry))| (i, entry.clone()))
+            .collect();
+        select.clear();
+
+        let mut wrapped_rows = HashMap::new();
+        let mut new_selected = None;
+        for (i, entry) in entries {
+            if Some(i) == selected {
+                new_selected = Some(select.len());
+            }
+            self.add_entry(&mut select, entry, &selection, &mut wrapped_rows);
+        }
+        if let Some(id) = new_selected {
+            // the side panel already shows this entry
+            let _ = select.set_selection(id);
+        }
+
+        if let Some(data) = self.cursive.user_data::<UserData>() {
+            data.wrapped_rows = wrapped_rows;
+        }
+    }
+
+    /// Queues the next batch of hidden entries for the file view, followed by a "Show more" entry
+    /// if any are still left.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - Message shown below the file view once the entries are in.
+    ///
+    fn show_more_entries(&mut self, msg: String) {
+        let count = match self.max_visible_entries {
+            0 => self.hidden_entries.len(),
+            max => max.min(self.hidden_entries.len()),
+        };
+        let mut entries: Vec<EntryType> = self.hidden_entries.drain(..count).collect();
+        if !self.hidden_entries.is_empty() {
+            entries.push(EntryType::More(self.hidden_entries.len()));
+        }
+
+        self.pending_entries = Some(PendingEntries {
+            total: entries.len(),
+            entries: entries.into_iter(),
+            msg,
+        });
+        // the first entries are shown right away; the rest follow on later frames
+        self.add_pending_entries();
+    }
+
+    /// Adds the next few entries of the current page to the file view. While entries are left,
+    /// the message below the file view shows how far along it is.
+    fn add_pending_entries(&mut self) {
+        let Some(mut pending) = self.pending_entries.take() else {
+            return;
+        };
+
+        let selection = self.selection();
+        let mut select = self
+            .cursive
+            .find_name::<SelectView<EntryType>>("file_view")
+            .unwrap();
+        let mut wrapped_rows = HashMap::new();
+        for entry in pending.entries.by_ref().take(ENTRIES_PER_FRAME) {
+            self.add_entry(&mut select, entry, &selection, &mut wrapped_rows);
+        }
+        drop(select);
+        if let Some(data) = self.cursive.user_data::<UserData>() {
+            data.wrapped_rows.extend(wrapped_rows);
+        }
+
+        let mut msg_view = self.cursive.find_name::<TextView>("file_msg_view").unwrap();
+        if pending.entries.len() == 0 {
+            msg_view.set_content(&pending.msg);
+        } else {
+            msg_view.set_content(format!(
+                "Rendering {} of {} entries...",
+                pending.total - pending.entries.len(),
+                pending.total
+            ));
+            self.pending_entries = Some(pending);
+        }
+    }
+
+    /// Returns the local files and folders picked for a batch operation and shows how many there
+    /// are below the file view.
+    fn selection(&mut self) -> HashSet<Url> {
+        let selection = self
+            .cursive
+            .user_data::<UserData>()
+            .map(|d| d.selection.clone())
+            .unwrap_or_default();
+
+        let count = match selection.len() {
+            0 => String::new(),
+            n => format!("{} selected", n),
+        };
+        if let Some(mut view) = self.cursive.find_name::<TextView>("selection_view") {
+            view.set_content(count);
+        }
+        selection
+    }
+
+    /// Opens the theme file in `$VISUAL` or `$EDITOR`, falling back to `vi`. Cursive gives the
+    /// terminal back while the editor runs and takes it over again once it exits, after which the
+    /// theme is reloaded so the changes show right away.
     ///
     /// # Arguments
     ///
-    /// * `frame` - The frame we are currently on
+    /// * `preset` - Preset the theme file is applied on top of
     ///
-    pub fn step(&mut self, frame: u32) -> bool {
+    fn edit_theme(&mut self, preset: Option<String>) {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut args = editor.split_whitespace();
+        let program = args.next().unwrap_or("vi");
+
+        // dropping the runner's backend restores the terminal
+        let runner = std::mem::replace(
+            &mut self.cursive,
+            cursive::CursiveRunner::new(Cursive::new(), cursive::backend::Dummy::init()),
+        );
+        let siv = runner.into_inner();
+
+        let status = std::process::Command::new(program)
+            .args(args)
+            .arg(&self.theme_path)
+            .status();
+
+        self.cursive = cursive::CursiveRunner::new(
+            siv,
+            cursive::backends::try_default().expect("could not take back the terminal"),
+        );
+        if let Some(sixel) = &mut self.sixel {
+            sixel.invalidate();
+        }
+
+        let failure = match status {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("{} exited with {}", program, status)),
+            Err(err) => Some(format!("Could not start {}: {}", program, err)),
+        };
+        if let Some(err) = failure {
+            log::error!("{}", err);
+            self.ui_tx
+                .send(UIMessage::ShowInfo("Could not edit theme".to_string(), err))
+                .expect("failed to send UI message");
+        }
+
+        // reload even if the editor failed; it may have saved before exiting
+        self.ui_tx
+            .send(UIMessage::ApplyTheme(preset))
+            .expect("failed to send UI message");
+    }
+
+    /// Returns the label a connection currently has in the View menu.
+    fn connection_label(&self, name: &str) -> String {
+        self.connections
+            .get(name)
+            .map(|c| c.label.clone())
+            .unwrap_or(self.numbered_name(name))
+    }
+
+    /// Returns the connection's name preceded by the number key that switches to it, if it has
+    /// one.
+    fn numbered_name(&self, name: &str) -> String {
+        match self.connection_order.iter().position(|n| n == name) {
+            Some(i) if i < 9 => format!("{} {}", i + 1, name),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Main UI loop. Listens to messages from controller and updates UI accordingly.
+    pub fn step(&mut self) -> bool {
         if !self.cursive.is_running() {
             return false;
         }
@@ -233,53 +908,102 @@ impl UIRoot {
         let layer_sizes = self.cursive.screen().layer_sizes();
         let screen_size = layer_sizes.first().unwrap();
 
+        // whether anything on screen may have changed since the last frame
+        let mut changed = false;
+
         while let Some(message) = self.ui_rx.try_iter().next() {
+            changed = true;
             match message {
-                UIMessage::UpdateDirectoryView(title, items, msg) => {
-                    // refactor such that directory view is a struct that can access its fields
-                    // directly
-                    let mut select = self
-                        .cursive
-                        .find_name::<SelectView<EntryType>>("file_view")
-                        .unwrap();
+                UIMessage::UpdateDirectoryView(title, items, state) => {
+                    let msg = state.message(items.len());
+                    self.page_state = state;
 
-                    let mut title_view = self.cursive.find_name::<TextView>("title_view").unwrap();
-                    let mut msg_view = self.cursive.find_name::<TextView>("file_msg_view").unwrap();
-                    msg_view.set_content(&msg);
-
-                    if msg.is_empty() && items.is_empty() {
-                        msg_view.set_content("No files found.");
+                    // files that are no longer shown cannot stay selected
+                    if let Some(data) = self.cursive.user_data::<UserData>() {
+                        data.selection.retain(|u| {
+                            items.iter().any(|e| match e {
+                                EntryType::File(_, p, _) | EntryType::Directory(_, p, _) => p == u,
+                                _ => false,
+                            })
+                        });
                     }
 
-                    select.clear();
-                    for entry in items {
-                        let d = entry.clone();
-                        match entry {
-                            EntryType::File(title, _url) => select.add_item(title, d),
-                            EntryType::Directory(title, _url) => select.add_item(title, d),
-                            EntryType::OPDSEntry(e) => select.add_item(&e.title, d),
-                        }
+                    let mut title_view = self.cursive.find_name::<TextView>("title_view").unwrap();
+                    title_view.set_content(&title);
+
+                    // refactor such that directory view is a struct that can access its fields
+                    // directly
+                    self.cursive
+                        .find_name::<SelectView<EntryType>>("file_view")
+                        .unwrap()
+                        .clear();
+                    if let Some(data) = self.cursive.user_data::<UserData>() {
+                        data.wrapped_rows.clear();
                     }
 
-                    title_view.set_content(&title);
+                    self.hidden_entries = if self.sort_by_date {
+                        sorted_by_date(items)
+                    } else {
+                        with_group_headers(items)
+                    };
+                    self.show_more_entries(msg);
 
+                    let mut select = self
+                        .cursive
+                        .find_name::<SelectView<EntryType>>("file_view")
+                        .unwrap();
                     if !select.is_empty() {
                         let cb = select.set_selection(0);
+                        // the callback looks the view up again
+                        drop(select);
                         cb(&mut self.cursive);
                     }
                 }
+                UIMessage::LoadProgress(address, msg) => {
+                    // loading pages are titled with their address
+                    let loading = self.page_state == PageState::Loading
+                        && self
+                            .cursive
+                            .find_name::<TextView>("title_view")
+                            .unwrap()
+                            .get_content()
+                            .source()
+                            == address;
+                    if loading {
+                        self.cursive
+                            .find_name::<TextView>("file_msg_view")
+                            .unwrap()
+                            .set_content(msg);
+                    }
+                }
                 UIMessage::AddConnection(name, server, pwd) => {
+                    if !self.connection_order.contains(&name) {
+                        self.connection_order.push(name.clone());
+
+                        let number = self.connection_order.len();
+                        if number <= 9 {
+                            let key = char::from_digit(number as u32, 10).unwrap();
+                            let ctx = self.controller_tx.clone();
+                            let data = name.clone();
+                            self.cursive.add_global_callback(key, move |_| {
+                                ctx.send(ControllerMessage::ChangeConnection(data.clone()))
+                                    .expect("Failed to change to new connection");
+                            });
+                        }
+                    }
+
                     // update view tree
+                    let label = self.connection_label(&name);
                     let mb = self.cursive.menubar();
                     let st = mb.get_subtree(1).expect("View tree missing!");
 
-                    let leaf = st.find_item(&name);
+                    let leaf = st.find_item(&label);
 
                     if leaf.is_none() {
                         let data = name.clone();
                         let ctx = self.controller_tx.clone();
 
-                        st.add_leaf(name.clone(), move |_| {
+                        st.add_leaf(label, move |_| {
                             ctx.send(ControllerMessage::ChangeConnection(data.clone()))
                                 .expect("Failed to change to new connection");
                         });
@@ -292,12 +1016,58 @@ impl UIRoot {
                     let edit_leaf = et.find_item(&name);
                     if edit_leaf.is_none() {
                         et.add_leaf(name.clone(), move |s| {
-                            let diag = ui::serverinfomodal::new(edit_ctx.clone());
+                            let mut diag =
+                                ui::serverinfomodal::new(edit_ctx.clone(), Some(name.clone()));
+
+                            let clear_ctx = edit_ctx.clone();
+                            let clear_name = name.clone();
+                            diag.add_button("Clear cache", move |s| {
+                                clear_ctx
+                                    .send(ControllerMessage::ClearCache(clear_name.clone()))
+                                    .expect("failed to send controller message");
+                                s.pop_layer();
+                            });
+
                             s.add_layer(diag);
                             ui::serverinfomodal::populate_fields(s, &name, &server, pwd.clone());
                         });
                     }
                 }
+                UIMessage::RenameConnection(old, new) => {
+                    let old_label = self.connection_label(&old);
+                    self.connections.remove(&old);
+
+                    if let Some(i) = self.connection_order.iter().position(|n| *n == old) {
+                        self.connection_order[i] = new.clone();
+                        if i < 9 {
+                            let key = char::from_digit(i as u32 + 1, 10).unwrap();
+                            let ctx = self.controller_tx.clone();
+                            let data = new.clone();
+                            self.cursive.clear_global_callbacks(key);
+                            self.cursive.add_global_callback(key, move |_| {
+                                ctx.send(ControllerMessage::ChangeConnection(data.clone()))
+                                    .expect("Failed to change to new connection");
+                            });
+                        }
+                    }
+
+                    let label = self.connection_label(&new);
+                    let mb = self.cursive.menubar();
+                    let st = mb.get_subtree(1).expect("View tree missing!");
+                    if let Some(pos) = st.find_position(&old_label) {
+                        st.remove(pos);
+                        let ctx = self.controller_tx.clone();
+                        st.insert_leaf(pos, label, move |_| {
+                            ctx.send(ControllerMessage::ChangeConnection(new.clone()))
+                                .expect("Failed to change to new connection");
+                        });
+                    }
+
+                    let et = mb.get_subtree(2).expect("Edit tree missing!");
+                    if let Some(pos) = et.find_position(&old) {
+                        et.remove(pos);
+                    }
+                }
                 UIMessage::ShowInfo(title, err) => {
                     // remove any lingering dialogs before showing this one
                     let old_diag = self.cursive.find_name::<Dialog>("info_dialog");
@@ -306,12 +1076,202 @@ impl UIRoot {
                         self.cursive.pop_layer();
                     }
 
-                    let dialog = Dialog::info(&err).title(title).with_name("info_dialog");
+                    let mut dialog = Dialog::info(&err).title(title);
+
+                    // only offer copying if there is something to copy with
+                    if clipboard_command().is_some() {
+                        dialog.add_button("Copy", move |_| {
+                            if let Err(e) = copy_to_clipboard(&err) {
+                                log::error!("{}", e);
+                            }
+                        });
+                    }
+
+                    self.cursive.add_layer(dialog.with_name("info_dialog"));
+                }
+                UIMessage::Confirm(title, question, on_yes) => {
+                    let ctx = self.controller_tx.clone();
+                    let dialog = Dialog::text(question)
+                        .title(title)
+                        .button("Yes", move |s| {
+                            ctx.send(on_yes.clone())
+                                .expect("failed to send controller message");
+                            s.pop_layer();
+                        })
+                        .dismiss_button("No");
                     self.cursive.add_layer(dialog);
                 }
+                UIMessage::PassphrasePrompt(exists) => {
+                    let title = if exists {
+                        "Enter the passphrase for your saved passwords"
+                    } else {
+                        "Choose a passphrase to protect saved passwords"
+                    };
+
+                    let ctx = self.controller_tx.clone();
+                    let d = input_dialog(
+                        title,
+                        move |passphrase| {
+                            ctx.send(ControllerMessage::Unlock(passphrase))
+                                .expect("failed to send controller message");
+                        },
+                        true,
+                    );
+                    self.cursive.add_layer(d);
+                }
+                UIMessage::LastRefresh(time, anonymous) => {
+                    let mut content = match time {
+                        Some(t) => format!("updated {}", time_ago(t)),
+                        None => String::new(),
+                    };
+                    if anonymous {
+                        content = if content.is_empty() {
+                            String::from("anonymous")
+                        } else {
+                            format!("{} · anonymous", content)
+                        };
+                    }
+                    self.cursive
+                        .call_on_name("refresh_view", |v: &mut TextView| v.set_content(content));
+                }
+                UIMessage::DownloadedFiles(files) => {
+                    self.downloaded = files;
+                    self.relabel_entries();
+                }
+                UIMessage::SelectionChanged() => {
+                    self.relabel_entries();
+                }
+                UIMessage::ApplyTheme(preset) => {
+                    let theme = match theme_toml(preset.as_deref(), &self.theme_path) {
+                        Ok(theme) => theme,
+                        Err(err) => {
+                            log::error!("could not build the theme: {}", err);
+                            continue;
+                        }
+                    };
+                    if let Err(err) = self.cursive.load_toml(&theme) {
+                        log::error!("could not load the theme: {:?}", err);
+                    }
+                    // setting the theme clears the terminal, cover included
+                    if let Some(sixel) = &mut self.sixel {
+                        sixel.invalidate();
+                    }
+                }
+                UIMessage::ShowRawPage(url, body) => {
+                    self.cursive
+                        .add_layer(reader(&format!("Sent for {}", url), &body));
+                }
+                UIMessage::RecentDownloads(downloads, toggle) => {
+                    let layer = self
+                        .cursive
+                        .screen_mut()
+                        .find_layer_from_name("recent_downloads");
+                    match (layer, toggle) {
+                        (Some(position), true) => {
+                            self.cursive.screen_mut().remove_layer(position);
+                        }
+                        (Some(_), false) => fill_recent_downloads(&mut self.cursive, downloads),
+                        (None, true) => {
+                            let d = downloads_dialog(self.controller_tx.clone());
+                            self.cursive.add_layer(d);
+                            fill_recent_downloads(&mut self.cursive, downloads);
+                        }
+                        (None, false) => {}
+                    }
+                }
+                UIMessage::EditTheme(preset) => {
+                    self.edit_theme(preset);
+                }
+                UIMessage::ShowMoreEntries() => {
+                    // "Show more" is only added once every entry before it is in
+                    if self.pending_entries.is_some() {
+                        continue;
+                    }
+
+                    let mut select = self
+                        .cursive
+                        .find_name::<SelectView<EntryType>>("file_view")
+                        .unwrap();
+                    if !matches!(select.iter().last(), Some((_, EntryType::More(_)))) {
+                        continue;
+                    }
+                    let index = select.len() - 1;
+                    select.remove_item(index);
+                    drop(select);
+
+                    let msg = self
+                        .cursive
+                        .find_name::<TextView>("file_msg_view")
+                        .unwrap()
+                        .get_content()
+                        .source()
+                        .to_string();
+                    self.show_more_entries(msg);
+
+                    // the first of the new entries takes the place of "Show more"
+                    let cb = self
+                        .cursive
+                        .find_name::<SelectView<EntryType>>("file_view")
+                        .unwrap()
+                        .set_selection(index);
+                    cb(&mut self.cursive);
+                }
+                UIMessage::ConnectionTest(result) => {
+                    let msg = match &result {
+                        None => format!("Testing {}", SPINNER[0]),
+                        Some(Ok(msg)) => msg.clone(),
+                        Some(Err(err)) => format!("Failed: {}", err),
+                    };
+                    self.testing_connection = result.is_none();
+
+                    if let Some(mut status) = self.cursive.find_name::<TextView>("test_status") {
+                        status.set_content(msg);
+                    }
+                }
+                UIMessage::Quit() => {
+                    self.cursive.quit();
+                }
+                UIMessage::ToggleDetails() => {
+                    self.details_hidden = !self.details_hidden;
+                    self.resize_panels();
+                }
+                UIMessage::SortByDate(sort) => {
+                    self.sort_by_date = sort;
+                }
+                UIMessage::DismissNotifications() => {
+                    // only notification layers are removed, open dialogs stay where they are
+                    let screen = self.cursive.screen_mut();
+                    for n in self.notifications.drain(..) {
+                        if let Some(p) = screen.find_layer_from_name(&n.id) {
+                            screen.remove_layer(p);
+                        }
+                    }
+                }
+                UIMessage::SearchPrompt(history) => {
+                    let d = search_dialog(self.controller_tx.clone(), history);
+                    self.cursive.add_layer(d);
+                }
+                UIMessage::AddressPrompt(current) => {
+                    let d = address_dialog(self.controller_tx.clone(), current);
+                    self.cursive.add_layer(d);
+                }
+                UIMessage::ConnectionHealth(name, health) => {
+                    self.set_connection_health(&name, health);
+                }
+                UIMessage::ConnectionStatus(name, status) => {
+                    self.set_connection_status(&name, status);
+                }
+                UIMessage::ConnectionSearchable(name, searchable) => {
+                    self.set_connection_searchable(&name, searchable);
+                }
                 UIMessage::ShowNotification(title, content) => {
-                    let id = notification(&mut self.cursive, &title, &content, screen_size);
-                    self.notifications.push((frame, id));
+                    let (id, size) = notification(&mut self.cursive, &title, &content);
+                    self.notifications.push(Notification {
+                        shown_at: Instant::now(),
+                        id,
+                        size,
+                    });
+                    self.layout_notifications();
                 }
                 UIMessage::ShowContextMenu(title, entries) => {
                     let ctx = self.controller_tx.clone();
@@ -344,12 +1304,31 @@ impl UIRoot {
                         },
                     );
 
-                    for e in entries {
-                        select.add_item(e.0, e.1);
+                    // only the first nine items can be picked by number; the rest line up
+                    for (i, e) in entries.into_iter().enumerate() {
+                        let label = match i {
+                            0..=8 => format!("{} {}", i + 1, e.0),
+                            _ => format!("  {}", e.0),
+                        };
+                        select.add_item(label, e.1);
                     }
 
-                    self.cursive
-                        .add_layer(Dialog::around(NamedView::new("popup", select)).title(&title));
+                    let mut menu = OnEventView::new(NamedView::new("popup", select));
+                    // digits switch connections otherwise, so they are all taken while the menu is
+                    // open
+                    for digit in '0'..='9' {
+                        menu.set_on_event_inner(digit, move |v, _| {
+                            let index = digit.to_digit(10)? as usize;
+                            let mut select = v.get_mut();
+                            if index == 0 || index > select.len() {
+                                return Some(EventResult::Consumed(None));
+                            }
+                            select.set_selection(index - 1);
+                            Some(select.on_event(Event::Key(Key::Enter)))
+                        });
+                    }
+
+                    self.cursive.add_layer(Dialog::around(menu).title(&title));
                 }
                 UIMessage::StoreImage(title, image_data) => {
                     let select = self
@@ -372,63 +1351,167 @@ impl UIRoot {
                         canvas.from_image(&image_data);
                     }
 
-                    self.cursive
-                        .with_user_data(|id: &mut HashMap<String, DynamicImage>| {
-                            id.insert(title.clone(), image_data.clone())
-                        });
+                    self.cursive.with_user_data(|data: &mut UserData| {
+                        data.images.insert(title.clone(), image_data.clone())
+                    });
                 }
-                UIMessage::PasswordPrompt(name, s) => {
+                UIMessage::PasswordPrompt(name, s, auth) => {
                     let ctx = self.controller_tx.clone();
                     let server = s.clone();
-                    let title = format!(
-                        "Please enter a password for {}@{}",
-                        s.username.unwrap(),
-                        s.base_url
-                    );
+                    let cancelled = ControllerMessage::LoginCancelled(name.clone());
 
-                    let d = input_dialog(
-                        &title,
-                        move |pwd| {
-                            ctx.send(ControllerMessage::AddConnection(
-                                name.clone(),
-                                server.clone(),
-                                Some(pwd.to_string()),
-                            ))
-                            .expect("Failed to update connection");
-                        },
-                        true,
-                    );
+                    let mut d = match &s.username {
+                        Some(u) => {
+                            let title = match &auth {
+                                Some(a) => format!(
+                                    "{}: please enter your {} for {}@{}",
+                                    a.title,
+                                    a.password_label.to_lowercase(),
+                                    u,
+                                    s.base_url
+                                ),
+                                None => {
+                                    format!("Please enter a password for {}@{}", u, s.base_url)
+                                }
+                            };
+
+                            input_dialog(
+                                &title,
+                                move |pwd| {
+                                    ctx.send(ControllerMessage::AddConnection(
+                                        name.clone(),
+                                        server.clone(),
+                                        Some(pwd.to_string()),
+                                    ))
+                                    .expect("Failed to update connection");
+                                },
+                                true,
+                            )
+                        }
+                        // the server asked for credentials we never had, so ask for both
+                        None => {
+                            let (title, login_label, password_label) = match &auth {
+                                Some(a) => (
+                                    format!("{} requires you to log in", a.title),
+                                    a.login_label.clone(),
+                                    a.password_label.clone(),
+                                ),
+                                None => (
+                                    format!("{} requires you to log in", s.base_url),
+                                    String::from("Username"),
+                                    String::from("Password"),
+                                ),
+                            };
 
+                            credentials_dialog(
+                                &title,
+                                &login_label,
+                                &password_label,
+                                move |login, pwd| {
+                                    let mut server = server.clone();
+                                    server.username = Some(login);
+                                    ctx.send(ControllerMessage::AddConnection(
+                                        name.clone(),
+                                        server,
+                                        Some(pwd),
+                                    ))
+                                    .expect("Failed to update connection");
+                                },
+                            )
+                        }
+                    };
+
+                    // the connection is shown as failed once nobody is going to log in
+                    let ctx = self.controller_tx.clone();
+                    d.remove_button(1);
+                    d.add_button("Cancel", move |siv| {
+                        ctx.send(cancelled.clone())
+                            .expect("failed to send controller message");
+                        siv.pop_layer();
+                    });
                     self.cursive.add_layer(d);
                 }
             }
         }
 
-        // clears lingering notifications after 5 seconds
+        // keep the spinners of connections that are still connecting moving
+        let connecting: Vec<String> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| c.status == ConnectionStatus::Connecting)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let step = self.started.elapsed().as_millis() / SPINNER_STEP.as_millis();
+        let spinner = SPINNER[step as usize % SPINNER.len()];
+        changed |= (!connecting.is_empty() || self.testing_connection) && spinner != self.spinner;
+        self.spinner = spinner;
+        for name in connecting {
+            self.set_connection_status(&name, ConnectionStatus::Connecting);
+        }
+        if self.testing_connection {
+            if let Some(mut status) = self.cursive.find_name::<TextView>("test_status") {
+                status.set_content(format!("Testing {}", spinner));
+            }
+        }
+
+        // clears lingering notifications
         let screen = self.cursive.screen_mut(); // reference to StackView
-        for (last_rendered, n_id) in &self.notifications {
-            // fps * time in seconds
-            if frame - last_rendered > 30 * 5 {
-                let pos = screen.find_layer_from_name(n_id);
-                if let Some(p) = pos {
-                    screen.remove_layer(p);
-                }
+        let count = self.notifications.len();
+        self.notifications.retain(|n| {
+            if n.shown_at.elapsed() <= NOTIFICATION_TIME {
+                return true;
+            }
+
+            if let Some(p) = screen.find_layer_from_name(&n.id) {
+                screen.remove_layer(p);
             }
+            false
+        });
+
+        if self.notifications.len() != count {
+            changed = true;
+            self.layout_notifications();
         }
 
+        changed |= self.pending_entries.is_some();
+        self.add_pending_entries();
         self.update_size(screen_size.x, screen_size.y);
-        self.cursive.step();
+
+        // without autorefresh, the screen is still redrawn once a second in case a change was
+        // missed
+        if self.cursive.fps().is_none() && (self.fallback_redraw.due() || changed) {
+            self.cursive.refresh();
+        }
+        let received = self.cursive.step();
+        if let Some(sixel) = &mut self.sixel {
+            sixel.render(&mut self.cursive, changed || received);
+        }
         true
     }
 }
 
-fn about_screen() -> Dialog {
-    let tc = TextContent::new(
-                    "ncopds: A TUI program for OPDS catalogs\n\nHotkeys:\no - Open file in local view mode\nd - Delete file in local view mode\nr - Rename file in local view mode\n/ - Open search if connection supports it\n? - Opens this screen\n Rostyslav Hnatyshyn 2023-2024",
-                );
+/// Builds the help screen. The hotkey table is generated from the keybindings so it always matches
+/// what is actually registered.
+///
+/// # Arguments
+///
+/// * `bindings` - Current keybindings.
+///
+pub fn about_screen(bindings: &Keybindings) -> Dialog {
+    let mut content = String::from("ncopds: A TUI program for OPDS catalogs\n\nHotkeys:\n");
+
+    for action in Action::ALL {
+        let keys = keys_for(bindings, action);
+        if !keys.is_empty() {
+            content += &format!("{:<16}{}\n", keys, action.description());
+        }
+    }
+
+    content += "\nRostyslav Hnatyshyn 2023-2024";
+
     Dialog::new()
         .title("About ncopds")
-        .content(TextView::new_with_content(tc))
+        .content(TextView::new_with_content(TextContent::new(content)))
         .button("Ok", move |s| {
             s.pop_layer();
         })
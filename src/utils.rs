@@ -1,11 +1,19 @@
+use crate::config::VerifyDownloads;
+use chrono::{DateTime, Utc};
+use cursive::utils::lines::simple::{make_lines, simple_prefix};
 use infer;
+use std::cmp::Ordering;
 use std::error::Error;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use url::Url;
 
+/// Appended to the name of a file while a download is writing it.
+pub const PARTIAL_SUFFIX: &str = ".ncopds-part";
+
 /// Returns the contents of a directory.
 ///
 /// # Arguments
@@ -25,40 +33,90 @@ pub fn read_dir(file_path: &Url) -> Result<Vec<String>, Box<dyn Error>> {
 }
 
 /// Saves bytes in a file specified by dir and fname. Checks magic bytes using
-/// [infer](https://docs.rs/infer/latest/infer/) and errors out if extension doesn't match the
-/// filetype given by the magic bytes.
+/// [infer](https://docs.rs/infer/latest/infer/) against the extension of the filename; what
+/// happens when they do not match depends on `verify`. Returns the path of the saved file, along
+/// with a warning if the file was saved despite not matching.
 ///
 /// # Arguments
 ///
 /// * `data` - Bytes containing file data
 /// * `dir` - Directory to save the file in
 /// * `fname` - Filename
+/// * `verify` - What to do when the extension does not match the magic bytes
 ///
 /// # Errors
 ///
-/// Can error out on file creation, joining directory with filename or when the file extension of
-/// the filename does not match the magic bytes in the file.
+/// Can error out on file creation, joining directory with filename or, when `verify` is strict,
+/// when the file extension of the filename does not match the magic bytes in the file.
 ///
 /// ```
-pub fn save_as(data: bytes::Bytes, dir: &Url, fname: &str) -> Result<(), Box<dyn Error>> {
+pub fn save_as(
+    data: bytes::Bytes,
+    dir: &Url,
+    fname: &str,
+    verify: VerifyDownloads,
+) -> Result<(PathBuf, Option<String>), Box<dyn Error>> {
     let full_fname = Url::join(dir, fname).unwrap().to_file_path().unwrap();
 
-    // move extension testing into fn, test
-    let ext = full_fname.extension();
-    let kind = infer::get(&data).expect("file type is known");
+    // files without magic bytes, i.e. plain text, cannot be checked
+    let ext = full_fname
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    let mismatch = infer::get(&data)
+        .map(|kind| kind.extension())
+        .filter(|kind| ext.as_deref() != Some(*kind) && verify != VerifyDownloads::Off);
 
-    if kind.extension() != ext.unwrap() {
-        return Err(format!(
-            "Could not save {}. File was not downloaded properly. File was returned from the server as a {}",
-            fname,
-            kind.extension()
-        )
-        .into());
-    }
+    let warning = match mismatch {
+        Some(kind) if verify == VerifyDownloads::Strict => {
+            return Err(format!(
+                "Could not save {}. File was not downloaded properly. File was returned from the server as a {}",
+                fname,
+                kind
+            )
+            .into());
+        }
+        Some(kind) => {
+            log::warn!("{} was returned from the server as a {}", fname, kind);
+            Some(format!(
+                "Saved {}, but it was returned from the server as a {}; it may not open.",
+                fname, kind
+            ))
+        }
+        None => None,
+    };
 
-    let mut file = File::create(&full_fname)?;
-    let _ = file.write(&data);
-    Ok(())
+    // written next to the final name first, so an interrupted write never looks like a book
+    let mut partial = full_fname.clone().into_os_string();
+    partial.push(PARTIAL_SUFFIX);
+
+    let mut file = File::create(&partial)?;
+    file.write_all(&data)?;
+    fs::rename(&partial, &full_fname)?;
+    Ok((full_fname, warning))
+}
+
+/// Removes files that downloads were still writing into the directory when they were stopped.
+///
+/// # Arguments
+///
+/// * `dir` - Directory downloads are saved in.
+///
+pub fn remove_partial_downloads(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(PARTIAL_SUFFIX)
+        {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                log::error!("could not remove {}: {}", entry.path().display(), err);
+            }
+        }
+    }
 }
 
 /// Converts a string file path to a URL.
@@ -104,14 +162,226 @@ pub fn directory_str_to_url(directory: &str) -> Result<Url, Box<dyn Error>> {
     let init_dir = str_to_file_url(directory)?;
 
     if !file_url_is_dir(&init_dir) {
-        return Err(format!("{} is not a directory.", directory).into());
+        return Err(directory_problem(Path::new(directory)).into());
     }
 
     Ok(init_dir)
 }
 
+/// Checks that downloads can be saved in a directory. Only its permissions are looked at, so
+/// nothing shows up in the directory's watcher.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to check.
+///
+/// # Errors
+///
+/// Errors with a message meant for the user if the directory does not exist, is not a
+/// directory or cannot be written to.
+///
+pub fn check_writable(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !dir.is_dir() {
+        return Err(directory_problem(dir).into());
+    }
+
+    match can_create_files(dir) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(format!(
+            "Download directory {} is not writable ({}). Check its permissions or set \
+             download_directory in config.toml to another folder.",
+            dir.display(),
+            err
+        )
+        .into()),
+    }
+}
+
+/// Asks the system whether this process may create files in a directory.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to check.
+///
+#[cfg(unix)]
+fn can_create_files(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+    // creating a file takes write and search permission on the directory
+    match unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+fn can_create_files(dir: &Path) -> std::io::Result<()> {
+    if fs::metadata(dir)?.permissions().readonly() {
+        return Err(std::io::ErrorKind::PermissionDenied.into());
+    }
+    Ok(())
+}
+
+/// Describes why a path cannot be used as the download directory.
+///
+/// # Arguments
+///
+/// * `dir` - Path that is not a directory.
+///
+fn directory_problem(dir: &Path) -> String {
+    if dir.exists() {
+        format!(
+            "Download directory {} is not a directory. Set download_directory in config.toml \
+             to a folder.",
+            dir.display()
+        )
+    } else {
+        format!(
+            "Download directory {} does not exist. Create it or set download_directory in \
+             config.toml to another folder.",
+            dir.display()
+        )
+    }
+}
+
+/// Checks whether a file looks like an Atom / OPDS feed, based on its extension and whether its
+/// beginning contains a feed element.
+///
+/// # Arguments
+///
+/// * `path` - Path to the file.
+///
+pub fn is_feed_file(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    if !matches!(ext.as_deref(), Some("xml" | "atom" | "opds")) {
+        return false;
+    }
+
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+
+    let mut head = [0; 1024];
+    let read = file.read(&mut head).unwrap_or(0);
+    String::from_utf8_lossy(&head[..read]).contains("<feed")
+}
+
+/// Reads the cover of a comic book archive, which is its first page. Pages are ordered by name the
+/// way a person would, so `page2.jpg` comes before `page10.jpg`. Only CBZ (zip) archives can be
+/// read; other files have no cover.
+///
+/// # Arguments
+///
+/// * `path` - Path to the archive.
+///
+/// # Errors
+///
+/// Errors if the file cannot be read or is not a valid archive.
+///
+pub fn comic_cover(path: &Path) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    if ext.as_deref() != Some("cbz") {
+        return Ok(None);
+    }
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let first_page = archive
+        .file_names()
+        .filter(|n| is_page(n))
+        .min_by(|a, b| natural_cmp(a, b))
+        .map(|n| n.to_string());
+
+    let Some(name) = first_page else {
+        return Ok(None);
+    };
+
+    let mut page = vec![];
+    archive.by_name(&name)?.read_to_end(&mut page)?;
+    Ok(Some(page))
+}
+
+/// Checks whether an entry of a comic book archive is a page, skipping the metadata that some
+/// tools leave behind, i.e. `__MACOSX/` folders.
+///
+/// # Arguments
+///
+/// * `name` - Path of the entry inside the archive.
+///
+fn is_page(name: &str) -> bool {
+    let path = Path::new(name);
+    let hidden = path.components().any(|c| {
+        let c = c.as_os_str().to_string_lossy();
+        c.starts_with('.') || c == "__MACOSX"
+    });
+
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    !hidden
+        && matches!(
+            ext.as_deref(),
+            Some("jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp")
+        )
+}
+
+/// Compares two names, treating runs of digits as numbers and ignoring case.
+///
+/// # Arguments
+///
+/// * `a` - First name.
+/// * `b` - Second name.
+///
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(d) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(d);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+
+                // without leading zeros, the longer number is the larger one
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
 /// Renames a file at old_path with the name in new_path. new_path is just the filename, the
-/// function uses the parent directory of old_path to correctly rename the file.
+/// function uses the parent directory of old_path to correctly rename the file. Returns the full
+/// path of the renamed file.
 ///
 /// # Arguments
 ///
@@ -122,11 +392,110 @@ pub fn directory_str_to_url(directory: &str) -> Result<Url, Box<dyn Error>> {
 /// Error could get thrown if the operation fails.
 ///
 ///
-pub fn rename_full_dir_fname(old_path: PathBuf, new_path: PathBuf) -> Result<(), Box<dyn Error>> {
+pub fn rename_full_dir_fname(
+    old_path: PathBuf,
+    new_path: PathBuf,
+) -> Result<PathBuf, Box<dyn Error>> {
     // is this necessary though?
     let folder = old_path.parent().expect("we should be inside a folder");
     let np = folder.join(&new_path);
-    std::fs::rename(old_path, np)?;
+    std::fs::rename(old_path, &np)?;
+    Ok(np)
+}
+
+/// Moves a file or directory. Renaming does not work across filesystems, so in that case files
+/// are copied to the destination and the original is removed.
+///
+/// # Arguments
+///
+/// * `from` - Path to move.
+/// * `to` - Full destination path, including the filename.
+///
+/// # Errors
+///
+/// Errors if the move fails or a directory has to be moved to a different filesystem.
+///
+pub fn move_path(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    match fs::rename(from, to) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            if from.is_dir() {
+                return Err("Cannot move folders between filesystems.".into());
+            }
+            fs::copy(from, to)?;
+            fs::remove_file(from)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns a path inside `dir` for `name` that does not exist yet. If `name` is taken, a number
+/// is added to it, i.e. "book.epub" -> "book (1).epub".
+///
+/// # Arguments
+///
+/// * `dir` - Directory the path should be in.
+/// * `name` - Preferred filename.
+///
+pub fn unique_path(dir: &Path, name: &std::ffi::OsStr) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = name
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut i = 1;
+    loop {
+        let candidate = dir.join(format!("{} ({}){}", stem, i, ext));
+        if !candidate.exists() {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+/// Moves a file or directory into the trash folder, creating the folder if needed. Returns where
+/// the file ended up.
+///
+/// # Arguments
+///
+/// * `path` - Path to throw away.
+/// * `trash` - Trash folder.
+///
+/// # Errors
+///
+/// Errors if the trash folder cannot be created or the file cannot be moved into it.
+///
+pub fn move_to_trash(path: &Path, trash: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(trash)?;
+    let dest = unique_path(trash, path.file_name().ok_or("Path has no filename.")?);
+    move_path(path, &dest)?;
+    Ok(dest)
+}
+
+/// Copies a file.
+///
+/// # Arguments
+///
+/// * `from` - File to copy.
+/// * `to` - Full destination path, including the filename.
+///
+/// # Errors
+///
+/// Errors if the copy fails or if `from` is a directory.
+///
+pub fn copy_path(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if from.is_dir() {
+        return Err("Copying folders is not supported.".into());
+    }
+    fs::copy(from, to)?;
     Ok(())
 }
 
@@ -144,6 +513,11 @@ pub fn rename_full_dir_fname(old_path: PathBuf, new_path: PathBuf) -> Result<(),
 ///
 pub fn parse_href(href: &str, base_url: &Url) -> Result<Url, url::ParseError> {
     Ok(match Url::parse(href) {
+        // "localhost:8080/cover.jpg" reads as a URL with the scheme "localhost"; feeds that leave
+        // out the scheme mean the host, with the scheme of the feed
+        Ok(res) if res.scheme() == "localhost" => {
+            Url::parse(&format!("{}://{}", base_url.scheme(), href))?
+        }
         Ok(res) => res,
         Err(e) => match e {
             url::ParseError::RelativeUrlWithoutBase => Url::join(base_url, href)?,
@@ -181,3 +555,452 @@ pub fn extract_filename_from_content_disposition(
             .replace("%20", " "),
     )
 }
+
+/// Formats a number of bytes using the largest fitting unit, i.e. 1536 -> "1.5 KB".
+///
+/// # Arguments
+///
+/// * `bytes` - Size in bytes.
+///
+pub fn human_readable_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[unit])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// Opens a file with a command from the config. The command is split on whitespace and every
+/// `{}` in it is replaced with the path; without one, the path is added at the end. The command
+/// runs in the background.
+///
+/// # Arguments
+///
+/// * `template` - Command to run, i.e. "zathura --fork {}".
+/// * `path` - File to open.
+///
+/// # Errors
+///
+/// Errors if the command is empty or cannot be started.
+///
+pub fn open_with(template: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    let path = path.to_string_lossy();
+    let mut parts: Vec<String> = template
+        .split_whitespace()
+        .map(|p| p.replace("{}", &path))
+        .collect();
+    if !template.contains("{}") {
+        parts.push(path.to_string());
+    }
+
+    let (program, args) = parts.split_first().ok_or("Open command is empty.")?;
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Could not run {}: {}", program, err))?;
+
+    // reaped in the background so the UI does not wait for the reader to close
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Shortens a link so it can be shared, i.e. in a bug report: credentials and fragments are left
+/// out, and the values of query parameters, which often carry tokens, are replaced with "…".
+/// Relative links are kept relative.
+///
+/// # Arguments
+///
+/// * `href` - Link to redact.
+///
+pub fn redact_href(href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let (path, query) = match href.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (href, None),
+    };
+
+    let mut redacted = match Url::parse(path) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => path.to_string(),
+    };
+    if let Some(query) = query {
+        let params: Vec<String> = query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.split_once('=') {
+                Some((name, _)) => format!("{}=…", name),
+                None => "…".to_string(),
+            })
+            .collect();
+        redacted.push('?');
+        redacted.push_str(&params.join("&"));
+    }
+    redacted
+}
+
+/// Finds a command line tool that can write to the system clipboard. Returns the program and its
+/// arguments, or None if no supported tool is installed.
+pub fn clipboard_command() -> Option<(&'static str, Vec<&'static str>)> {
+    let candidates: [(&str, Vec<&str>, Option<&str>); 4] = [
+        ("wl-copy", vec![], Some("WAYLAND_DISPLAY")),
+        ("xclip", vec!["-selection", "clipboard"], Some("DISPLAY")),
+        ("xsel", vec!["--clipboard", "--input"], Some("DISPLAY")),
+        ("pbcopy", vec![], None),
+    ];
+
+    let path = std::env::var_os("PATH")?;
+
+    candidates
+        .into_iter()
+        // clipboard tools for a display server are useless without that display server
+        .filter(|(_, _, display)| display.is_none_or(|d| std::env::var_os(d).is_some()))
+        .find(|(program, _, _)| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .map(|(program, args, _)| (program, args))
+}
+
+/// Puts text on the system clipboard using whichever clipboard tool is available.
+///
+/// # Arguments
+///
+/// * `text` - Text to copy.
+///
+/// # Errors
+///
+/// Errors if no clipboard tool is available or it fails to run.
+///
+pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn Error>> {
+    let (program, args) = clipboard_command().ok_or("No clipboard tool is available.")?;
+
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    child
+        .stdin
+        .take()
+        .ok_or("Could not write to the clipboard tool.")?
+        .write_all(text.as_bytes())?;
+
+    if !child.wait()?.success() {
+        return Err(format!("{} failed to copy to the clipboard.", program).into());
+    }
+    Ok(())
+}
+
+/// Describes how long ago a moment was in the largest whole unit, i.e. "2m ago".
+///
+/// # Arguments
+///
+/// * `then` - Moment in the past.
+///
+pub fn time_ago(then: DateTime<Utc>) -> String {
+    let seconds = (Utc::now() - then).num_seconds().max(0);
+
+    match seconds {
+        0..=9 => "just now".to_string(),
+        10..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// Shortens text to fit into a number of cells, ending it with "…" if anything was cut off.
+///
+/// # Arguments
+///
+/// * `text` - Text to shorten.
+/// * `width` - Number of cells available.
+///
+pub fn ellipsize(text: &str, width: usize) -> String {
+    if simple_prefix(text, width).length == text.len() {
+        return text.to_string();
+    }
+
+    let keep = simple_prefix(text, width.saturating_sub(1)).length;
+    format!("{}…", &text[..keep])
+}
+
+/// Breaks text into lines that fit into a number of cells, between words where possible. Lines
+/// after the first are indented so they read as part of the same title.
+///
+/// # Arguments
+///
+/// * `text` - Text to break up.
+/// * `width` - Number of cells available.
+///
+pub fn wrap_title(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let Some(first) = make_lines(text, width).into_iter().next() else {
+        return vec![text.to_string()];
+    };
+
+    let mut lines = vec![text[first.start..first.end].trim_end().to_string()];
+    let rest = text[first.end..].trim_start();
+    let indent = if width > 2 { "  " } else { "" };
+    for row in make_lines(rest, width - indent.len()) {
+        lines.push(format!("{}{}", indent, rest[row.start..row.end].trim_end()));
+    }
+    lines
+}
+
+/// Turns HTML into plain text that reads well in the terminal. Tags are dropped, block elements
+/// and line breaks start new lines, list items get a bullet, scripts and styles are left out and
+/// the common character references are decoded.
+///
+/// # Arguments
+///
+/// * `html` - HTML to convert.
+///
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    // text inside these elements is not meant to be read
+    let mut skip_until: Option<&str> = None;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            if skip_until.is_none() {
+                push_text(&mut text, rest);
+            }
+            break;
+        };
+        if skip_until.is_none() {
+            push_text(&mut text, &rest[..start]);
+        }
+
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |e| start + e + 1);
+        let tag = rest[start + 1..end].trim_end_matches('>').trim();
+        rest = &rest[end..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if let Some(skipped) = skip_until {
+            if closing && name == skipped {
+                skip_until = None;
+            }
+            continue;
+        }
+
+        match name.as_str() {
+            "script" if !closing => skip_until = Some("script"),
+            "style" if !closing => skip_until = Some("style"),
+            "br" => push_break(&mut text, 1),
+            "li" if !closing => {
+                push_break(&mut text, 1);
+                text.push_str("• ");
+            }
+            "p" | "div" | "blockquote" | "pre" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul"
+            | "ol" | "table" | "hr" => push_break(&mut text, 2),
+            "tr" | "li" | "dt" | "dd" => push_break(&mut text, 1),
+            _ => {}
+        }
+    }
+
+    decode_entities(text.trim())
+}
+
+/// Adds text from between two tags, collapsing its whitespace the way a browser would.
+fn push_text(text: &mut String, run: &str) {
+    if run.starts_with(char::is_whitespace) && !text.is_empty() && !text.ends_with(['\n', ' ']) {
+        text.push(' ');
+    }
+
+    let words = run.split_whitespace().collect::<Vec<&str>>().join(" ");
+    text.push_str(&words);
+    if !words.is_empty() && run.ends_with(char::is_whitespace) {
+        text.push(' ');
+    }
+}
+
+/// Ends the current line so that at most `lines` line breaks follow the text.
+fn push_break(text: &mut String, lines: usize) {
+    while text.ends_with(' ') {
+        text.pop();
+    }
+    if text.is_empty() {
+        return;
+    }
+    let present = text.len() - text.trim_end_matches('\n').len();
+    for _ in present..lines {
+        text.push('\n');
+    }
+}
+
+/// Replaces the common named and every numeric character reference with the character.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let reference = rest[1..]
+            .find(';')
+            .filter(|&e| e <= 10)
+            .map(|e| &rest[1..e + 1]);
+        let c = reference.and_then(|r| match r {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "hellip" => Some('…'),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            _ => {
+                let number = r.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+
+        match (reference, c) {
+            (Some(r), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[r.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Tells when something that is done every so often is due again. Goes by the clock, since
+/// how many frames are drawn a second depends on `redraw_rate`.
+pub struct Periodic {
+    period: Duration,
+    last: Option<Instant>,
+}
+
+impl Periodic {
+    pub fn new(period: Duration) -> Self {
+        Periodic { period, last: None }
+    }
+
+    /// Whether the period has passed since this last returned true. It is due the first time
+    /// it is asked.
+    pub fn due(&mut self) -> bool {
+        let now = Instant::now();
+        if self.last.is_some_and(|last| now - last < self.period) {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::scratch_dir;
+
+    #[test]
+    fn writable_directories_are_left_untouched() {
+        let dir = scratch_dir("writable", &[]);
+        check_writable(&dir).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unusable_directories_say_why() {
+        let dir = scratch_dir("unusable", &[]);
+        let file = dir.join("book.epub");
+        File::create(&file).unwrap();
+
+        let missing = check_writable(&dir.join("missing")).unwrap_err();
+        assert!(missing.to_string().contains("does not exist"));
+        let not_dir = check_writable(&file).unwrap_err();
+        assert!(not_dir.to_string().contains("is not a directory"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_directories_are_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // root may write anywhere
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = scratch_dir("read-only", &[]);
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        let err = check_writable(&dir).unwrap_err();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(err.to_string().contains("is not writable"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn periodic_goes_by_the_clock() {
+        let mut every = Periodic::new(Duration::from_millis(50));
+        assert!(every.due());
+        assert!(!every.due());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(every.due());
+        assert!(!every.due());
+
+        let mut always = Periodic::new(Duration::ZERO);
+        assert!(always.due());
+        assert!(always.due());
+    }
+
+    #[test]
+    fn wrapped_titles_fit_and_keep_every_word() {
+        let title = "The Adventures of Sherlock Holmes";
+        assert_eq!(
+            wrap_title(title, 16),
+            ["The Adventures", "  of Sherlock", "  Holmes"]
+        );
+        assert_eq!(wrap_title(title, 80), [title]);
+        // words wider than the view are broken up
+        assert_eq!(
+            wrap_title("Supercalifragilistic", 8),
+            ["Supercal", "  ifragi", "  listic"]
+        );
+        assert_eq!(wrap_title("", 8), [""]);
+    }
+}